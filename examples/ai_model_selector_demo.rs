@@ -1,6 +1,5 @@
 use std::io::{self, Write};
 use serde_json::json;
-use reqwest;
 
 const BASE_URL: &str = "http://localhost:11434";
 
@@ -181,7 +180,7 @@ fn display_model_selector(models: &[serde_json::Value]) {
         let name = model["name"].as_str().unwrap_or(id);
         let description = model["description"].as_str().unwrap_or("");
         
-        println!("│ {:2}. {}", index + 1, format!("{:<20} {}", name, id));
+        println!("│ {:2}. {:<20} {}", index + 1, name, id);
         if !description.is_empty() {
             println!("│     └─ {}", description);
         }
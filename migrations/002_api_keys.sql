@@ -0,0 +1,16 @@
+-- Virtual API keys for LLM Link's own HTTP APIs
+CREATE TABLE IF NOT EXISTS api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(50) UNIQUE NOT NULL,
+    key_prefix VARCHAR(16) NOT NULL,
+    key_hash TEXT UNIQUE NOT NULL,
+    models TEXT NOT NULL DEFAULT '[]',
+    providers TEXT NOT NULL DEFAULT '[]',
+    frontends TEXT NOT NULL DEFAULT '[]',
+    expires_at TIMESTAMP,
+    enabled BOOLEAN DEFAULT true,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_enabled ON api_keys(enabled);
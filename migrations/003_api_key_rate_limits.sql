@@ -0,0 +1,4 @@
+-- Per-key rate limits; NULL falls back to the configured per-key default
+ALTER TABLE api_keys ADD COLUMN requests_per_minute INTEGER;
+ALTER TABLE api_keys ADD COLUMN tokens_per_minute INTEGER;
+ALTER TABLE api_keys ADD COLUMN max_concurrent_streams INTEGER;
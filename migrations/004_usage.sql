@@ -0,0 +1,18 @@
+-- One row per inference request
+CREATE TABLE IF NOT EXISTS usage (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key_name TEXT,
+    frontend VARCHAR(20) NOT NULL,
+    provider TEXT,
+    model TEXT,
+    prompt_tokens INTEGER NOT NULL DEFAULT 0,
+    completion_tokens INTEGER NOT NULL DEFAULT 0,
+    latency_ms INTEGER NOT NULL,
+    status INTEGER NOT NULL,
+    cost REAL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_created_at ON usage(created_at);
+CREATE INDEX IF NOT EXISTS idx_usage_key_name ON usage(key_name);
+CREATE INDEX IF NOT EXISTS idx_usage_model ON usage(model);
@@ -0,0 +1,19 @@
+-- Spend budgets on a virtual API key or a provider
+CREATE TABLE IF NOT EXISTS budgets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    api_key_id INTEGER REFERENCES api_keys(id) ON DELETE CASCADE,
+    provider_id INTEGER REFERENCES providers(id) ON DELETE CASCADE,
+    period VARCHAR(10) NOT NULL,
+    limit_usd REAL NOT NULL,
+    warn_threshold REAL,
+    auto_reset BOOLEAN NOT NULL DEFAULT true,
+    period_start TIMESTAMP NOT NULL,
+    warned_at TIMESTAMP,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((api_key_id IS NULL) != (provider_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_budgets_api_key_id ON budgets(api_key_id);
+CREATE INDEX IF NOT EXISTS idx_budgets_provider_id ON budgets(provider_id);
@@ -42,6 +42,15 @@ pub enum ClientAdapter {
 }
 
 impl ClientAdapter {
+    /// 适配器名称（用于 metrics 标签）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientAdapter::Standard => "standard",
+            ClientAdapter::Zed => "zed",
+            ClientAdapter::OpenAI => "openai",
+        }
+    }
+
     /// 获取该客户端的首选流式格式
     ///
     /// 当客户端没有明确指定 Accept 头（或使用 `*/*`）时，
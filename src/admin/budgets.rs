@@ -0,0 +1,224 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{SubsecRound, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::db::{Budget, BudgetPeriod, DatabasePool, NewBudget, UpdateBudget};
+
+#[derive(Debug, Serialize)]
+pub struct BudgetListResponse {
+    pub success: bool,
+    pub data: Option<Vec<Budget>>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BudgetResponse {
+    pub success: bool,
+    pub data: Option<Budget>,
+    pub message: String,
+}
+
+impl BudgetResponse {
+    fn failed(message: impl Into<String>) -> Json<Self> {
+        Json(Self {
+            success: false,
+            data: None,
+            message: message.into(),
+        })
+    }
+}
+
+/// Respond with the stored budget `id`
+async fn stored_budget(db_pool: &DatabasePool, id: i64, message: &str) -> Result<Json<BudgetResponse>, StatusCode> {
+    match db_pool.get_budget(id).await {
+        Ok(Some(budget)) => Ok(Json(BudgetResponse {
+            success: true,
+            data: Some(budget),
+            message: message.to_string(),
+        })),
+        Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            tracing::error!("Failed to retrieve budget: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBudgetRequest {
+    /// Exactly one of `api_key_id` and `provider_id` must be set
+    pub api_key_id: Option<i64>,
+    pub provider_id: Option<i64>,
+    pub period: BudgetPeriod,
+    pub limit_usd: f64,
+    pub warn_threshold: Option<f64>,
+    pub auto_reset: Option<bool>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateBudgetRequest {
+    pub period: Option<BudgetPeriod>,
+    pub limit_usd: Option<f64>,
+    /// `null` removes the threshold, a missing field keeps it
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub warn_threshold: Option<Option<f64>>,
+    pub auto_reset: Option<bool>,
+    pub enabled: Option<bool>,
+}
+
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+fn validate_amounts(limit_usd: Option<f64>, warn_threshold: Option<f64>) -> Result<(), String> {
+    if limit_usd.is_some_and(|limit| !limit.is_finite() || limit < 0.0) {
+        return Err("limit_usd must be zero or more".to_string());
+    }
+    if warn_threshold.is_some_and(|threshold| threshold.is_nan() || threshold <= 0.0 || threshold > 1.0) {
+        return Err("warn_threshold must be a fraction between 0 and 1, e.g. 0.8".to_string());
+    }
+    Ok(())
+}
+
+/// List all budgets with their current spend
+pub async fn list_budgets_api(
+    State(db_pool): State<DatabasePool>,
+) -> Result<Json<BudgetListResponse>, StatusCode> {
+    match db_pool.current_budgets(Utc::now()).await {
+        Ok(budgets) => Ok(Json(BudgetListResponse {
+            success: true,
+            data: Some(budgets),
+            message: "Budgets retrieved successfully".to_string(),
+        })),
+        Err(e) => {
+            tracing::error!("Failed to list budgets: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Get budget by ID
+pub async fn get_budget_api(
+    State(db_pool): State<DatabasePool>,
+    Path(id): Path<i64>,
+) -> Result<Json<BudgetResponse>, StatusCode> {
+    match db_pool.get_budget(id).await {
+        Ok(Some(budget)) => Ok(Json(BudgetResponse {
+            success: true,
+            data: Some(budget),
+            message: "Budget retrieved successfully".to_string(),
+        })),
+        Ok(None) => Ok(BudgetResponse::failed("Budget not found")),
+        Err(e) => {
+            tracing::error!("Failed to get budget: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Create a budget on a virtual API key or a provider
+pub async fn create_budget_api(
+    State(db_pool): State<DatabasePool>,
+    Json(request): Json<CreateBudgetRequest>,
+) -> Result<Json<BudgetResponse>, StatusCode> {
+    if request.api_key_id.is_some() == request.provider_id.is_some() {
+        return Ok(BudgetResponse::failed("Exactly one of api_key_id and provider_id is required"));
+    }
+    if let Err(message) = validate_amounts(Some(request.limit_usd), request.warn_threshold) {
+        return Ok(BudgetResponse::failed(message));
+    }
+
+    let new_budget = NewBudget {
+        api_key_id: request.api_key_id,
+        provider_id: request.provider_id,
+        period: request.period,
+        limit_usd: request.limit_usd,
+        warn_threshold: request.warn_threshold,
+        auto_reset: request.auto_reset.unwrap_or(true),
+        enabled: request.enabled.unwrap_or(true),
+    };
+
+    match db_pool.create_budget(new_budget).await {
+        Ok(budget_id) => {
+            tracing::info!("💰 Created budget {}", budget_id);
+            stored_budget(&db_pool, budget_id, "Budget created successfully").await
+        }
+        Err(e) => {
+            tracing::error!("Failed to create budget: {}", e);
+            Ok(BudgetResponse::failed(format!("Failed to create budget: {}", e)))
+        }
+    }
+}
+
+/// Update budget
+pub async fn update_budget_api(
+    State(db_pool): State<DatabasePool>,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateBudgetRequest>,
+) -> Result<Json<BudgetResponse>, StatusCode> {
+    if let Err(message) = validate_amounts(request.limit_usd, request.warn_threshold.flatten()) {
+        return Ok(BudgetResponse::failed(message));
+    }
+
+    let update = UpdateBudget {
+        period: request.period,
+        limit_usd: request.limit_usd,
+        warn_threshold: request.warn_threshold,
+        auto_reset: request.auto_reset,
+        enabled: request.enabled,
+    };
+
+    match db_pool.update_budget(id, update).await {
+        Ok(true) => stored_budget(&db_pool, id, "Budget updated successfully").await,
+        Ok(false) => Ok(BudgetResponse::failed("Budget not found or no changes made")),
+        Err(e) => {
+            tracing::error!("Failed to update budget: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Reset spend by starting a new period now
+pub async fn reset_budget_api(
+    State(db_pool): State<DatabasePool>,
+    Path(id): Path<i64>,
+) -> Result<Json<BudgetResponse>, StatusCode> {
+    match db_pool.reset_budget(id, Utc::now().trunc_subsecs(0)).await {
+        Ok(true) => {
+            tracing::info!("🔄 Reset budget {}", id);
+            stored_budget(&db_pool, id, "Budget reset successfully").await
+        }
+        Ok(false) => Ok(BudgetResponse::failed("Budget not found")),
+        Err(e) => {
+            tracing::error!("Failed to reset budget: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Delete budget
+pub async fn delete_budget_api(
+    State(db_pool): State<DatabasePool>,
+    Path(id): Path<i64>,
+) -> Result<Json<BudgetResponse>, StatusCode> {
+    match db_pool.delete_budget(id).await {
+        Ok(true) => Ok(Json(BudgetResponse {
+            success: true,
+            data: None,
+            message: "Budget deleted successfully".to_string(),
+        })),
+        Ok(false) => Ok(BudgetResponse::failed("Budget not found")),
+        Err(e) => {
+            tracing::error!("Failed to delete budget: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::api::auth::{generate_api_key, hash_api_key, Frontend, KEY_PREFIX};
+use crate::db::{ApiKey, DatabasePool, NewApiKey, UpdateApiKey};
+use crate::settings::RateLimit;
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyListResponse {
+    pub success: bool,
+    pub data: Option<Vec<ApiKey>>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub success: bool,
+    pub data: Option<ApiKey>,
+    /// Plaintext secret, only returned when the key is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl ApiKeyResponse {
+    fn failed(message: impl Into<String>) -> Json<Self> {
+        Json(Self {
+            success: false,
+            data: None,
+            key: None,
+            message: message.into(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(default)]
+    pub providers: Vec<String>,
+    #[serde(default)]
+    pub frontends: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub rate_limit: RateLimit,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateApiKeyRequest {
+    pub name: Option<String>,
+    pub models: Option<Vec<String>>,
+    pub providers: Option<Vec<String>>,
+    pub frontends: Option<Vec<String>>,
+    /// `null` removes the expiry (or a limit), a missing field keeps it
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    pub enabled: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub requests_per_minute: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub tokens_per_minute: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub max_concurrent_streams: Option<Option<u32>>,
+}
+
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Normalize frontend names, rejecting unknown ones
+fn parse_frontends(frontends: Vec<String>) -> Result<Vec<String>, String> {
+    frontends
+        .iter()
+        .map(|name| {
+            Frontend::parse(name)
+                .map(|frontend| frontend.as_str().to_string())
+                .ok_or_else(|| format!("Unknown frontend '{}' (expected openai, ollama or anthropic)", name))
+        })
+        .collect()
+}
+
+/// List all API keys
+pub async fn list_api_keys_api(
+    State(db_pool): State<DatabasePool>,
+) -> Result<Json<ApiKeyListResponse>, StatusCode> {
+    match db_pool.list_api_keys().await {
+        Ok(keys) => Ok(Json(ApiKeyListResponse {
+            success: true,
+            data: Some(keys),
+            message: "API keys retrieved successfully".to_string(),
+        })),
+        Err(e) => {
+            tracing::error!("Failed to list API keys: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Get API key by ID
+pub async fn get_api_key_api(
+    State(db_pool): State<DatabasePool>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiKeyResponse>, StatusCode> {
+    match db_pool.get_api_key(id).await {
+        Ok(Some(key)) => Ok(Json(ApiKeyResponse {
+            success: true,
+            data: Some(key),
+            key: None,
+            message: "API key retrieved successfully".to_string(),
+        })),
+        Ok(None) => Ok(ApiKeyResponse::failed("API key not found")),
+        Err(e) => {
+            tracing::error!("Failed to get API key: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Create new API key
+///
+/// The secret is generated here and returned once; only its hash is stored.
+pub async fn create_api_key_api(
+    State(db_pool): State<DatabasePool>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<Json<ApiKeyResponse>, StatusCode> {
+    if request.name.trim().is_empty() {
+        return Ok(ApiKeyResponse::failed("Name is required"));
+    }
+    let frontends = match parse_frontends(request.frontends) {
+        Ok(frontends) => frontends,
+        Err(message) => return Ok(ApiKeyResponse::failed(message)),
+    };
+
+    let secret = generate_api_key();
+    let new_key = NewApiKey {
+        name: request.name,
+        key_prefix: secret[..KEY_PREFIX.len() + 6].to_string(),
+        key_hash: hash_api_key(&secret),
+        models: request.models,
+        providers: request.providers,
+        frontends,
+        expires_at: request.expires_at,
+        enabled: request.enabled.unwrap_or(true),
+        rate_limit: request.rate_limit,
+    };
+
+    let key_id = match db_pool.create_api_key(new_key).await {
+        Ok(key_id) => key_id,
+        Err(e) => {
+            tracing::error!("Failed to create API key: {}", e);
+            return Ok(ApiKeyResponse::failed(format!("Failed to create API key: {}", e)));
+        }
+    };
+
+    match db_pool.get_api_key(key_id).await {
+        Ok(Some(key)) => {
+            tracing::info!("🔑 Created API key '{}'", key.name);
+            Ok(Json(ApiKeyResponse {
+                success: true,
+                data: Some(key),
+                key: Some(secret),
+                message: "API key created successfully; store the key now, it cannot be shown again".to_string(),
+            }))
+        }
+        Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            tracing::error!("Failed to retrieve created API key: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Update API key
+pub async fn update_api_key_api(
+    State(db_pool): State<DatabasePool>,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateApiKeyRequest>,
+) -> Result<Json<ApiKeyResponse>, StatusCode> {
+    let frontends = match request.frontends.map(parse_frontends).transpose() {
+        Ok(frontends) => frontends,
+        Err(message) => return Ok(ApiKeyResponse::failed(message)),
+    };
+
+    let update = UpdateApiKey {
+        name: request.name,
+        models: request.models,
+        providers: request.providers,
+        frontends,
+        expires_at: request.expires_at,
+        enabled: request.enabled,
+        requests_per_minute: request.requests_per_minute,
+        tokens_per_minute: request.tokens_per_minute,
+        max_concurrent_streams: request.max_concurrent_streams,
+    };
+
+    match db_pool.update_api_key(id, update).await {
+        Ok(true) => match db_pool.get_api_key(id).await {
+            Ok(Some(key)) => Ok(Json(ApiKeyResponse {
+                success: true,
+                data: Some(key),
+                key: None,
+                message: "API key updated successfully".to_string(),
+            })),
+            Ok(None) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            Err(e) => {
+                tracing::error!("Failed to retrieve updated API key: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+        Ok(false) => Ok(ApiKeyResponse::failed("API key not found or no changes made")),
+        Err(e) => {
+            tracing::error!("Failed to update API key: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Delete API key
+pub async fn delete_api_key_api(
+    State(db_pool): State<DatabasePool>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiKeyResponse>, StatusCode> {
+    match db_pool.delete_api_key(id).await {
+        Ok(true) => Ok(Json(ApiKeyResponse {
+            success: true,
+            data: None,
+            key: None,
+            message: "API key deleted successfully".to_string(),
+        })),
+        Ok(false) => Ok(ApiKeyResponse::failed("API key not found")),
+        Err(e) => {
+            tracing::error!("Failed to delete API key: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
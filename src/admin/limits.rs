@@ -0,0 +1,56 @@
+use axum::{extract::State, http::StatusCode, response::Json};
+use serde::Serialize;
+use crate::db::DatabasePool;
+use crate::settings::RateLimitSettings;
+
+/// Config key holding the global and per-model rate limits as JSON
+pub const RATE_LIMITS_CONFIG_KEY: &str = "rate_limits";
+
+#[derive(Debug, Serialize)]
+pub struct RateLimitsResponse {
+    pub success: bool,
+    pub data: Option<RateLimitSettings>,
+    pub message: String,
+}
+
+/// Get the global, per-key default and per-model rate limits
+pub async fn get_rate_limits_api(
+    State(db_pool): State<DatabasePool>,
+) -> Result<Json<RateLimitsResponse>, StatusCode> {
+    let value = db_pool.get_config(RATE_LIMITS_CONFIG_KEY).await.map_err(|e| {
+        tracing::error!("Failed to get rate limits: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let rate_limits = match value {
+        Some(value) => serde_json::from_str(&value).map_err(|e| {
+            tracing::error!("Invalid stored rate limits: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        None => RateLimitSettings::default(),
+    };
+
+    Ok(Json(RateLimitsResponse {
+        success: true,
+        data: Some(rate_limits),
+        message: "Rate limits retrieved successfully".to_string(),
+    }))
+}
+
+/// Replace the rate limits; the API server applies them immediately
+pub async fn update_rate_limits_api(
+    State(db_pool): State<DatabasePool>,
+    Json(rate_limits): Json<RateLimitSettings>,
+) -> Result<Json<RateLimitsResponse>, StatusCode> {
+    let value = serde_json::to_string(&rate_limits).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    match db_pool.set_config(RATE_LIMITS_CONFIG_KEY, &value).await {
+        Ok(()) => Ok(Json(RateLimitsResponse {
+            success: true,
+            data: Some(rate_limits),
+            message: "Rate limits updated successfully".to_string(),
+        })),
+        Err(e) => {
+            tracing::error!("Failed to update rate limits: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -1,8 +1,16 @@
 pub mod setup;
 pub mod handlers;
+pub mod keys;
+pub mod limits;
+pub mod usage;
+pub mod budgets;
 
 pub use setup::*;
 pub use handlers::*;
+pub use keys::*;
+pub use limits::*;
+pub use usage::*;
+pub use budgets::*;
 
 use axum::{Router, routing::{get, post}, response::Html};
 use crate::db::DatabasePool;
@@ -17,6 +25,13 @@ pub fn create_admin_app(db_pool: DatabasePool) -> Router {
         .route("/api/providers/:id", get(get_provider_api).put(update_provider_api).delete(delete_provider_api))
         .route("/api/providers/:id/toggle", post(toggle_provider_api))
         .route("/api/test-provider/:id", post(test_provider_api))
+        .route("/api/keys", get(list_api_keys_api).post(create_api_key_api))
+        .route("/api/keys/:id", get(get_api_key_api).put(update_api_key_api).delete(delete_api_key_api))
+        .route("/api/rate-limits", get(get_rate_limits_api).put(update_rate_limits_api))
+        .route("/api/usage/report", get(get_usage_report_api))
+        .route("/api/budgets", get(list_budgets_api).post(create_budget_api))
+        .route("/api/budgets/:id", get(get_budget_api).put(update_budget_api).delete(delete_budget_api))
+        .route("/api/budgets/:id/reset", post(reset_budget_api))
         .with_state(db_pool)
 }
 
@@ -0,0 +1,85 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::db::{DatabasePool, UsageGroup, UsageReportRow};
+
+#[derive(Debug, Deserialize)]
+pub struct UsageReportQuery {
+    /// Comma-separated dimensions: `day`, `key`, `model`, `provider` (default `day`)
+    pub by: Option<String>,
+    /// First day included, `YYYY-MM-DD` (UTC)
+    pub from: Option<String>,
+    /// Last day included, `YYYY-MM-DD` (UTC)
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReportResponse {
+    pub success: bool,
+    pub data: Option<Vec<UsageReportRow>>,
+    pub message: String,
+}
+
+impl UsageReportResponse {
+    fn failed(message: impl Into<String>) -> Json<Self> {
+        Json(Self {
+            success: false,
+            data: None,
+            message: message.into(),
+        })
+    }
+}
+
+fn parse_groups(by: Option<&str>) -> Result<Vec<UsageGroup>, String> {
+    let Some(by) = by.filter(|by| !by.trim().is_empty()) else {
+        return Ok(vec![UsageGroup::Day]);
+    };
+    by.split(',')
+        .map(|name| match name.trim().to_ascii_lowercase().as_str() {
+            "day" => Ok(UsageGroup::Day),
+            "key" => Ok(UsageGroup::Key),
+            "model" => Ok(UsageGroup::Model),
+            "provider" => Ok(UsageGroup::Provider),
+            other => Err(format!("Unknown grouping '{}' (expected day, key, model or provider)", other)),
+        })
+        .collect()
+}
+
+fn parse_day(day: Option<&str>) -> Result<Option<String>, String> {
+    day.map(|day| {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .map_err(|_| format!("Invalid date '{}' (expected YYYY-MM-DD)", day))
+    })
+    .transpose()
+}
+
+/// Aggregated usage by day, key, model and/or provider
+pub async fn get_usage_report_api(
+    State(db_pool): State<DatabasePool>,
+    Query(query): Query<UsageReportQuery>,
+) -> Result<Json<UsageReportResponse>, StatusCode> {
+    let parsed = parse_groups(query.by.as_deref()).and_then(|groups| {
+        Ok((groups, parse_day(query.from.as_deref())?, parse_day(query.to.as_deref())?))
+    });
+    let (groups, from, to) = match parsed {
+        Ok(parsed) => parsed,
+        Err(message) => return Ok(UsageReportResponse::failed(message)),
+    };
+
+    match db_pool.usage_report(&groups, from.as_deref(), to.as_deref()).await {
+        Ok(rows) => Ok(Json(UsageReportResponse {
+            success: true,
+            data: Some(rows),
+            message: "Usage report retrieved successfully".to_string(),
+        })),
+        Err(e) => {
+            tracing::error!("Failed to build usage report: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
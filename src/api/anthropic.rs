@@ -7,10 +7,16 @@ use axum::{
 use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::{error, info};
-
-use crate::api::AppState;
-use llm_connector::types::{ImageSource, Message as LlmMessage, MessageBlock, Role as LlmRole};
+use tracing::{error, info, warn};
+
+use crate::api::{AppState, error_status, with_provider_header, with_upstream_error};
+use crate::normalizer::{ChatOptions, OutputFormat};
+use crate::tokenizer::counter_for_model;
+use crate::settings::ReasoningMode;
+use llm_connector::types::{
+    Function, FunctionCall, ImageSource, Message as LlmMessage, MessageBlock, Role as LlmRole, Tool, ToolCall,
+    ToolChoice,
+};
 
 /// Anthropic Messages API Request
 #[derive(Debug, Deserialize, Serialize)]
@@ -29,13 +35,92 @@ pub struct AnthropicMessagesRequest {
     #[serde(default)]
     #[allow(dead_code)]
     pub stream: bool,
+    #[serde(default)]
+    pub tools: Option<Vec<AnthropicTool>>,
+    #[serde(default)]
+    pub tool_choice: Option<AnthropicToolChoice>,
+    #[serde(default)]
+    pub system: Option<AnthropicSystem>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub top_k: Option<u32>,
+    #[serde(default)]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(default)]
+    pub metadata: Option<AnthropicMetadata>,
+    #[serde(default)]
+    pub output_format: Option<serde_json::Value>,
+    #[serde(default)]
+    pub thinking: Option<AnthropicThinking>,
+}
+
+/// Anthropic `system` prompt: a plain string or an array of text blocks
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum AnthropicSystem {
+    Text(String),
+    Blocks(Vec<AnthropicContentBlock>),
+}
+
+impl AnthropicSystem {
+    /// Join the system prompt into a single string
+    pub fn text(&self) -> String {
+        match self {
+            AnthropicSystem::Text(text) => text.clone(),
+            AnthropicSystem::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    AnthropicContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Anthropic request metadata
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnthropicMetadata {
+    #[serde(default)]
+    pub user_id: Option<String>,
+}
+
+/// Anthropic extended thinking: `{"type": "enabled", "budget_tokens": 1024}` or `{"type": "disabled"}`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnthropicThinking {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub budget_tokens: Option<u32>,
+}
+
+/// Anthropic tool definition
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnthropicTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub input_schema: serde_json::Value,
+}
+
+/// Anthropic tool_choice: `auto`, `any`, `tool` (with a name) or `none`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicToolChoice {
+    Auto,
+    Any,
+    Tool { name: String },
+    None,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AnthropicMessage {
     pub role: String,
     #[serde(deserialize_with = "deserialize_content")]
-    pub content: Vec<MessageBlock>,
+    pub content: Vec<AnthropicContentBlock>,
 }
 
 /// Anthropic content can be either a string or an array of content blocks
@@ -46,18 +131,36 @@ enum AnthropicContentInput {
     Array(Vec<AnthropicContentBlock>),
 }
 
-#[derive(Debug, Deserialize)]
-struct AnthropicContentBlock {
-    #[serde(rename = "type")]
-    type_: String,
-    #[serde(default)]
-    text: Option<String>,
-    #[serde(default)]
-    source: Option<AnthropicImageSource>,
+/// Anthropic request content block
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: AnthropicImageSource,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        #[serde(default)]
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        /// Either a string or an array of content blocks
+        #[serde(default)]
+        content: serde_json::Value,
+        #[serde(default)]
+        is_error: Option<bool>,
+    },
+    #[serde(other)]
+    Unsupported,
 }
 
-#[derive(Debug, Deserialize)]
-struct AnthropicImageSource {
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnthropicImageSource {
     #[serde(rename = "type")]
     #[allow(dead_code)]
     type_: String,
@@ -66,44 +169,136 @@ struct AnthropicImageSource {
 }
 
 /// Custom deserializer for content field
-/// Converts Anthropic format to llm-connector MessageBlock format
-fn deserialize_content<'de, D>(deserializer: D) -> Result<Vec<MessageBlock>, D::Error>
+/// Accepts a plain string or an array of content blocks
+fn deserialize_content<'de, D>(deserializer: D) -> Result<Vec<AnthropicContentBlock>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let content = AnthropicContentInput::deserialize(deserializer)?;
     match content {
-        AnthropicContentInput::String(s) => {
-            // Simple string → single text block
-            Ok(vec![MessageBlock::Text { text: s }])
-        }
-        AnthropicContentInput::Array(blocks) => {
-            // Array of blocks → convert each block
-            let message_blocks: Vec<MessageBlock> = blocks
-                .into_iter()
-                .filter_map(|block| match block.type_.as_str() {
-                    "text" => block.text.map(|text| MessageBlock::Text { text }),
-                    "image" => block.source.map(|source| MessageBlock::Image {
-                        source: ImageSource::Base64 {
-                            media_type: source.media_type,
-                            data: source.data,
-                        },
-                    }),
-                    _ => {
-                        tracing::warn!("⚠️ Unsupported content block type: {}", block.type_);
-                        None
+        // Simple string → single text block
+        AnthropicContentInput::String(s) => Ok(vec![AnthropicContentBlock::Text { text: s }]),
+        AnthropicContentInput::Array(blocks) => Ok(blocks),
+    }
+}
+
+/// Marks the content of a `tool_result` sent with `is_error: true`
+const TOOL_ERROR_PREFIX: &str = "Tool call failed: ";
+
+/// Extract the text of a tool_result `content` (string or array of blocks)
+fn tool_result_text(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Convert Anthropic messages to llm-connector messages
+///
+/// `tool_use` blocks become assistant `tool_calls`, and each `tool_result`
+/// block becomes a separate `tool` message placed before the rest of the
+/// user turn, which is the shape OpenAI-compatible providers expect.
+pub fn anthropic_messages_to_llm(messages: Vec<AnthropicMessage>) -> Vec<LlmMessage> {
+    let mut llm_messages = Vec::with_capacity(messages.len());
+
+    for msg in messages {
+        let role = match msg.role.as_str() {
+            "user" => LlmRole::User,
+            "assistant" => LlmRole::Assistant,
+            "system" => LlmRole::System,
+            _ => LlmRole::User, // Default to user
+        };
+
+        let mut blocks = Vec::new();
+        let mut tool_calls = Vec::new();
+
+        for block in msg.content {
+            match block {
+                AnthropicContentBlock::Text { text } => blocks.push(MessageBlock::Text { text }),
+                AnthropicContentBlock::Image { source } => blocks.push(MessageBlock::Image {
+                    source: ImageSource::Base64 {
+                        media_type: source.media_type,
+                        data: source.data,
+                    },
+                }),
+                AnthropicContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                    id,
+                    call_type: "function".to_string(),
+                    function: FunctionCall {
+                        name,
+                        arguments: input.to_string(),
+                    },
+                    index: None,
+                }),
+                AnthropicContentBlock::ToolResult { tool_use_id, content, is_error } => {
+                    // tool 消息没有错误标志，只能写进正文让模型看到
+                    let mut text = tool_result_text(&content);
+                    if is_error == Some(true) {
+                        text = format!("{}{}", TOOL_ERROR_PREFIX, text);
                     }
-                })
-                .collect();
-
-            if message_blocks.is_empty() {
-                Ok(vec![MessageBlock::Text {
-                    text: String::new(),
-                }])
-            } else {
-                Ok(message_blocks)
+                    llm_messages.push(LlmMessage {
+                        role: LlmRole::Tool,
+                        content: vec![MessageBlock::Text { text }],
+                        tool_call_id: Some(tool_use_id),
+                        ..Default::default()
+                    });
+                }
+                AnthropicContentBlock::Unsupported => {
+                    tracing::warn!("⚠️ Unsupported content block type in {} message", msg.role);
+                }
             }
         }
+
+        if blocks.is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+        if blocks.is_empty() {
+            blocks.push(MessageBlock::Text { text: String::new() });
+        }
+
+        llm_messages.push(LlmMessage {
+            role,
+            content: blocks,
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+            ..Default::default()
+        });
+    }
+
+    llm_messages
+}
+
+/// Convert Anthropic tool definitions to llm-connector tools
+pub fn anthropic_tools_to_llm(tools: Vec<AnthropicTool>) -> Vec<Tool> {
+    tools
+        .into_iter()
+        .map(|tool| Tool {
+            tool_type: "function".to_string(),
+            function: Function {
+                name: tool.name,
+                description: tool.description,
+                parameters: if tool.input_schema.is_null() {
+                    json!({"type": "object", "properties": {}})
+                } else {
+                    tool.input_schema
+                },
+            },
+        })
+        .collect()
+}
+
+/// Convert Anthropic tool_choice to the OpenAI-style choice used upstream
+pub fn anthropic_tool_choice_to_llm(choice: AnthropicToolChoice) -> ToolChoice {
+    match choice {
+        AnthropicToolChoice::Auto => ToolChoice::auto(),
+        AnthropicToolChoice::Any => ToolChoice::required(),
+        AnthropicToolChoice::Tool { name } => ToolChoice::function(name),
+        AnthropicToolChoice::None => ToolChoice::none(),
     }
 }
 
@@ -118,15 +313,27 @@ pub struct AnthropicMessagesResponse {
     pub content: Vec<AnthropicContent>,
     pub model: String,
     pub stop_reason: Option<String>,
+    pub stop_sequence: Option<String>,
     pub usage: AnthropicUsage,
 }
 
+/// Anthropic response content block
 #[derive(Debug, Serialize)]
-#[allow(dead_code)]
-pub struct AnthropicContent {
-    #[serde(rename = "type")]
-    pub type_: String,
-    pub text: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContent {
+    Text {
+        text: String,
+    },
+    Thinking {
+        thinking: String,
+        /// Upstream providers do not sign their reasoning, so this is empty
+        signature: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -136,6 +343,79 @@ pub struct AnthropicUsage {
     pub output_tokens: u32,
 }
 
+/// Build response content blocks from the text and OpenAI-style tool calls
+fn response_content(content: String, reasoning: Option<String>, tool_calls: Option<&serde_json::Value>) -> Vec<AnthropicContent> {
+    let mut blocks = Vec::new();
+    if let Some(thinking) = reasoning {
+        blocks.push(AnthropicContent::Thinking { thinking, signature: String::new() });
+    }
+    if !content.is_empty() {
+        blocks.push(AnthropicContent::Text { text: content });
+    }
+
+    for call in tool_calls.and_then(|calls| calls.as_array()).into_iter().flatten() {
+        let arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+        blocks.push(AnthropicContent::ToolUse {
+            id: call["id"].as_str().map(str::to_string).unwrap_or_else(new_tool_use_id),
+            name: call["function"]["name"].as_str().unwrap_or_default().to_string(),
+            input: serde_json::from_str(arguments).unwrap_or_else(|_| json!({})),
+        });
+    }
+
+    if blocks.is_empty() {
+        blocks.push(AnthropicContent::Text { text: String::new() });
+    }
+    blocks
+}
+
+/// Map an upstream finish reason to Anthropic `stop_reason` / `stop_sequence`
+///
+/// OpenAI-compatible providers report a stop sequence hit as a plain "stop",
+/// so `stop_sequence` is only reported when the provider says so, and the
+/// sequence itself only when the request had just one.
+fn anthropic_stop_reason(
+    finish_reason: Option<&str>,
+    has_tool_calls: bool,
+    stop_sequences: &[String],
+) -> (&'static str, Option<String>) {
+    if has_tool_calls {
+        return ("tool_use", None);
+    }
+    match finish_reason {
+        Some("tool_calls") | Some("tool_use") | Some("function_call") => ("tool_use", None),
+        Some("length") | Some("max_tokens") => ("max_tokens", None),
+        Some("stop_sequence") => match stop_sequences {
+            [only] => ("stop_sequence", Some(only.clone())),
+            _ => ("stop_sequence", None),
+        },
+        _ => ("end_turn", None),
+    }
+}
+
+fn new_tool_use_id() -> String {
+    format!("toolu_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Error response in the Anthropic `{"type": "error", ...}` shape
+pub(crate) fn anthropic_error(status: StatusCode, message: String) -> Response {
+    let error_type = match status {
+        StatusCode::BAD_REQUEST => "invalid_request_error",
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::PAYMENT_REQUIRED => "billing_error",
+        StatusCode::FORBIDDEN => "permission_error",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limit_error",
+        _ => "api_error",
+    };
+    let body = json!({
+        "type": "error",
+        "error": {
+            "type": error_type,
+            "message": message,
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
 /// Anthropic Messages API Handler
 #[allow(dead_code)]
 pub async fn messages(
@@ -146,28 +426,7 @@ pub async fn messages(
     info!("📨 Anthropic Messages API request: client_model={}, stream={}", request.model, request.stream);
     info!("📋 Request details: messages_count={}, max_tokens={:?}, temperature={:?}",
           request.messages.len(), request.max_tokens, request.temperature);
-    
-    // Debug: Check Authorization header
-    if let Some(auth_header) = headers.get("authorization") {
-        info!("🔍 DEBUG: Client Authorization header: {:?}", auth_header);
-    } else {
-        info!("🔍 DEBUG: No Authorization header from client");
-    }
-    
-    // Debug: Log request size and first message content for debugging
-    let request_json = serde_json::to_string(&request).unwrap_or_default();
-    info!("🔍 DEBUG: Request size: {} bytes", request_json.len());
-    if !request.messages.is_empty() {
-        if let Some(first_content) = request.messages[0].content.get(0) {
-            match first_content {
-                MessageBlock::Text { text } => {
-                    let preview = if text.len() > 200 { &text[..200] } else { text };
-                    info!("🔍 DEBUG: First message preview: {}", preview);
-                }
-                _ => info!("🔍 DEBUG: First message is not text"),
-            }
-        }
-    }
+    // 请求内容只写入审计日志（--audit-log），不在普通日志中输出
 
     // Check if client expects streaming via Accept header
     // Some clients (like Claude Code) may indicate streaming preference via headers
@@ -183,93 +442,96 @@ pub async fn messages(
 
     info!("📋 Final streaming mode: {}", request.stream);
 
-    // Convert Anthropic messages to llm-connector format
-    let llm_messages: Vec<LlmMessage> = request
-        .messages
-        .into_iter()
-        .map(|msg| {
-            let role = match msg.role.as_str() {
-                "user" => LlmRole::User,
-                "assistant" => LlmRole::Assistant,
-                "system" => LlmRole::System,
-                _ => LlmRole::User, // Default to user
-            };
-
-            LlmMessage {
-                role,
-                content: msg.content,
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-                reasoning_content: None,
-                reasoning: None,
-                thought: None,
-                thinking: None,
-            }
-        })
-        .collect();
+    // Convert Anthropic messages and tools to llm-connector format
+    let mut llm_messages = Vec::with_capacity(request.messages.len() + 1);
+    if let Some(system) = request.system.take().map(|system| system.text()).filter(|text| !text.is_empty()) {
+        llm_messages.push(LlmMessage::system(system));
+    }
+    llm_messages.extend(anthropic_messages_to_llm(std::mem::take(&mut request.messages)));
+    let tools = request.tools.take().map(anthropic_tools_to_llm).filter(|tools| !tools.is_empty());
+    let stop_sequences = request.stop_sequences.take().unwrap_or_default();
+    // thinking 参数决定是否返回 thinking 块，未指定时使用应用配置
+    let thinking = request.thinking.take().map(|thinking| thinking.type_ == "enabled");
+    let reasoning = match thinking {
+        Some(true) => ReasoningMode::Native,
+        Some(false) => ReasoningMode::Strip,
+        None => state.config.read().await.reasoning_mode(),
+    };
+    let options = ChatOptions {
+        tool_choice: request.tool_choice.take().map(anthropic_tool_choice_to_llm),
+        temperature: request.temperature,
+        top_p: request.top_p,
+        max_tokens: request.max_tokens,
+        stop: Some(stop_sequences.clone()),
+        user: request.metadata.take().and_then(|metadata| metadata.user_id),
+        response_format: request.output_format.as_ref().and_then(OutputFormat::from_anthropic),
+        reasoning,
+        enable_thinking: thinking,
+        // 流式响应需要最终的 usage chunk 来填充 message_delta
+        include_usage: true,
+        ..Default::default()
+    };
+    if let Some(top_k) = request.top_k {
+        // ChatRequest has no top_k field, so it cannot be forwarded upstream
+        warn!("⚠️ top_k={} is not supported by upstream providers, ignoring", top_k);
+    }
+    if let Some(tools) = &tools {
+        info!("🔧 Request includes {} tools", tools.len());
+    }
 
     if request.stream {
         // Streaming response
-        let llm_service = state.llm_service.read().await;
-        let config = state.config.read().await;
-        // Use configured model instead of client model to avoid mapping issues
-        let configured_model = match &config.llm_backend {
-            crate::settings::LlmBackendSettings::Aliyun { model, .. } => model,
-            _ => &request.model,
-        };
-        info!("🔧 DEBUG: Using model for streaming: {} (client requested: {})", configured_model, request.model);
-        let stream_result = llm_service.chat_stream_openai(Some(configured_model), llm_messages, None, llm_connector::StreamFormat::SSE).await;
+        let llm_service = state.service().await;
+        let upstream_model = llm_service.resolve_model(Some(&request.model)).unwrap_or_else(|| request.model.clone());
+        let input_estimate = counter_for_model(&upstream_model).count_request(&llm_messages, tools.as_deref());
+        let stream_result = llm_service.chat_stream_openai(Some(&request.model), llm_messages, tools, &options, llm_connector::StreamFormat::SSE).await;
 
         match stream_result {
-            Ok(stream) => {
-                info!("✅ Starting Anthropic streaming response");
-                let anthropic_stream = convert_to_anthropic_stream(stream, request.model.clone());
+            Ok(served) => {
+                info!("✅ Starting Anthropic streaming response (provider: {})", served.provider);
+                let anthropic_stream = convert_to_anthropic_stream(served.output, request.model.clone(), stop_sequences, input_estimate);
                 let response = Sse::new(anthropic_stream).into_response();
                 // Add required Anthropic API headers
-                let mut response = response;
+                let mut response = with_provider_header(response, &served.provider);
                 response.headers_mut().insert("anthropic-version", "2023-06-01".parse().unwrap());
                 response.headers_mut().insert("request-id", uuid::Uuid::new_v4().to_string().parse().unwrap());
                 Ok(response)
             }
             Err(e) => {
                 error!("❌ Streaming error: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Ok(with_upstream_error(anthropic_error(error_status(&e), e.to_string()), &e))
             }
         }
     } else {
         // Non-streaming response
-        let llm_service = state.llm_service.read().await;
-        let config = state.config.read().await;
-        // Use configured model instead of client model to avoid mapping issues
-        let configured_model = match &config.llm_backend {
-            crate::settings::LlmBackendSettings::Aliyun { model, .. } => model,
-            _ => &request.model,
-        };
-        info!("🔧 DEBUG: Using model: {} (client requested: {})", configured_model, request.model);
-        let chat_result = llm_service.chat(Some(configured_model), llm_messages, None).await;
+        let llm_service = state.service().await;
+        let chat_result = llm_service.chat(Some(&request.model), llm_messages, tools, &options).await;
 
         match chat_result {
-            Ok(response) => {
-                info!("✅ Anthropic non-streaming response successful");
+            Ok(served) => {
+                info!("✅ Anthropic non-streaming response successful (provider: {})", served.provider);
+                let response = served.output;
+                let (stop_reason, stop_sequence) = anthropic_stop_reason(
+                    response.finish_reason.as_deref(),
+                    response.tool_calls.is_some(),
+                    &stop_sequences,
+                );
 
                 let anthropic_response = AnthropicMessagesResponse {
                     id: uuid::Uuid::new_v4().to_string(),
                     type_: "message".to_string(),
                     role: "assistant".to_string(),
-                    content: vec![AnthropicContent {
-                        type_: "text".to_string(),
-                        text: response.content,
-                    }],
+                    content: response_content(response.content, response.reasoning, response.tool_calls.as_ref()),
                     model: request.model,
-                    stop_reason: Some("end_turn".to_string()),
+                    stop_reason: Some(stop_reason.to_string()),
+                    stop_sequence,
                     usage: AnthropicUsage {
                         input_tokens: response.usage.prompt_tokens,
                         output_tokens: response.usage.completion_tokens,
                     },
                 };
 
-                let mut response = Json(anthropic_response).into_response();
+                let mut response = with_provider_header(Json(anthropic_response).into_response(), &served.provider);
                 // Add required Anthropic API headers for non-streaming responses
                 response.headers_mut().insert("anthropic-version", "2023-06-01".parse().unwrap());
                 response.headers_mut().insert("request-id", uuid::Uuid::new_v4().to_string().parse().unwrap());
@@ -277,7 +539,7 @@ pub async fn messages(
             }
             Err(e) => {
                 error!("❌ Chat error: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Ok(with_upstream_error(anthropic_error(error_status(&e), e.to_string()), &e))
             }
         }
     }
@@ -288,124 +550,244 @@ pub async fn messages(
 fn convert_to_anthropic_stream(
     stream: tokio_stream::wrappers::UnboundedReceiverStream<String>,
     model: String,
+    stop_sequences: Vec<String>,
+    input_tokens: u32,
 ) -> impl Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>> {
     use futures_util::{StreamExt, stream};
-    
-    let message_id = uuid::Uuid::new_v4().to_string();
-    let model_clone = model.clone();
-
-    // Create message_start event
-    let start_event = json!({
-        "type": "message_start",
-        "message": {
-            "id": message_id,
-            "type": "message",
-            "role": "assistant",
-            "content": [],
-            "model": model_clone,
-            "stop_reason": null,
-            "stop_sequence": null,
-            "usage": {
-                "input_tokens": 0,
-                "output_tokens": 0
-            }
-        }
-    });
 
-    // Create content_block_start event
-    let block_start_event = json!({
-        "type": "content_block_start",
-        "index": 0,
-        "content_block": {
-            "type": "text",
-            "text": ""
-        }
-    });
+    let mut converter = AnthropicStreamConverter::new(model, stop_sequences, input_tokens);
+
+    // None marks the end of the upstream stream so the converter can close
+    // the open block and emit message_delta / message_stop
+    stream
+        .map(Some)
+        .chain(stream::once(async { None }))
+        .flat_map(move |data| {
+            let events = match data {
+                Some(data) => converter.process(&data),
+                None => converter.finish(),
+            };
+            stream::iter(events.into_iter().map(|(name, event)| {
+                Ok(axum::response::sse::Event::default()
+                    .event(name)
+                    .data(event.to_string()))
+            }))
+        })
+}
 
-    // Process main stream
-    let content_stream = stream.map(move |data| {
-        // Parse the SSE data
-        let json_str = if data.starts_with("data: ") {
-            &data[6..]
-        } else {
-            &data
-        };
+/// Stateful OpenAI chunk → Anthropic event converter
+///
+/// Anthropic streams content as indexed blocks, so text and each tool call
+/// get their own `content_block_start` / `content_block_stop` pair.
+struct AnthropicStreamConverter {
+    message_id: String,
+    model: String,
+    started: bool,
+    finished: bool,
+    /// Next Anthropic block index
+    next_index: usize,
+    /// Currently open block: (anthropic index, kind)
+    open_block: Option<(usize, OpenBlock)>,
+    finish_reason: Option<String>,
+    has_tool_calls: bool,
+    stop_sequences: Vec<String>,
+    /// Estimated up front, replaced by upstream usage when it arrives
+    input_tokens: u64,
+    output_tokens: u64,
+}
 
-        // Skip empty lines and [DONE] markers
-        if json_str.trim().is_empty() || json_str.trim() == "[DONE]" {
-            let stop_event = json!({
-                "type": "message_stop"
-            });
-            return Ok(axum::response::sse::Event::default()
-                .event("message_stop")
-                .data(stop_event.to_string()));
+#[derive(Debug, PartialEq)]
+enum OpenBlock {
+    Thinking,
+    Text,
+    /// Tool call keyed by the upstream OpenAI tool_call index
+    ToolUse(u64),
+}
+
+impl AnthropicStreamConverter {
+    fn new(model: String, stop_sequences: Vec<String>, input_tokens: u32) -> Self {
+        Self {
+            message_id: format!("msg_{}", uuid::Uuid::new_v4().simple()),
+            model,
+            started: false,
+            finished: false,
+            next_index: 0,
+            open_block: None,
+            finish_reason: None,
+            has_tool_calls: false,
+            stop_sequences,
+            input_tokens: input_tokens.into(),
+            output_tokens: 0,
         }
+    }
+
+    /// Process one message from the OpenAI SSE stream (may hold several `data:` lines)
+    fn process(&mut self, data: &str) -> Vec<(&'static str, serde_json::Value)> {
+        let mut events = self.start();
+
+        for line in data.lines() {
+            let json_str = line.strip_prefix("data:").unwrap_or(line).trim();
+            if json_str.is_empty() || json_str == "[DONE]" {
+                continue;
+            }
+            let Ok(chunk) = serde_json::from_str::<serde_json::Value>(json_str) else {
+                continue;
+            };
+
+            // 上游中途失败：发送 Anthropic error 事件，不再发 message_stop
+            if let Some(error) = chunk.get("error").filter(|error| !error.is_null()) {
+                let message = error["message"].as_str().unwrap_or("Upstream stream failed");
+                events.push(("error", json!({
+                    "type": "error",
+                    "error": {"type": "api_error", "message": message}
+                })));
+                self.finished = true;
+                return events;
+            }
 
-        // Try to parse as OpenAI chunk
-        if let Ok(chunk) = serde_json::from_str::<serde_json::Value>(json_str) {
-            if let Some(content) = chunk["choices"][0]["delta"]["content"].as_str() {
-                let event = json!({
-                    "type": "content_block_delta",
-                    "index": 0,
-                    "delta": {
-                        "type": "text_delta",
-                        "text": content
+            if let Some(tokens) = chunk["usage"]["prompt_tokens"].as_u64() {
+                self.input_tokens = tokens;
+            }
+            if let Some(tokens) = chunk["usage"]["completion_tokens"].as_u64() {
+                self.output_tokens = tokens;
+            }
+
+            let choice = &chunk["choices"][0];
+            let delta = &choice["delta"];
+
+            if let Some(reasoning) = delta["reasoning_content"].as_str() {
+                if !reasoning.is_empty() {
+                    if self.open_block.as_ref().map(|(_, kind)| kind) != Some(&OpenBlock::Thinking) {
+                        events.extend(self.close_block());
+                        events.push(self.open(OpenBlock::Thinking, json!({"type": "thinking", "thinking": ""})));
+                    }
+                    events.push(self.delta(json!({"type": "thinking_delta", "thinking": reasoning})));
+                }
+            }
+
+            if let Some(content) = delta["content"].as_str() {
+                if !content.is_empty() {
+                    if self.open_block.as_ref().map(|(_, kind)| kind) != Some(&OpenBlock::Text) {
+                        events.extend(self.close_block());
+                        events.push(self.open(OpenBlock::Text, json!({"type": "text", "text": ""})));
                     }
-                });
-                return Ok(axum::response::sse::Event::default()
-                    .event("content_block_delta")
-                    .data(event.to_string()));
+                    events.push(self.delta(json!({"type": "text_delta", "text": content})));
+                }
+            }
+
+            for call in delta["tool_calls"].as_array().into_iter().flatten() {
+                let call_index = call["index"].as_u64().unwrap_or(0);
+                self.has_tool_calls = true;
+                if self.open_block.as_ref().map(|(_, kind)| kind) != Some(&OpenBlock::ToolUse(call_index)) {
+                    events.extend(self.close_block());
+                    let id = call["id"].as_str().map(str::to_string).unwrap_or_else(new_tool_use_id);
+                    let name = call["function"]["name"].as_str().unwrap_or_default();
+                    events.push(self.open(
+                        OpenBlock::ToolUse(call_index),
+                        json!({"type": "tool_use", "id": id, "name": name, "input": {}}),
+                    ));
+                }
+                if let Some(arguments) = call["function"]["arguments"].as_str() {
+                    if !arguments.is_empty() {
+                        events.push(self.delta(json!({"type": "input_json_delta", "partial_json": arguments})));
+                    }
+                }
+            }
+
+            // upstream_finish_reason 是规范化之前的原因，stop_sequence 只能从它得知
+            if let Some(reason) = choice["upstream_finish_reason"].as_str().or(choice["finish_reason"].as_str()) {
+                self.finish_reason = Some(reason.to_string());
             }
         }
 
-        // If parsing fails, return empty event
-        Ok(axum::response::sse::Event::default().data(""))
-    });
+        events
+    }
 
-    // Chain all events together: start -> block_start -> content -> stop
-    stream::once(async move {
-        Ok(axum::response::sse::Event::default()
-            .event("message_start")
-            .data(start_event.to_string()))
-    })
-    .chain(stream::once(async move {
-        Ok(axum::response::sse::Event::default()
-            .event("content_block_start")
-            .data(block_start_event.to_string()))
-    }))
-    .chain(content_stream)
-    .chain(stream::once(async move {
-        let block_stop_event = json!({
-            "type": "content_block_stop",
-            "index": 0
-        });
-        Ok(axum::response::sse::Event::default()
-            .event("content_block_stop")
-            .data(block_stop_event.to_string()))
-    }))
-    .chain(stream::once(async move {
-        let message_delta_event = json!({
+    /// Close the open block and emit the closing message events
+    fn finish(&mut self) -> Vec<(&'static str, serde_json::Value)> {
+        let mut events = self.start();
+        if self.finished {
+            return events;
+        }
+        self.finished = true;
+
+        // Anthropic clients expect at least one content block
+        if self.next_index == 0 {
+            events.push(self.open(OpenBlock::Text, json!({"type": "text", "text": ""})));
+        }
+        events.extend(self.close_block());
+
+        let (stop_reason, stop_sequence) = anthropic_stop_reason(
+            self.finish_reason.as_deref(),
+            self.has_tool_calls,
+            &self.stop_sequences,
+        );
+        events.push(("message_delta", json!({
             "type": "message_delta",
             "delta": {
-                "stop_reason": "end_turn",
-                "stop_sequence": null
+                "stop_reason": stop_reason,
+                "stop_sequence": stop_sequence
             },
             "usage": {
-                "output_tokens": 0
+                "input_tokens": self.input_tokens,
+                "output_tokens": self.output_tokens
             }
-        });
-        Ok(axum::response::sse::Event::default()
-            .event("message_delta")
-            .data(message_delta_event.to_string()))
-    }))
-    .chain(stream::once(async move {
-        let stop_event = json!({
-            "type": "message_stop"
-        });
-        Ok(axum::response::sse::Event::default()
-            .event("message_stop")
-            .data(stop_event.to_string()))
-    }))
+        })));
+        events.push(("message_stop", json!({"type": "message_stop"})));
+        events
+    }
+
+    fn start(&mut self) -> Vec<(&'static str, serde_json::Value)> {
+        if self.started {
+            return Vec::new();
+        }
+        self.started = true;
+        vec![("message_start", json!({
+            "type": "message_start",
+            "message": {
+                "id": self.message_id,
+                "type": "message",
+                "role": "assistant",
+                "content": [],
+                "model": self.model,
+                "stop_reason": null,
+                "stop_sequence": null,
+                "usage": {
+                    "input_tokens": self.input_tokens,
+                    "output_tokens": 0
+                }
+            }
+        }))]
+    }
+
+    fn open(&mut self, kind: OpenBlock, content_block: serde_json::Value) -> (&'static str, serde_json::Value) {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.open_block = Some((index, kind));
+        ("content_block_start", json!({
+            "type": "content_block_start",
+            "index": index,
+            "content_block": content_block
+        }))
+    }
+
+    fn delta(&self, delta: serde_json::Value) -> (&'static str, serde_json::Value) {
+        let index = self.open_block.as_ref().map(|(index, _)| *index).unwrap_or(0);
+        ("content_block_delta", json!({
+            "type": "content_block_delta",
+            "index": index,
+            "delta": delta
+        }))
+    }
+
+    fn close_block(&mut self) -> Option<(&'static str, serde_json::Value)> {
+        self.open_block.take().map(|(index, _)| {
+            ("content_block_stop", json!({
+                "type": "content_block_stop",
+                "index": index
+            }))
+        })
+    }
 }
 
 /// Anthropic Models API (占位符)
@@ -415,7 +797,7 @@ fn convert_to_anthropic_stream(
 pub async fn models(
     State(state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
-    let llm_service = state.llm_service.read().await;
+    let llm_service = state.service().await;
     let models_result = llm_service.list_models().await;
 
     match models_result {
@@ -429,19 +811,7 @@ pub async fn models(
                 })
             }).collect();
 
-            let config = state.config.read().await;
-            let current_provider = match &config.llm_backend {
-                crate::settings::LlmBackendSettings::OpenAI { .. } => "openai",
-                crate::settings::LlmBackendSettings::Anthropic { .. } => "anthropic",
-                crate::settings::LlmBackendSettings::Zhipu { .. } => "zhipu",
-                crate::settings::LlmBackendSettings::Ollama { .. } => "ollama",
-                crate::settings::LlmBackendSettings::Aliyun { .. } => "aliyun",
-                crate::settings::LlmBackendSettings::Volcengine { .. } => "volcengine",
-                crate::settings::LlmBackendSettings::Tencent { .. } => "tencent",
-                crate::settings::LlmBackendSettings::Longcat { .. } => "longcat",
-                crate::settings::LlmBackendSettings::Moonshot { .. } => "moonshot",
-                crate::settings::LlmBackendSettings::Minimax { .. } => "minimax",
-            };
+            let current_provider = state.config.read().await.llm_backend.provider_name();
 
             let response = json!({
                 "data": anthropic_models,
@@ -453,30 +823,48 @@ pub async fn models(
     }
 }
 
+/// Anthropic Count Tokens API request
+#[derive(Debug, Deserialize)]
+pub struct AnthropicCountTokensRequest {
+    #[serde(default)]
+    pub model: String,
+    pub messages: Vec<AnthropicMessage>,
+    #[serde(default)]
+    pub system: Option<AnthropicSystem>,
+    #[serde(default)]
+    pub tools: Option<Vec<AnthropicTool>>,
+}
+
 /// Anthropic Count Tokens API
-/// 
+///
 /// Claude Code 使用此端点来计算 token 数量
-/// 我们返回一个模拟的 token 计数响应
+/// 按实际路由到的上游模型选择 tokenizer，统计 system、messages 和 tools
 #[allow(dead_code)]
 pub async fn count_tokens(
-    State(_state): State<AppState>,
-    Json(request): Json<serde_json::Value>,
+    State(state): State<AppState>,
+    Json(request): Json<AnthropicCountTokensRequest>,
 ) -> Result<Response, StatusCode> {
     info!("📊 Anthropic Count Tokens API request received");
-    
-    // 计算整个请求的字符数来估算 token
-    let request_str = serde_json::to_string(&request).unwrap_or_default();
-    let total_chars = request_str.len();
-    
-    // 简单估算：每4个字符约等于1个token
-    let estimated_tokens = (total_chars / 4).max(1);
-    
-    info!("📊 Estimated tokens: {} (from {} chars)", estimated_tokens, total_chars);
-    
+
+    let mut llm_messages = Vec::with_capacity(request.messages.len() + 1);
+    if let Some(system) = request.system.map(|system| system.text()).filter(|text| !text.is_empty()) {
+        llm_messages.push(LlmMessage::system(system));
+    }
+    llm_messages.extend(anthropic_messages_to_llm(request.messages));
+    let tools = request.tools.map(anthropic_tools_to_llm);
+
+    let model = if request.model.is_empty() { None } else { Some(request.model.as_str()) };
+    let upstream_model = state.llm_service.read().await
+        .resolve_model(model)
+        .unwrap_or_else(|| request.model.clone());
+    let input_tokens = counter_for_model(&upstream_model).count_request(&llm_messages, tools.as_deref());
+
+    info!("📊 Counted {} input tokens for model '{}'", input_tokens, upstream_model);
+
     let response = json!({
-        "input_tokens": estimated_tokens
+        "input_tokens": input_tokens
     });
-    
+
     let mut response = Json(response).into_response();
     // Add required Anthropic API headers
     response.headers_mut().insert("anthropic-version", "2023-06-01".parse().unwrap());
@@ -484,3 +872,117 @@ pub async fn count_tokens(
     Ok(response)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_use_round_trip_messages() {
+        let messages: Vec<AnthropicMessage> = serde_json::from_value(json!([
+            {"role": "user", "content": "weather in Paris?"},
+            {"role": "assistant", "content": [
+                {"type": "text", "text": "Checking."},
+                {"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {"city": "Paris"}}
+            ]},
+            {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": [{"type": "text", "text": "22C"}]}
+            ]}
+        ]))
+        .unwrap();
+
+        let llm = anthropic_messages_to_llm(messages);
+        assert_eq!(llm.len(), 3);
+
+        let calls = llm[1].tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id, "toolu_1");
+        assert_eq!(calls[0].function.name, "get_weather");
+        assert_eq!(calls[0].function.arguments, r#"{"city":"Paris"}"#);
+
+        assert_eq!(llm[2].role, LlmRole::Tool);
+        assert_eq!(llm[2].tool_call_id.as_deref(), Some("toolu_1"));
+        assert!(matches!(&llm[2].content[0], MessageBlock::Text { text } if text == "22C"));
+
+        let failed: Vec<AnthropicMessage> = serde_json::from_value(json!([
+            {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "toolu_2", "content": "city not found", "is_error": true}
+            ]}
+        ]))
+        .unwrap();
+        let llm = anthropic_messages_to_llm(failed);
+        assert!(matches!(&llm[0].content[0], MessageBlock::Text { text } if text == "Tool call failed: city not found"));
+    }
+
+    #[test]
+    fn test_stream_converter_emits_tool_use_blocks() {
+        let mut converter = AnthropicStreamConverter::new("test".to_string(), Vec::new(), 12);
+        let mut events = converter.process(
+            r#"data: {"choices":[{"delta":{"content":"Hi"}}]}"#,
+        );
+        events.extend(converter.process(
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"get_weather","arguments":"{\"city\":"}}]}}]}"#,
+        ));
+        events.extend(converter.process(
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"Paris\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\ndata: [DONE]\n\n",
+        ));
+        events.extend(converter.process(
+            r#"data: {"choices":[],"usage":{"prompt_tokens":20,"completion_tokens":7,"total_tokens":27}}"#,
+        ));
+        events.extend(converter.finish());
+
+        let names: Vec<&str> = events.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, [
+            "message_start",
+            "content_block_start", "content_block_delta", "content_block_stop",
+            "content_block_start", "content_block_delta", "content_block_delta", "content_block_stop",
+            "message_delta", "message_stop",
+        ]);
+        assert_eq!(events[4].1["index"], 1);
+        assert_eq!(events[4].1["content_block"]["type"], "tool_use");
+        assert_eq!(events[4].1["content_block"]["id"], "call_1");
+        assert_eq!(events[6].1["delta"]["partial_json"], "\"Paris\"}");
+        assert_eq!(events[8].1["delta"]["stop_reason"], "tool_use");
+        assert_eq!(events[0].1["message"]["usage"]["input_tokens"], 12);
+        assert_eq!(events[8].1["usage"]["input_tokens"], 20);
+        assert_eq!(events[8].1["usage"]["output_tokens"], 7);
+    }
+
+    #[test]
+    fn test_stream_converter_emits_thinking_block() {
+        let mut converter = AnthropicStreamConverter::new("test".to_string(), Vec::new(), 0);
+        let mut events = converter.process(r#"data: {"choices":[{"delta":{"reasoning_content":"Let me see"}}]}"#);
+        events.extend(converter.process(r#"data: {"choices":[{"delta":{"content":"42"}}]}"#));
+        events.extend(converter.finish());
+
+        assert_eq!(events[1].1["content_block"]["type"], "thinking");
+        assert_eq!(events[2].1["delta"], json!({"type": "thinking_delta", "thinking": "Let me see"}));
+        assert_eq!(events[3].0, "content_block_stop");
+        assert_eq!(events[4].1["index"], 1);
+        assert_eq!(events[4].1["content_block"]["type"], "text");
+    }
+
+    #[test]
+    fn test_system_and_stop_reason() {
+        let request: AnthropicMessagesRequest = serde_json::from_value(json!({
+            "model": "claude",
+            "system": [{"type": "text", "text": "Be brief."}, {"type": "text", "text": "Answer in French."}],
+            "messages": [{"role": "user", "content": "hi"}],
+            "stop_sequences": ["END"],
+            "metadata": {"user_id": "u-1"}
+        }))
+        .unwrap();
+        assert_eq!(request.system.unwrap().text(), "Be brief.\nAnswer in French.");
+        assert_eq!(request.metadata.unwrap().user_id.as_deref(), Some("u-1"));
+
+        let stops = vec!["END".to_string()];
+        // 正文以停止序列结尾不代表命中了它
+        assert_eq!(anthropic_stop_reason(Some("stop"), false, &stops), ("end_turn", None));
+        assert_eq!(
+            anthropic_stop_reason(Some("stop_sequence"), false, &stops),
+            ("stop_sequence", Some("END".to_string()))
+        );
+        let two = vec!["END".to_string(), "STOP".to_string()];
+        assert_eq!(anthropic_stop_reason(Some("stop_sequence"), false, &two), ("stop_sequence", None));
+        assert_eq!(anthropic_stop_reason(Some("length"), false, &stops), ("max_tokens", None));
+        assert_eq!(anthropic_stop_reason(Some("stop"), true, &stops), ("tool_use", None));
+    }
+}
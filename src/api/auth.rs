@@ -0,0 +1,325 @@
+use crate::api::AppState;
+use crate::db::ApiKey;
+use crate::service::{glob_match, RouteFilter};
+use crate::api::inspect::RequestInfo;
+use crate::settings::{RateLimit, Settings};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+
+/// Prefix of generated virtual key secrets
+pub const KEY_PREFIX: &str = "llk-";
+
+/// Name the shared key (`--auth-key`) is accounted under
+pub const SHARED_KEY_NAME: &str = "shared";
+
+/// Frontend API a request came in through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frontend {
+    OpenAI,
+    Ollama,
+    Anthropic,
+}
+
+impl Frontend {
+    pub const ALL: [Frontend; 3] = [Frontend::OpenAI, Frontend::Ollama, Frontend::Anthropic];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frontend::OpenAI => "openai",
+            Frontend::Ollama => "ollama",
+            Frontend::Anthropic => "anthropic",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|frontend| frontend.as_str().eq_ignore_ascii_case(name))
+    }
+
+    /// Error response in this frontend's native shape
+    pub(crate) fn error(self, status: StatusCode, message: String) -> Response {
+        match self {
+            Frontend::OpenAI => super::openai::openai_error(status, message),
+            Frontend::Anthropic => super::anthropic::anthropic_error(status, message),
+            Frontend::Ollama => (status, Json(json!({ "error": message }))).into_response(),
+        }
+    }
+
+    /// Shared key and header configured for this frontend
+    pub(crate) fn legacy_key(self, config: &Settings) -> (Option<String>, Option<String>) {
+        let apis = &config.apis;
+        match self {
+            Frontend::OpenAI => apis.openai.as_ref().map(|c| (c.api_key.clone(), c.api_key_header.clone())),
+            Frontend::Ollama => apis.ollama.as_ref().map(|c| (c.api_key.clone(), c.api_key_header.clone())),
+            Frontend::Anthropic => apis.anthropic.as_ref().map(|c| (c.api_key.clone(), c.api_key_header.clone())),
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// Virtual key that authenticated a request
+///
+/// Inserted into the request extensions by [`require_api_key`].
+#[derive(Debug, Clone)]
+pub struct KeyScope {
+    pub name: String,
+    pub models: Vec<String>,
+    pub providers: Vec<String>,
+    /// The key's own rate limits; unset fields fall back to `per_key`
+    pub rate_limit: RateLimit,
+}
+
+impl KeyScope {
+    /// Unrestricted scope of the shared key (`--auth-key`)
+    pub fn shared() -> Self {
+        Self {
+            name: SHARED_KEY_NAME.to_string(),
+            models: Vec::new(),
+            providers: Vec::new(),
+            rate_limit: RateLimit::default(),
+        }
+    }
+
+    fn is_restricted(&self) -> bool {
+        !self.models.is_empty() || !self.providers.is_empty()
+    }
+
+    /// Backends requests made with this key may be routed or failed over to
+    pub fn route_filter(&self) -> RouteFilter {
+        RouteFilter { providers: self.providers.clone(), ..Default::default() }
+    }
+
+    /// Check a request for `model`, routed to `provider`, against the scope
+    ///
+    /// Model patterns match either the full name or the part after a
+    /// `provider/` prefix.
+    pub fn check(&self, model: Option<&str>, provider: Option<&str>) -> Result<(), String> {
+        if !self.models.is_empty() {
+            let model = model.ok_or_else(|| format!("API key '{}' requires the request to name a model", self.name))?;
+            let bare = model.split_once('/').map(|(_, rest)| rest).unwrap_or(model);
+            if !self.models.iter().any(|pattern| glob_match(pattern, model) || glob_match(pattern, bare)) {
+                return Err(format!("API key '{}' is not allowed to use model '{}'", self.name, model));
+            }
+        }
+        if !self.providers.is_empty() && !provider.is_some_and(|p| self.providers.iter().any(|allowed| allowed == p)) {
+            return Err(format!(
+                "API key '{}' is not allowed to use provider '{}'",
+                self.name,
+                provider.unwrap_or("none")
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<&ApiKey> for KeyScope {
+    fn from(key: &ApiKey) -> Self {
+        Self {
+            name: key.name.clone(),
+            models: key.models.0.clone(),
+            providers: key.providers.0.clone(),
+            rate_limit: key.rate_limit(),
+        }
+    }
+}
+
+/// Generate a new virtual key secret
+pub fn generate_api_key() -> String {
+    format!("{}{}{}", KEY_PREFIX, uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// Hash of a key secret as stored in the database
+pub fn hash_api_key(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// Compare a token against the shared key in constant time
+///
+/// Both sides are hashed first so neither the content nor the length of the
+/// key leaks through timing.
+fn shared_key_matches(shared_key: &str, token: &str) -> bool {
+    let (expected, actual) = (Sha256::digest(shared_key.as_bytes()), Sha256::digest(token.as_bytes()));
+    expected.iter().zip(actual.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Whether `key` may be used on `frontend` at `now`
+fn check_key(key: &ApiKey, frontend: Frontend, now: DateTime<Utc>) -> Result<(), (StatusCode, String)> {
+    if !key.enabled {
+        return Err((StatusCode::UNAUTHORIZED, format!("API key '{}' is disabled", key.name)));
+    }
+    if key.is_expired(now) {
+        return Err((StatusCode::UNAUTHORIZED, format!("API key '{}' has expired", key.name)));
+    }
+    let frontends = &key.frontends.0;
+    if !frontends.is_empty() && !frontends.iter().any(|f| f.eq_ignore_ascii_case(frontend.as_str())) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("API key '{}' is not allowed on the {} API", key.name, frontend.as_str()),
+        ));
+    }
+    Ok(())
+}
+
+/// Token sent by the client, from the configured header or the usual ones
+pub(crate) fn request_token(headers: &HeaderMap, header: Option<&str>) -> Option<String> {
+    let configured = header.map(str::to_ascii_lowercase);
+    let value = configured
+        .iter()
+        .map(String::as_str)
+        .chain(["authorization", "x-api-key"])
+        .find_map(|name| headers.get(name)?.to_str().ok())?;
+    let token = value.strip_prefix("Bearer ").unwrap_or(value).trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+/// API key middleware for one frontend
+///
+/// Authentication is required once the frontend has a shared key configured
+/// (`--auth-key` or `api_key` in the config) or any virtual key exists. The
+/// shared key grants unrestricted access; a virtual key is checked against
+/// its expiry, frontends, models and providers.
+pub async fn require_api_key(
+    State((state, frontend)): State<(AppState, Frontend)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (legacy_key, header) = frontend.legacy_key(&*state.config.read().await);
+
+    let keys_enabled = match &state.db {
+        Some(db) => match db.has_api_keys().await {
+            Ok(enabled) => enabled,
+            Err(e) => {
+                error!("❌ Failed to load API keys: {}", e);
+                return frontend.error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load API keys".to_string());
+            }
+        },
+        None => false,
+    };
+    if legacy_key.is_none() && !keys_enabled {
+        return next.run(request).await;
+    }
+
+    let Some(token) = request_token(request.headers(), header.as_deref()) else {
+        warn!("🚫 {} API request without API key", frontend.as_str());
+        return frontend.error(StatusCode::UNAUTHORIZED, "Missing API key".to_string());
+    };
+
+    if legacy_key.as_deref().is_some_and(|legacy_key| shared_key_matches(legacy_key, &token)) {
+        info!("✅ {} API key authentication successful", frontend.as_str());
+        return run_with_scope(request, next, KeyScope::shared()).await;
+    }
+
+    let key = match (&state.db, keys_enabled) {
+        (Some(db), true) => match db.find_api_key_by_hash(&hash_api_key(&token)).await {
+            Ok(key) => key,
+            Err(e) => {
+                error!("❌ Failed to look up API key: {}", e);
+                return frontend.error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load API keys".to_string());
+            }
+        },
+        _ => None,
+    };
+    let Some(key) = key else {
+        warn!("🚫 {} API key authentication failed", frontend.as_str());
+        return frontend.error(StatusCode::UNAUTHORIZED, "Invalid API key".to_string());
+    };
+    if let Err((status, message)) = check_key(&key, frontend, Utc::now()) {
+        warn!("🚫 {}", message);
+        return frontend.error(status, message);
+    }
+
+    let scope = KeyScope::from(&key);
+    if let Some(info) = request.extensions().get::<RequestInfo>().filter(|_| scope.is_restricted()) {
+        // 只看 key 允许的后端；故障切换时同样只会在这些后端之间进行
+        let provider = {
+            let service = state.service().await;
+            scope.route_filter().scope(async { service.provider_for(info.model.as_deref()) }).await
+        };
+        if let Err(message) = scope.check(info.model.as_deref(), provider.as_deref()) {
+            warn!("🚫 {}", message);
+            return frontend.error(StatusCode::FORBIDDEN, message);
+        }
+    }
+
+    info!("✅ {} API request authenticated with key '{}'", frontend.as_str(), scope.name);
+    run_with_scope(request, next, scope).await
+}
+
+/// Run the handler with `scope` on both the request and the response
+///
+/// Outer layers (usage accounting) only see the response, so the key is
+/// passed back out through its extensions. The handler runs under the key's
+/// [`RouteFilter`], so failover never leaves the providers it allows.
+async fn run_with_scope(mut request: Request, next: Next, scope: KeyScope) -> Response {
+    request.extensions_mut().insert(scope.clone());
+    let mut response = scope.route_filter().scope(next.run(request)).await;
+    response.extensions_mut().insert(scope);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::Json as SqlJson;
+
+    fn key(frontends: &[&str], expires_at: Option<DateTime<Utc>>) -> ApiKey {
+        let now = Utc::now();
+        ApiKey {
+            id: 1,
+            name: "ci".to_string(),
+            key_prefix: "llk-0000".to_string(),
+            models: SqlJson(vec!["glm-*".to_string()]),
+            providers: SqlJson(vec!["zhipu-main".to_string()]),
+            frontends: SqlJson(frontends.iter().map(|f| f.to_string()).collect()),
+            expires_at,
+            enabled: true,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            max_concurrent_streams: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_key_checks() {
+        let now = Utc::now();
+        assert_eq!(hash_api_key("secret"), hash_api_key("secret"));
+        assert_ne!(hash_api_key("secret"), hash_api_key("other"));
+        assert!(generate_api_key().starts_with(KEY_PREFIX));
+
+        assert!(check_key(&key(&[], None), Frontend::Anthropic, now).is_ok());
+        assert!(check_key(&key(&["openai"], Some(now + chrono::Duration::hours(1))), Frontend::OpenAI, now).is_ok());
+        assert_eq!(check_key(&key(&["openai"], None), Frontend::Ollama, now).unwrap_err().0, StatusCode::FORBIDDEN);
+        assert_eq!(check_key(&key(&[], Some(now)), Frontend::OpenAI, now).unwrap_err().0, StatusCode::UNAUTHORIZED);
+
+        let scope = KeyScope::from(&key(&[], None));
+        assert!(scope.check(Some("glm-4-flash"), Some("zhipu-main")).is_ok());
+        assert!(scope.check(Some("zhipu/glm-4-flash"), Some("zhipu-main")).is_ok());
+        assert!(scope.check(Some("gpt-4o"), Some("zhipu-main")).is_err());
+        assert!(scope.check(Some("glm-4-flash"), Some("other")).is_err());
+        assert!(scope.check(None, Some("zhipu-main")).is_err());
+        assert!(scope.route_filter().allows("zhipu-main") && !scope.route_filter().allows("other"));
+
+        assert!(shared_key_matches("secret", "secret"));
+        assert!(!shared_key_matches("secret", "secreT") && !shared_key_matches("secret", "secret2"));
+    }
+
+    #[test]
+    fn test_request_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_token(&headers, None), None);
+        headers.insert("x-api-key", "llk-abc".parse().unwrap());
+        assert_eq!(request_token(&headers, None).as_deref(), Some("llk-abc"));
+        headers.insert("authorization", "Bearer llk-def".parse().unwrap());
+        assert_eq!(request_token(&headers, Some("x-api-key")).as_deref(), Some("llk-abc"));
+        assert_eq!(request_token(&headers, None).as_deref(), Some("llk-def"));
+    }
+}
@@ -55,7 +55,7 @@ fn validate_api_key(provider: &str, api_key: &str) -> Result<(), String> {
 /// 验证 provider 名称
 fn validate_provider(provider: &str) -> Result<(), String> {
     match provider {
-        "openai" | "anthropic" | "zhipu" | "ollama" | "aliyun" | "volcengine" | "tencent" | "longcat" | "moonshot" | "minimax" => Ok(()),
+        "openai" | "anthropic" | "zhipu" | "ollama" | "aliyun" | "volcengine" | "tencent" | "longcat" | "moonshot" | "minimax" | "mock" => Ok(()),
         _ => Err(format!("Unsupported provider: {}", provider)),
     }
 }
@@ -152,6 +152,9 @@ pub async fn get_current_config(
         LlmBackendSettings::Minimax { model, .. } => {
             ("minimax", model.clone(), true, false)
         }
+        LlmBackendSettings::Mock { model, .. } => {
+            ("mock", model.clone(), false, false)
+        }
     };
     
     Ok(Json(CurrentConfigResponse {
@@ -183,6 +186,7 @@ pub async fn get_health(
         LlmBackendSettings::Longcat { model, .. } => ("longcat", model.clone()),
         LlmBackendSettings::Moonshot { model, .. } => ("moonshot", model.clone()),
         LlmBackendSettings::Minimax { model, .. } => ("minimax", model.clone()),
+        LlmBackendSettings::Mock { model, .. } => ("mock", model.clone()),
     };
     
     Ok(Json(json!({
@@ -841,7 +845,7 @@ pub async fn switch_provider(
                     return Err(StatusCode::BAD_REQUEST);
                 }
             }
-            "ollama" => String::new(), // Ollama 不需要 API key
+            "ollama" | "mock" => String::new(), // Ollama / mock 不需要 API key
             _ => {
                 error!("❌ Unsupported provider: {}", request.provider);
                 return Err(StatusCode::BAD_REQUEST);
@@ -861,6 +865,7 @@ pub async fn switch_provider(
             "tencent" => "hunyuan-lite".to_string(),
             "longcat" => "LongCat-Flash-Chat".to_string(),
             "minimax" => "MiniMax-M2".to_string(),
+            "mock" => "mock".to_string(),
             _ => "default-model".to_string(),
         }
     });
@@ -909,6 +914,10 @@ pub async fn switch_provider(
             api_key,
             model,
         },
+        "mock" => crate::settings::LlmBackendSettings::Mock {
+            model,
+            mock: Default::default(),
+        },
         _ => {
             error!("❌ Unsupported provider: {}", request.provider);
             return Err(StatusCode::BAD_REQUEST);
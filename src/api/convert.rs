@@ -1,10 +1,11 @@
-use crate::normalizer::Response;
+use crate::normalizer::{image_block_from_url, Response};
 use anyhow::Result;
-use llm_connector::types::{Function, Message as LlmMessage, MessageBlock, Role as LlmRole, Tool};
+use llm_connector::types::{Function, ImageSource, Message as LlmMessage, MessageBlock, Role as LlmRole, Tool};
 use serde_json::Value;
 
 /// Convert OpenAI messages format to llm-connector format
 #[allow(dead_code)]
+#[tracing::instrument(skip_all, fields(messages = messages.len()))]
 pub fn openai_messages_to_llm(messages: Vec<Value>) -> Result<Vec<LlmMessage>> {
     let mut llm_messages = Vec::with_capacity(messages.len());
 
@@ -23,28 +24,45 @@ pub fn openai_messages_to_llm(messages: Vec<Value>) -> Result<Vec<LlmMessage>> {
         };
 
         // Handle content (can be string, array, or null)
-        let content = if msg["content"].is_null() {
+        let mut content = if msg["content"].is_null() {
             // Null content is allowed for assistant messages with tool_calls
-            String::new()
+            vec![MessageBlock::Text { text: String::new() }]
         } else if let Some(content_str) = msg["content"].as_str() {
             // Simple string content
-            content_str.to_string()
+            vec![MessageBlock::Text { text: content_str.to_string() }]
         } else if let Some(content_array) = msg["content"].as_array() {
             // Array content (e.g., from Codex with text and images)
-            // Extract text parts and concatenate them
-            let mut text_parts = Vec::with_capacity(content_array.len());
+            // Adjacent text parts are joined, image parts become image blocks
+            let mut blocks: Vec<MessageBlock> = Vec::with_capacity(content_array.len());
             for part in content_array {
-                if let Some(text) = part["text"].as_str() {
-                    text_parts.push(text);
+                let text = if part["type"] == "image_url" {
+                    let url = part["image_url"]["url"]
+                        .as_str()
+                        .or_else(|| part["image_url"].as_str())
+                        .ok_or_else(|| anyhow::anyhow!("image_url part has no url"))?;
+                    blocks.push(image_block_from_url(url));
+                    continue;
+                } else if let Some(text) = part["text"].as_str() {
+                    text
                 } else if let Some(text) = part.as_str() {
                     // Sometimes the array contains direct strings
-                    text_parts.push(text);
+                    text
+                } else {
+                    continue;
+                };
+
+                match blocks.last_mut() {
+                    Some(MessageBlock::Text { text: previous }) => {
+                        previous.push('\n');
+                        previous.push_str(text);
+                    }
+                    _ => blocks.push(MessageBlock::Text { text: text.to_string() }),
                 }
             }
-            if text_parts.is_empty() {
-                return Err(anyhow::anyhow!("Content array has no text parts"));
+            if blocks.is_empty() {
+                return Err(anyhow::anyhow!("Content array has no text or image parts"));
             }
-            text_parts.join("\n")
+            blocks
         } else {
             return Err(anyhow::anyhow!(
                 "Content must be string, array, or null, got: {:?}",
@@ -52,6 +70,17 @@ pub fn openai_messages_to_llm(messages: Vec<Value>) -> Result<Vec<LlmMessage>> {
             ));
         };
 
+        // Ollama clients send images as a separate array of base64 strings
+        if let Some(images) = msg["images"].as_array() {
+            for image in images.iter().filter_map(|image| image.as_str()) {
+                content.push(if image.starts_with("data:") {
+                    image_block_from_url(image)
+                } else {
+                    base64_image_block(image)
+                });
+            }
+        }
+
         // Extract tool_calls if present (for assistant messages)
         let tool_calls = if role == "assistant" {
             msg.get("tool_calls")
@@ -86,7 +115,7 @@ pub fn openai_messages_to_llm(messages: Vec<Value>) -> Result<Vec<LlmMessage>> {
 
         // Debug logging for tool messages
         if role == "tool" {
-            tracing::debug!("🔧 Converting tool message: role={}, tool_call_id={:?}, content_len={}",
+            tracing::debug!("🔧 Converting tool message: role={}, tool_call_id={:?}, blocks={}",
                           role, tool_call_id, content.len());
 
             // Additional validation: ensure tool_call_id is not empty
@@ -102,7 +131,7 @@ pub fn openai_messages_to_llm(messages: Vec<Value>) -> Result<Vec<LlmMessage>> {
 
         llm_messages.push(LlmMessage {
             role: llm_role,
-            content: vec![MessageBlock::Text { text: content }],
+            content,
             name: None,
             tool_calls,
             tool_call_id,
@@ -116,8 +145,31 @@ pub fn openai_messages_to_llm(messages: Vec<Value>) -> Result<Vec<LlmMessage>> {
     Ok(llm_messages)
 }
 
+/// Build an image block from raw base64 data (as sent by Ollama clients)
+///
+/// The media type is sniffed from the magic bytes; PNG is assumed otherwise.
+pub fn base64_image_block(data: &str) -> MessageBlock {
+    let media_type = if data.starts_with("/9j/") {
+        "image/jpeg"
+    } else if data.starts_with("R0lGOD") {
+        "image/gif"
+    } else if data.starts_with("UklGR") {
+        "image/webp"
+    } else {
+        "image/png"
+    };
+
+    MessageBlock::Image {
+        source: ImageSource::Base64 {
+            media_type: media_type.to_string(),
+            data: data.to_string(),
+        },
+    }
+}
+
 /// Convert Response to OpenAI format
 #[allow(dead_code)]
+#[tracing::instrument(skip_all)]
 pub fn response_to_openai(response: Response) -> Value {
     let mut message = serde_json::json!({
         "role": "assistant",
@@ -129,6 +181,11 @@ pub fn response_to_openai(response: Response) -> Value {
         message["tool_calls"] = tool_calls;
     }
 
+    // Only set in ReasoningMode::Native
+    if let Some(reasoning) = response.reasoning {
+        message["reasoning_content"] = Value::String(reasoning);
+    }
+
     serde_json::json!({
         "id": uuid::Uuid::new_v4().to_string(),
         "object": "chat.completion",
@@ -149,6 +206,7 @@ pub fn response_to_openai(response: Response) -> Value {
 
 /// Convert Response to Ollama format
 #[allow(dead_code)]
+#[tracing::instrument(skip_all)]
 pub fn response_to_ollama(response: Response) -> Value {
     let mut message = serde_json::json!({
         "role": "assistant",
@@ -159,6 +217,10 @@ pub fn response_to_ollama(response: Response) -> Value {
         message["tool_calls"] = tool_calls;
     }
 
+    if let Some(reasoning) = response.reasoning {
+        message["thinking"] = Value::String(reasoning);
+    }
+
     serde_json::json!({
         "model": response.model,
         "created_at": chrono::Utc::now().to_rfc3339(),
@@ -211,6 +273,7 @@ pub fn response_to_ollama_from_minimax(minimax_response: Value) -> Value {
 
 /// Convert OpenAI tools format to llm-connector format
 #[allow(dead_code)]
+#[tracing::instrument(skip_all, fields(tools = tools.len()))]
 pub fn openai_tools_to_llm(tools: Vec<Value>) -> Vec<Tool> {
     tools
         .into_iter()
@@ -351,5 +414,41 @@ mod tests {
         let error_msg = result.unwrap_err().to_string();
         assert!(error_msg.contains("Tool message has empty 'tool_call_id' field"));
     }
-}
 
+    #[test]
+    fn test_image_content_conversion() {
+        let messages = vec![
+            json!({
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "Compare"},
+                    {"type": "text", "text": "these images"},
+                    {"type": "image_url", "image_url": {"url": "data:image/jpeg;base64,/9j/4AAQ"}},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+                ]
+            }),
+            // Ollama style
+            json!({"role": "user", "content": "And this one?", "images": ["iVBORw0KGgo="]}),
+        ];
+
+        let result = openai_messages_to_llm(messages).unwrap();
+        assert_eq!(
+            result[0].content,
+            vec![
+                MessageBlock::Text { text: "Compare\nthese images".to_string() },
+                MessageBlock::Image {
+                    source: ImageSource::Base64 { media_type: "image/jpeg".to_string(), data: "/9j/4AAQ".to_string() },
+                },
+                MessageBlock::Image {
+                    source: ImageSource::Url { url: "https://example.com/cat.png".to_string() },
+                },
+            ]
+        );
+        assert_eq!(
+            result[1].content[1],
+            MessageBlock::Image {
+                source: ImageSource::Base64 { media_type: "image/png".to_string(), data: "iVBORw0KGgo=".to_string() },
+            }
+        );
+    }
+}
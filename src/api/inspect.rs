@@ -0,0 +1,173 @@
+//! Request body inspection shared by the middleware stack
+//!
+//! [`parse`] is the outermost middleware on the API endpoints. It reads the
+//! JSON body once, with the same size limit as axum's `Json` extractor, and
+//! attaches a [`RequestInfo`] to the request. Auth, rate limits, budgets,
+//! usage, metrics, tracing and the audit log read that instead of buffering
+//! and parsing the body again.
+
+use crate::api::auth::Frontend;
+use crate::api::AppState;
+use crate::settings::ApiSettings;
+use crate::tokenizer::counter_for_model;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+
+/// Largest request body read, the default limit of axum's `Json` extractor
+pub const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// What the middleware stack needs to know about a request body
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    pub frontend: Frontend,
+    /// Chat, completion or embedding endpoint (rate limited, metered and audited)
+    pub inference: bool,
+    /// `model`, or the older Ollama `name`
+    pub model: Option<String>,
+    pub stream: bool,
+    /// Estimated prompt tokens; 0 outside the inference endpoints
+    pub prompt_tokens: u32,
+    pub body: Bytes,
+}
+
+impl RequestInfo {
+    /// Info of `request` if it goes to an inference endpoint
+    pub fn inference(request: &Request) -> Option<&Self> {
+        request.extensions().get::<Self>().filter(|info| info.inference)
+    }
+}
+
+/// Frontend of a JSON endpoint, and whether it is an inference endpoint
+fn classify(path: &str, apis: &ApiSettings) -> Option<(Frontend, bool)> {
+    let under = |base: &str, endpoints: &[&str]| {
+        path.strip_prefix(base).is_some_and(|rest| endpoints.contains(&rest))
+    };
+    if let Some(c) = apis.openai.as_ref().filter(|c| c.enabled) {
+        if under(&c.path, &["/chat/completions", "/embeddings"]) {
+            return Some((Frontend::OpenAI, true));
+        }
+    }
+    if let Some(c) = apis.anthropic.as_ref().filter(|c| c.enabled) {
+        if under(&c.path, &["/v1/messages"]) {
+            return Some((Frontend::Anthropic, true));
+        }
+        if under(&c.path, &["/v1/messages/count_tokens"]) {
+            return Some((Frontend::Anthropic, false));
+        }
+    }
+    if let Some(c) = apis.ollama.as_ref().filter(|c| c.enabled) {
+        if under(&c.path, &["/api/chat", "/api/generate", "/api/embed", "/api/embeddings"]) {
+            return Some((Frontend::Ollama, true));
+        }
+        if under(&c.path, &["/api/show"]) {
+            return Some((Frontend::Ollama, false));
+        }
+    }
+    None
+}
+
+/// Model, stream flag and (for inference endpoints) estimated prompt tokens of a body
+fn inspect_body(body: &[u8], frontend: Frontend, inference: bool) -> (Option<String>, bool, u32) {
+    let Ok(body) = serde_json::from_slice::<Value>(body) else {
+        return (None, false, 0);
+    };
+    let model = body
+        .get("model")
+        .or_else(|| body.get("name"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    // Ollama streams unless told otherwise
+    let stream = body
+        .get("stream")
+        .and_then(Value::as_bool)
+        .unwrap_or(frontend == Frontend::Ollama);
+    if !inference {
+        return (model, stream, 0);
+    }
+
+    let mut text = String::new();
+    for field in ["messages", "system", "prompt", "input", "tools"] {
+        if let Some(value) = body.get(field) {
+            collect_strings(value, &mut text);
+        }
+    }
+    let counter = counter_for_model(model.as_deref().unwrap_or_default());
+    let tokens = counter.count_text(&text);
+    (model, stream, tokens)
+}
+
+fn collect_strings(value: &Value, text: &mut String) {
+    match value {
+        Value::String(s) => {
+            text.push_str(s);
+            text.push('\n');
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_strings(item, text)),
+        Value::Object(map) => map.values().for_each(|item| collect_strings(item, text)),
+        _ => {}
+    }
+}
+
+/// Body inspection middleware, outermost on the API endpoints
+///
+/// Bodies over [`MAX_BODY_BYTES`] are rejected with a 413 in the frontend's
+/// error format.
+pub async fn parse(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some((frontend, inference)) = classify(request.uri().path(), &state.config.read().await.apis) else {
+        return next.run(request).await;
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            return frontend.error(StatusCode::PAYLOAD_TOO_LARGE, format!("Failed to read request body: {}", e));
+        }
+    };
+    let (model, stream, prompt_tokens) = inspect_body(&body, frontend, inference);
+    parts.extensions.insert(RequestInfo {
+        frontend,
+        inference,
+        model,
+        stream,
+        prompt_tokens,
+        body: body.clone(),
+    });
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_classify() {
+        let mut settings = Settings::default();
+        settings.apis.ollama.as_mut().unwrap().path = String::new();
+        let apis = &settings.apis;
+        assert_eq!(classify("/v1/chat/completions", apis), Some((Frontend::OpenAI, true)));
+        assert_eq!(classify("/anthropic/v1/messages", apis), Some((Frontend::Anthropic, true)));
+        assert_eq!(classify("/api/chat", apis), Some((Frontend::Ollama, true)));
+        assert_eq!(classify("/api/config/update", apis), None);
+        assert_eq!(classify("/v1/models", apis), None);
+        assert_eq!(classify("/anthropic/v1/messages/count_tokens", apis), Some((Frontend::Anthropic, false)));
+        assert_eq!(classify("/api/show", apis), Some((Frontend::Ollama, false)));
+
+        let (model, stream, tokens) = inspect_body(br#"{"name":"llama3"}"#, Frontend::Ollama, false);
+        assert_eq!((model.as_deref(), stream, tokens), (Some("llama3"), true, 0));
+        let (model, stream, tokens) =
+            inspect_body(br#"{"model":"gpt-4o","messages":[{"role":"user","content":"hello world"}]}"#, Frontend::OpenAI, true);
+        assert_eq!((model.as_deref(), stream), (Some("gpt-4o"), false));
+        assert!(tokens > 0);
+    }
+}
@@ -3,10 +3,14 @@ pub mod ollama;
 pub mod anthropic;
 pub mod convert;
 pub mod config;
+pub mod auth;
+pub mod inspect;
 
 use crate::settings::{Settings, LlmBackendSettings};
 use crate::service::Service as LlmService;
 use crate::models::ModelsConfig;
+use crate::db::DatabasePool;
+use crate::ratelimit::RateLimiter;
 use axum::response::Json;
 use axum::extract::State;
 use axum::http::StatusCode;
@@ -15,32 +19,118 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use anyhow::Result;
 
+/// Response header naming the provider that served a request
+pub const PROVIDER_HEADER: &str = "x-llm-link-provider";
+
+/// Record the serving provider on a response
+///
+/// Provider names are user-defined in multi mode; names that are not valid
+/// header values are skipped rather than failing the request.
+pub fn with_provider_header(mut response: axum::response::Response, provider: &str) -> axum::response::Response {
+    if let Ok(value) = axum::http::HeaderValue::from_str(provider) {
+        response.headers_mut().insert(PROVIDER_HEADER, value);
+    }
+    response
+}
+
+/// Class of a failed upstream request, carried on the error response
+///
+/// Lets outer layers (metrics) tell upstream failures from requests the
+/// proxy rejected itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamError(pub &'static str);
+
+/// Mark `response` as the result of the upstream error `e`
+pub fn with_upstream_error(mut response: axum::response::Response, e: &anyhow::Error) -> axum::response::Response {
+    response.extensions_mut().insert(UpstreamError(error_class(e)));
+    response
+}
+
+/// Coarse class of an upstream error, e.g. `rate_limit` or `network`
+pub fn error_class(e: &anyhow::Error) -> &'static str {
+    use llm_connector::error::LlmConnectorError;
+
+    match e.chain().find_map(|cause| cause.downcast_ref::<LlmConnectorError>()) {
+        Some(LlmConnectorError::AuthenticationError(_) | LlmConnectorError::PermissionError(_)) => "auth",
+        Some(LlmConnectorError::RateLimitError(_)) => "rate_limit",
+        Some(
+            LlmConnectorError::InvalidRequest(_)
+            | LlmConnectorError::UnsupportedModel(_)
+            | LlmConnectorError::NotFoundError(_),
+        ) => "invalid_request",
+        Some(LlmConnectorError::TimeoutError(_)) => "timeout",
+        Some(
+            LlmConnectorError::NetworkError(_)
+            | LlmConnectorError::ConnectionError(_)
+            | LlmConnectorError::MaxRetriesExceeded(_),
+        ) => "network",
+        Some(LlmConnectorError::ParseError(_) | LlmConnectorError::StreamingError(_)) => "protocol",
+        Some(LlmConnectorError::ConfigError(_)) => "config",
+        _ => "server",
+    }
+}
+
+/// HTTP status for a failed upstream request
+///
+/// Requests rejected as invalid, by the provider or by the proxy itself,
+/// are the client's fault and map to 400 (429 for rate limits). Upstream
+/// auth and parse errors are the proxy's problem, so they stay a 500.
+pub fn error_status(e: &anyhow::Error) -> StatusCode {
+    use llm_connector::error::LlmConnectorError;
+
+    match e.chain().find_map(|cause| cause.downcast_ref::<LlmConnectorError>()) {
+        Some(LlmConnectorError::InvalidRequest(_) | LlmConnectorError::UnsupportedModel(_)) => StatusCode::BAD_REQUEST,
+        Some(LlmConnectorError::RateLimitError(_)) => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 /// Application state
 #[derive(Clone)]
 pub struct AppState {
-    pub llm_service: Arc<RwLock<LlmService>>,
+    pub llm_service: Arc<RwLock<Arc<LlmService>>>,
     pub config: Arc<RwLock<Settings>>,
+    /// Multi-mode database (virtual API keys, usage records)
+    pub db: Option<DatabasePool>,
+    pub rate_limiter: Arc<RateLimiter>,
 }
 
 impl AppState {
     pub fn new(llm_service: LlmService, config: Settings) -> Self {
         Self {
-            llm_service: Arc::new(RwLock::new(llm_service)),
+            llm_service: Arc::new(RwLock::new(Arc::new(llm_service))),
             config: Arc::new(RwLock::new(config)),
+            db: None,
+            rate_limiter: Arc::new(RateLimiter::default()),
         }
     }
 
+    /// Check requests against the virtual API keys in `db` and record usage there
+    pub fn with_database(mut self, db: DatabasePool) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// Current LLM service
+    ///
+    /// Cloned out of the lock, so requests waiting on an upstream call don't
+    /// hold up a reload swapping in a new service.
+    pub async fn service(&self) -> Arc<LlmService> {
+        self.llm_service.read().await.clone()
+    }
+
     /// Dynamically update LLM service configuration
     ///
     /// This method allows updating LLM backend configuration at runtime without restarting the service
     pub async fn update_llm_service(&self, new_backend: &LlmBackendSettings) -> Result<()> {
-        // Create new LLM service
-        let new_service = LlmService::new(new_backend)?;
+        // Create new LLM service, keeping any configured model routes
+        let routes = self.config.read().await.routes.clone();
+        let new_service = LlmService::with_routes(new_backend, &routes)?;
 
         // Update service
         {
             let mut service = self.llm_service.write().await;
-            *service = new_service;
+            *service = Arc::new(new_service);
         }
 
         // Update configuration
@@ -52,6 +142,24 @@ impl AppState {
         Ok(())
     }
 
+    /// Swap in a prebuilt LLM service
+    ///
+    /// Used by multi mode to apply provider changes from the admin interface.
+    /// The configured backend is updated to the new service's primary backend.
+    pub async fn replace_llm_service(&self, new_service: LlmService) {
+        let primary = new_service.primary_settings().cloned();
+
+        {
+            let mut service = self.llm_service.write().await;
+            *service = Arc::new(new_service);
+        }
+
+        if let Some(backend) = primary {
+            let mut config = self.config.write().await;
+            config.llm_backend = backend;
+        }
+    }
+
     /// Get a copy of the current configuration
     pub async fn get_current_config(&self) -> Result<Settings> {
         let config = self.config.read().await;
@@ -80,8 +188,12 @@ pub async fn debug_test() -> Json<serde_json::Value> {
 pub async fn info(
     State(state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
+    let keys_enabled = match &state.db {
+        Some(db) => db.has_api_keys().await.unwrap_or(false),
+        None => false,
+    };
     let config = state.config.read().await;
-    let current_provider = get_provider_name(&config.llm_backend);
+    let current_provider = config.llm_backend.provider_name();
     let current_model = get_current_model(&config.llm_backend);
     
     let models_config = ModelsConfig::load_with_fallback();
@@ -109,7 +221,7 @@ pub async fn info(
             api_endpoints.insert("ollama".to_string(), json!({
                 "path": ollama_config.path,
                 "enabled": true,
-                "auth_required": ollama_config.api_key.is_some(),
+                "auth_required": keys_enabled || ollama_config.api_key.is_some(),
             }));
         }
     }
@@ -119,7 +231,7 @@ pub async fn info(
             api_endpoints.insert("openai".to_string(), json!({
                 "path": openai_config.path,
                 "enabled": true,
-                "auth_required": openai_config.api_key.is_some(),
+                "auth_required": keys_enabled || openai_config.api_key.is_some(),
             }));
         }
     }
@@ -129,6 +241,7 @@ pub async fn info(
             api_endpoints.insert("anthropic".to_string(), json!({
                 "path": anthropic_config.path,
                 "enabled": true,
+                "auth_required": keys_enabled || anthropic_config.api_key.is_some(),
             }));
         }
     }
@@ -145,21 +258,6 @@ pub async fn info(
     Ok(Json(response))
 }
 
-fn get_provider_name(backend: &LlmBackendSettings) -> &str {
-    match backend {
-        LlmBackendSettings::OpenAI { .. } => "openai",
-        LlmBackendSettings::Anthropic { .. } => "anthropic",
-        LlmBackendSettings::Zhipu { .. } => "zhipu",
-        LlmBackendSettings::Ollama { .. } => "ollama",
-        LlmBackendSettings::Aliyun { .. } => "aliyun",
-        LlmBackendSettings::Volcengine { .. } => "volcengine",
-        LlmBackendSettings::Tencent { .. } => "tencent",
-        LlmBackendSettings::Longcat { .. } => "longcat",
-        LlmBackendSettings::Moonshot { .. } => "moonshot",
-        LlmBackendSettings::Minimax { .. } => "minimax",
-    }
-}
-
 fn get_current_model(backend: &LlmBackendSettings) -> String {
     match backend {
         LlmBackendSettings::OpenAI { model, .. } => model.clone(),
@@ -172,5 +270,6 @@ fn get_current_model(backend: &LlmBackendSettings) -> String {
         LlmBackendSettings::Longcat { model, .. } => model.clone(),
         LlmBackendSettings::Moonshot { model, .. } => model.clone(),
         LlmBackendSettings::Minimax { model, .. } => model.clone(),
+        LlmBackendSettings::Mock { model, .. } => model.clone(),
     }
 }
@@ -13,18 +13,22 @@ use serde_json::{json, Value};
 use std::convert::Infallible;
 use std::sync::{Arc, Mutex, OnceLock};
 use std::collections::HashMap;
+use std::time::Instant;
 use tracing::{info, warn, error};
 
 use crate::adapters::{ClientAdapter, FormatDetector};
-use crate::api::{AppState, convert};
-use crate::models::ModelsConfig;
+use crate::api::{AppState, convert, error_status, with_provider_header, with_upstream_error};
+use crate::normalizer::{normalize_finish_reason, ChatOptions, EmbeddingRequest, OutputFormat};
+use crate::settings::ReasoningMode;
 use crate::settings;
 use crate::provider::minimax::MinimaxClient;
 
 // 全局工具缓存，用于在对话过程中保持工具定义
-static TOOL_CACHE: OnceLock<Arc<Mutex<HashMap<String, Vec<llm_connector::types::Tool>>>>> = OnceLock::new();
+type ToolCache = Arc<Mutex<HashMap<String, Vec<llm_connector::types::Tool>>>>;
 
-fn get_tool_cache() -> &'static Arc<Mutex<HashMap<String, Vec<llm_connector::types::Tool>>>> {
+static TOOL_CACHE: OnceLock<ToolCache> = OnceLock::new();
+
+fn get_tool_cache() -> &'static ToolCache {
     TOOL_CACHE.get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
 }
 
@@ -95,27 +99,6 @@ async fn chat_impl(
     request: OllamaChatRequest,
 ) -> Result<Response, StatusCode> {
 
-    // Ollama API 通常不需要认证，但可以配置
-    {
-        let config = state.config.read().await;
-        if let Some(cfg) = &config.apis.ollama {
-            if let Some(_expected_key) = cfg.api_key.as_ref() {
-                // 如果配置了 API key，则进行验证
-                // 这里可以添加 Ollama API key 验证逻辑
-            }
-        }
-    }
-
-    // 验证模型
-    if !request.model.is_empty() {
-        let llm_service = state.llm_service.read().await;
-        match llm_service.validate_model(&request.model).await {
-            Ok(false) => return Err(StatusCode::BAD_REQUEST),
-            Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-            Ok(true) => {}
-        }
-    }
-
     // 转换消息格式
     match convert::openai_messages_to_llm(request.messages) {
         Ok(messages) => {
@@ -168,13 +151,13 @@ async fn handle_streaming_request(
     info!("📡 Starting Ollama streaming response - Client: {:?}, Format: {:?} ({}), Tools: {}",
           client_adapter, final_format, content_type, tools.as_ref().map_or(0, |t| t.len()));
 
-    let llm_service = state.llm_service.read().await;
-    let stream_result = llm_service.chat_stream_ollama_with_tools(model, messages.clone(), tools.clone(), final_format).await;
-    drop(llm_service); // 显式释放锁
+    let llm_service = state.service().await;
+    let stream_result = llm_service.chat_stream_ollama_with_tools(model, messages.clone(), tools.clone(), &ChatOptions::default(), final_format).await;
 
     match stream_result {
-        Ok(rx) => {
-            info!("✅ Ollama streaming response started successfully");
+        Ok(served) => {
+            info!("✅ Ollama streaming response started successfully (provider: {})", served.provider);
+            let rx = served.output;
 
             // Get config before entering the map closure and clone it for the closure
             let config = state.config.read().await.clone();
@@ -209,7 +192,7 @@ async fn handle_streaming_request(
                 .body(body)
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-            Ok(response)
+            Ok(with_provider_header(response, &served.provider))
         }
         Err(e) => {
             warn!("⚠️ Ollama streaming failed, falling back to non-streaming: {:?}", e);
@@ -244,13 +227,13 @@ async fn handle_non_streaming_request(
 ) -> Result<Response, StatusCode> {
     info!("📡 Ollama non-streaming request - Tools: {}", tools.as_ref().map_or(0, |t| t.len()));
 
-    let llm_service = state.llm_service.read().await;
-    let chat_result = llm_service.chat(model, messages, tools).await;
+    let llm_service = state.service().await;
+    let chat_result = llm_service.chat(model, messages, tools, &ChatOptions::default()).await;
 
     match chat_result {
-        Ok(response) => {
-            let ollama_response = convert::response_to_ollama(response);
-            Ok(Json(ollama_response).into_response())
+        Ok(served) => {
+            let ollama_response = convert::response_to_ollama(served.output);
+            Ok(with_provider_header(Json(ollama_response).into_response(), &served.provider))
         }
         Err(e) => {
             error!("❌ Ollama chat request failed: {:?}", e);
@@ -264,26 +247,14 @@ async fn handle_non_streaming_request(
 pub async fn models(
     State(state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
-    let llm_service = state.llm_service.read().await;
+    let llm_service = state.service().await;
     let models_result = llm_service.list_models().await;
 
     match models_result {
         Ok(models) => {
             let ollama_models = convert::models_to_ollama(models);
 
-            let config = state.config.read().await;
-            let current_provider = match &config.llm_backend {
-                crate::settings::LlmBackendSettings::OpenAI { .. } => "openai",
-                crate::settings::LlmBackendSettings::Anthropic { .. } => "anthropic",
-                crate::settings::LlmBackendSettings::Zhipu { .. } => "zhipu",
-                crate::settings::LlmBackendSettings::Ollama { .. } => "ollama",
-                crate::settings::LlmBackendSettings::Aliyun { .. } => "aliyun",
-                crate::settings::LlmBackendSettings::Volcengine { .. } => "volcengine",
-                crate::settings::LlmBackendSettings::Tencent { .. } => "tencent",
-                crate::settings::LlmBackendSettings::Longcat { .. } => "longcat",
-                crate::settings::LlmBackendSettings::Moonshot { .. } => "moonshot",
-                crate::settings::LlmBackendSettings::Minimax { .. } => "minimax",
-            };
+            let current_provider = state.config.read().await.llm_backend.provider_name();
 
             let response = json!({
                 "models": ollama_models,
@@ -297,7 +268,7 @@ pub async fn models(
 
 /// 检测 Ollama 客户端类型
 #[allow(dead_code)]
-fn detect_ollama_client(headers: &HeaderMap, config: &crate::settings::Settings) -> ClientAdapter {
+pub(crate) fn detect_ollama_client(headers: &HeaderMap, config: &crate::settings::Settings) -> ClientAdapter {
     // 1. 检查强制适配器设置
     if let Some(ref adapters) = config.client_adapters {
         if let Some(force_adapter) = &adapters.force_adapter {
@@ -352,16 +323,373 @@ fn detect_ollama_client(headers: &HeaderMap, config: &crate::settings::Settings)
     ClientAdapter::Standard
 }
 
-/// Ollama Generate API (占位符)
-#[allow(dead_code)]
+/// Ollama Generate API request
+#[derive(Debug, Deserialize)]
+pub struct OllamaGenerateRequest {
+    pub model: String,
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub raw: bool,
+    /// Base64-encoded images
+    #[serde(default)]
+    pub images: Option<Vec<String>>,
+    /// `"json"` or a JSON schema
+    #[serde(default)]
+    pub format: Option<Value>,
+    #[serde(default)]
+    pub options: Option<Value>,
+    /// Only used to tell load/unload requests apart; remote models stay loaded
+    #[serde(default)]
+    pub keep_alive: Option<Value>,
+    /// Token context from a previous response (not supported by remote providers)
+    #[serde(default)]
+    pub context: Option<Vec<i64>>,
+    #[serde(default)]
+    pub stream: Option<bool>,
+    /// `true` / `false`, or an effort level such as `"high"`
+    #[serde(default)]
+    pub think: Option<Value>,
+}
+
+impl OllamaGenerateRequest {
+    /// 将 prompt / system / template 转换为 chat 消息
+    fn to_messages(&self) -> Vec<llm_connector::types::Message> {
+        use llm_connector::types::{Message, MessageBlock, Role};
+
+        // raw 模式或自定义模板：整段 prompt 作为单条 user 消息发送
+        let (system, prompt) = if self.raw {
+            (None, self.prompt.clone())
+        } else if let Some(template) = self.template.as_deref().filter(|t| !t.is_empty()) {
+            (None, render_template(template, self.system.as_deref().unwrap_or(""), &self.prompt))
+        } else {
+            (self.system.clone().filter(|s| !s.is_empty()), self.prompt.clone())
+        };
+
+        let mut content = vec![MessageBlock::Text { text: prompt }];
+        content.extend(self.images.iter().flatten().map(|image| convert::base64_image_block(image)));
+
+        let mut messages = Vec::with_capacity(2);
+        if let Some(system) = system {
+            messages.push(Message::system(system));
+        }
+        messages.push(Message::new(Role::User, content));
+        messages
+    }
+
+    fn chat_options(&self, default_reasoning: ReasoningMode) -> ChatOptions {
+        let mut options = ollama_chat_options(self.options.as_ref());
+        options.response_format = self.format.as_ref().and_then(OutputFormat::from_ollama);
+        apply_ollama_think(&mut options, self.think.as_ref(), default_reasoning);
+        options
+    }
+
+    /// 空 prompt 是 Ollama 客户端用来加载 / 卸载模型的请求
+    fn is_load_request(&self) -> bool {
+        self.prompt.is_empty() && self.images.as_ref().is_none_or(|images| images.is_empty())
+    }
+
+    fn is_unload(&self) -> bool {
+        match &self.keep_alive {
+            Some(Value::Number(n)) => n.as_f64() == Some(0.0),
+            Some(Value::String(s)) => matches!(s.trim(), "0" | "0s" | "0m" | "0h"),
+            _ => false,
+        }
+    }
+}
+
+/// Map Ollama `options` onto upstream request options
+///
+/// Options without an upstream equivalent (`top_k`, `num_ctx`, ...) are ignored.
+pub fn ollama_chat_options(options: Option<&Value>) -> ChatOptions {
+    let Some(options) = options else {
+        return ChatOptions::default();
+    };
+
+    let as_f32 = |key: &str| options.get(key).and_then(|v| v.as_f64()).map(|v| v as f32);
+    ChatOptions {
+        temperature: as_f32("temperature"),
+        top_p: as_f32("top_p"),
+        presence_penalty: as_f32("presence_penalty"),
+        frequency_penalty: as_f32("frequency_penalty"),
+        // num_predict 为 -1 / -2 时表示不限制
+        max_tokens: options.get("num_predict")
+            .and_then(|v| v.as_i64())
+            .filter(|n| *n > 0)
+            .map(|n| n as u32),
+        seed: options.get("seed").and_then(|v| v.as_u64()),
+        stop: options.get("stop").and_then(|v| v.as_array()).map(|stops| {
+            stops.iter().filter_map(|s| s.as_str().map(str::to_string)).collect()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Apply the Ollama `think` parameter
+///
+/// `think` turns the model's thinking on and returns it in `thinking`;
+/// `false` turns it off. Without it the app's reasoning mode is used.
+pub fn apply_ollama_think(options: &mut ChatOptions, think: Option<&Value>, default_reasoning: ReasoningMode) {
+    let think = match think {
+        Some(Value::Bool(think)) => Some(*think),
+        // gpt-oss 等模型使用 "low" / "medium" / "high"
+        Some(Value::String(_)) => Some(true),
+        _ => None,
+    };
+    options.enable_thinking = think;
+    options.reasoning = match think {
+        Some(true) => ReasoningMode::Native,
+        Some(false) => ReasoningMode::Strip,
+        None => default_reasoning,
+    };
+}
+
+/// Render an Ollama Go prompt template
+///
+/// Only `.System`, `.Prompt` and `.Response` are substituted; `if` / `else` /
+/// `end` actions are dropped, which covers the templates clients send.
+fn render_template(template: &str, system: &str, prompt: &str) -> String {
+    static ACTION: OnceLock<regex::Regex> = OnceLock::new();
+    let action = ACTION.get_or_init(|| {
+        regex::Regex::new(r"\{\{-?\s*(?:if|else|end|range|with)\b[^}]*?-?\}\}").expect("valid template regex")
+    });
+    static FIELD: OnceLock<regex::Regex> = OnceLock::new();
+    let field = FIELD.get_or_init(|| {
+        regex::Regex::new(r"\{\{-?\s*\.(System|Prompt|Response)\s*-?\}\}").expect("valid template regex")
+    });
+
+    let stripped = action.replace_all(template, "");
+    field
+        .replace_all(&stripped, |caps: &regex::Captures| match &caps[1] {
+            "System" => system.to_string(),
+            "Prompt" => prompt.to_string(),
+            _ => String::new(),
+        })
+        .into_owned()
+}
+
+/// Timing fields of a finished generate response, in nanoseconds
+fn generate_timings(started: Instant, first_token: Option<Instant>) -> Value {
+    let now = Instant::now();
+    let first_token = first_token.unwrap_or(now);
+    json!({
+        "total_duration": now.duration_since(started).as_nanos() as u64,
+        "load_duration": 0,
+        "prompt_eval_duration": first_token.duration_since(started).as_nanos() as u64,
+        "eval_duration": now.duration_since(first_token).as_nanos() as u64,
+    })
+}
+
+/// Ollama Generate API
 pub async fn generate(
-    State(_state): State<AppState>,
-    Json(_request): Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    // 暂时返回未实现
-    Ok(Json(serde_json::json!({
-        "error": "Generate API not implemented yet"
-    })))
+    State(state): State<AppState>,
+    Json(request): Json<OllamaGenerateRequest>,
+) -> Response {
+    let started = Instant::now();
+    let stream = request.stream.unwrap_or(true);
+    info!("📨 Generate request: model={}, prompt_len={}, raw={}, stream={}",
+          request.model, request.prompt.len(), request.raw, stream);
+
+    if request.is_load_request() {
+        let done_reason = if request.is_unload() { "unload" } else { "load" };
+        info!("📦 Generate {} request for model {}", done_reason, request.model);
+        return Json(json!({
+            "model": request.model,
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "response": "",
+            "done": true,
+            "done_reason": done_reason
+        })).into_response();
+    }
+    if request.context.as_ref().is_some_and(|context| !context.is_empty()) {
+        warn!("⚠️ Generate 'context' is not supported for remote providers, ignoring");
+    }
+
+    let messages = request.to_messages();
+    let options = request.chat_options(state.config.read().await.reasoning_mode());
+    let model = if request.model.is_empty() { None } else { Some(request.model.as_str()) };
+    let llm_service = state.service().await;
+
+    if stream {
+        let stream_result = llm_service
+            .chat_stream_ollama_with_tools(model, messages, None, &options, llm_connector::StreamFormat::NDJSON)
+            .await;
+        match stream_result {
+            Ok(served) => {
+                let model_name = request.model.clone();
+                let mut first_token: Option<Instant> = None;
+                let body_stream = served.output.map(move |data| {
+                    let mut out = String::new();
+                    for chunk in data.lines().filter_map(|line| serde_json::from_str::<Value>(line).ok()) {
+                        let generate_chunk = if chunk["done"].as_bool().unwrap_or(false) {
+                            let mut done = json!({
+                                "model": model_name,
+                                "created_at": chrono::Utc::now().to_rfc3339(),
+                                "response": "",
+                                "done": true,
+                                "done_reason": chunk["done_reason"].as_str().unwrap_or("stop"),
+                                "context": [],
+                                "prompt_eval_count": chunk["prompt_eval_count"],
+                                "eval_count": chunk["eval_count"],
+                            });
+                            if let (Some(done), Value::Object(timings)) = (done.as_object_mut(), generate_timings(started, first_token)) {
+                                done.extend(timings);
+                            }
+                            done
+                        } else {
+                            let text = chunk["message"]["content"].as_str().unwrap_or_default();
+                            let thinking = chunk["message"]["thinking"].as_str();
+                            if text.is_empty() && thinking.is_none() {
+                                continue;
+                            }
+                            first_token.get_or_insert_with(Instant::now);
+                            let mut generate_chunk = json!({
+                                "model": model_name,
+                                "created_at": chrono::Utc::now().to_rfc3339(),
+                                "response": text,
+                                "done": false
+                            });
+                            if let Some(thinking) = thinking {
+                                generate_chunk["thinking"] = json!(thinking);
+                            }
+                            generate_chunk
+                        };
+                        out.push_str(&format!("{}\n", generate_chunk));
+                    }
+                    Ok::<_, Infallible>(axum::body::Bytes::from(out))
+                });
+
+                let response = Response::builder()
+                    .status(200)
+                    .header("content-type", "application/x-ndjson")
+                    .body(Body::from_stream(body_stream))
+                    .unwrap();
+                with_provider_header(response, &served.provider)
+            }
+            Err(e) => {
+                error!("❌ Generate streaming request failed: {:?}", e);
+                with_upstream_error((error_status(&e), Json(json!({"error": e.to_string()}))).into_response(), &e)
+            }
+        }
+    } else {
+        match llm_service.chat(model, messages, None, &options).await {
+            Ok(served) => {
+                let output = served.output;
+                let mut body = json!({
+                    "model": request.model,
+                    "created_at": chrono::Utc::now().to_rfc3339(),
+                    "response": output.content,
+                    "done": true,
+                    "done_reason": output.finish_reason.as_deref().map(normalize_finish_reason).unwrap_or("stop"),
+                    "context": [],
+                    "prompt_eval_count": output.usage.prompt_tokens,
+                    "eval_count": output.usage.completion_tokens,
+                });
+                if let Some(thinking) = output.reasoning {
+                    body["thinking"] = json!(thinking);
+                }
+                if let (Some(body), Value::Object(timings)) = (body.as_object_mut(), generate_timings(started, None)) {
+                    body.extend(timings);
+                }
+                with_provider_header(Json(body).into_response(), &served.provider)
+            }
+            Err(e) => {
+                error!("❌ Generate request failed: {:?}", e);
+                with_upstream_error((error_status(&e), Json(json!({"error": e.to_string()}))).into_response(), &e)
+            }
+        }
+    }
+}
+
+/// Ollama Embed API request (`/api/embed`)
+#[derive(Debug, Deserialize)]
+pub struct OllamaEmbedRequest {
+    pub model: String,
+    /// A string or an array of strings
+    pub input: Value,
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub truncate: Option<bool>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub keep_alive: Option<Value>,
+}
+
+/// Legacy Ollama Embeddings API request (`/api/embeddings`)
+#[derive(Debug, Deserialize)]
+pub struct OllamaEmbeddingsRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub keep_alive: Option<Value>,
+}
+
+/// Ollama Embed API
+pub async fn embed(
+    State(state): State<AppState>,
+    Json(request): Json<OllamaEmbedRequest>,
+) -> Response {
+    let started = Instant::now();
+    let input = match &request.input {
+        Value::String(text) => vec![text.clone()],
+        Value::Array(items) => items.iter().filter_map(|item| item.as_str().map(str::to_string)).collect(),
+        _ => Vec::new(),
+    };
+    if input.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(json!({"error": "input must be a string or array of strings"}))).into_response();
+    }
+    info!("🧮 Embed request: model={}, inputs={}", request.model, input.len());
+
+    let model = if request.model.is_empty() { None } else { Some(request.model.as_str()) };
+    let embedding_request = EmbeddingRequest { input, dimensions: request.dimensions };
+    let llm_service = state.service().await;
+
+    match llm_service.embed(model, &embedding_request).await {
+        Ok(served) => {
+            let body = json!({
+                "model": request.model,
+                "embeddings": served.output.vectors,
+                "total_duration": started.elapsed().as_nanos() as u64,
+                "load_duration": 0,
+                "prompt_eval_count": served.output.prompt_tokens,
+            });
+            with_provider_header(Json(body).into_response(), &served.provider)
+        }
+        Err(e) => {
+            error!("❌ Embed request failed: {:?}", e);
+            with_upstream_error((error_status(&e), Json(json!({"error": e.to_string()}))).into_response(), &e)
+        }
+    }
+}
+
+/// Legacy Ollama Embeddings API, one prompt per request
+pub async fn embeddings(
+    State(state): State<AppState>,
+    Json(request): Json<OllamaEmbeddingsRequest>,
+) -> Response {
+    info!("🧮 Embeddings request: model={}", request.model);
+
+    let model = if request.model.is_empty() { None } else { Some(request.model.as_str()) };
+    let embedding_request = EmbeddingRequest { input: vec![request.prompt], dimensions: None };
+    let llm_service = state.service().await;
+
+    match llm_service.embed(model, &embedding_request).await {
+        Ok(served) => {
+            let embedding = served.output.vectors.into_iter().next().unwrap_or_default();
+            with_provider_header(Json(json!({"embedding": embedding})).into_response(), &served.provider)
+        }
+        Err(e) => {
+            error!("❌ Embeddings request failed: {:?}", e);
+            with_upstream_error((error_status(&e), Json(json!({"error": e.to_string()}))).into_response(), &e)
+        }
+    }
 }
 
 /// Ollama Show API - Handler for Axum (with proper signature)
@@ -369,7 +697,7 @@ pub async fn show_handler(
     State(state): State<AppState>,
     Json(request): Json<Value>,
 ) -> Result<Json<Value>, StatusCode> {
-    use crate::models::ModelsConfig;
+    use crate::models::{ModelsConfig, DEFAULT_CONTEXT_LENGTH};
 
     // Extract model name from request
     let model_name = request.get("name")
@@ -378,20 +706,7 @@ pub async fn show_handler(
         .unwrap_or("MiniMax-M2");
 
     // Get model capabilities from configuration
-    let config = state.config.read().await;
-    let provider_name = match &config.llm_backend {
-        crate::settings::LlmBackendSettings::OpenAI { .. } => "openai",
-        crate::settings::LlmBackendSettings::Anthropic { .. } => "anthropic",
-        crate::settings::LlmBackendSettings::Zhipu { .. } => "zhipu",
-        crate::settings::LlmBackendSettings::Ollama { .. } => "ollama",
-        crate::settings::LlmBackendSettings::Aliyun { .. } => "aliyun",
-        crate::settings::LlmBackendSettings::Volcengine { .. } => "volcengine",
-        crate::settings::LlmBackendSettings::Tencent { .. } => "tencent",
-        crate::settings::LlmBackendSettings::Longcat { .. } => "longcat",
-        crate::settings::LlmBackendSettings::Moonshot { .. } => "moonshot",
-        crate::settings::LlmBackendSettings::Minimax { .. } => "minimax",
-    };
-    drop(config);
+    let provider_name = state.config.read().await.llm_backend.provider_name();
 
     // Load models configuration
     let models_config = ModelsConfig::load_with_fallback();
@@ -404,15 +719,18 @@ pub async fn show_handler(
     let mut capabilities = Vec::new();
     let context_length = if let Some(model_info) = provider_models.iter().find(|m| m.id == model_name) {
         info!("✅ Found model '{}', supports_tools={}, context_length={}",
-              model_name, model_info.supports_tools, model_info.context_length);
+              model_name, model_info.supports_tools, model_info.context_length.unwrap_or(DEFAULT_CONTEXT_LENGTH));
         if model_info.supports_tools {
             capabilities.push("tools");
             info!("✅ Model {} supports tools (via /api/show)", model_name);
         }
-        model_info.context_length
+        if model_info.supports_vision {
+            capabilities.push("vision");
+        }
+        model_info.context_length.unwrap_or(DEFAULT_CONTEXT_LENGTH)
     } else {
         info!("⚠️ Model '{}' not found in provider '{}', using default context_length", model_name, provider_name);
-        4096
+        DEFAULT_CONTEXT_LENGTH
     };
 
     // Return model details in Ollama format
@@ -467,7 +785,7 @@ pub async fn show(
     info!("🔍 /api/show request for model: '{}', full request: {}", model_name, request);
 
     // Check if model exists
-    let llm_service = state.llm_service.read().await;
+    let llm_service = state.service().await;
     let validation_result = llm_service.validate_model(model_name).await;
 
     match validation_result {
@@ -540,27 +858,8 @@ pub fn build_ollama_routes(state: AppState, ollama_config: &settings::OllamaApiS
             async move {
                 use axum::Json;
 
-                // Determine current provider name from backend
-                let provider_name = {
-                    let cfg = state.config.read().await;
-                    let name = match &cfg.llm_backend {
-                        settings::LlmBackendSettings::OpenAI { .. } => "openai",
-                        settings::LlmBackendSettings::Anthropic { .. } => "anthropic",
-                        settings::LlmBackendSettings::Zhipu { .. } => "zhipu",
-                        settings::LlmBackendSettings::Ollama { .. } => "ollama",
-                        settings::LlmBackendSettings::Aliyun { .. } => "aliyun",
-                        settings::LlmBackendSettings::Volcengine { .. } => "volcengine",
-                        settings::LlmBackendSettings::Tencent { .. } => "tencent",
-                        settings::LlmBackendSettings::Longcat { .. } => "longcat",
-                        settings::LlmBackendSettings::Moonshot { .. } => "moonshot",
-                        settings::LlmBackendSettings::Minimax { .. } => "minimax",
-                    };
-                    name.to_string()
-                };
-
-                // Load models from embedded models.yaml
-                let models_config = ModelsConfig::load_with_fallback();
-                let provider_models = models_config.get_models_for_provider(&provider_name);
+                // Merge models.yaml entries of every configured backend
+                let provider_models = state.llm_service.read().await.model_infos();
 
                 // Map to Ollama tags format
                 let ollama_models: Vec<serde_json::Value> = provider_models
@@ -576,6 +875,9 @@ pub fn build_ollama_routes(state: AppState, ollama_config: &settings::OllamaApiS
                         } else {
                             info!("⚠️ Model {} does NOT support tools", m.id);
                         }
+                        if m.supports_vision {
+                            tags.push("vision");
+                        }
 
                         serde_json::json!({
                             "name": m.id,
@@ -642,12 +944,18 @@ pub fn build_ollama_routes(state: AppState, ollama_config: &settings::OllamaApiS
                 let response: Response = if is_minimax {
                     handle_minimax_chat(&model, messages_value, stream, minimax_api_key).await
                 } else {
-                    handle_generic_chat(s, &model, messages_value, tools_value, stream).await
+                    let mut options = ollama_chat_options(req.get("options"));
+                    options.response_format = req.get("format").and_then(OutputFormat::from_ollama);
+                    apply_ollama_think(&mut options, req.get("think"), s.config.read().await.reasoning_mode());
+                    handle_generic_chat(s, &model, messages_value, tools_value, options, stream).await
                 };
 
                 response.into_response()
             }
         }))
+        .route(&format!("{}/api/generate", ollama_config.path), post(generate))
+        .route(&format!("{}/api/embed", ollama_config.path), post(embed))
+        .route(&format!("{}/api/embeddings", ollama_config.path), post(embeddings))
         .route(&format!("{}/api/show", ollama_config.path), post(show_handler))
         .route(&format!("{}/api/version", ollama_config.path), get(|| async {
             axum::Json(serde_json::json!({
@@ -839,6 +1147,7 @@ async fn handle_generic_chat(
     model: &str,
     messages_value: Vec<serde_json::Value>,
     tools_value: Option<Vec<serde_json::Value>>,
+    options: ChatOptions,
     stream: bool,
 ) -> Response {
     use tracing::info;
@@ -872,9 +1181,9 @@ async fn handle_generic_chat(
     // resolution (e.g. Volcengine endpoint IDs) is handled by the
     // normalizer/client via ModelResolver.
     if stream {
-        handle_generic_chat_stream(state, Some(model.to_string()), messages, tools).await
+        handle_generic_chat_stream(state, Some(model.to_string()), messages, tools, &options).await
     } else {
-        handle_generic_chat_nonstream(state, Some(model.to_string()), messages, tools).await
+        handle_generic_chat_nonstream(state, Some(model.to_string()), messages, tools, &options).await
     }
 }
 
@@ -883,6 +1192,7 @@ async fn handle_generic_chat_stream(
     model_arg: Option<String>,
     messages: Vec<llm_connector::types::Message>,
     tools: Option<Vec<llm_connector::types::Tool>>,
+    options: &ChatOptions,
 ) -> Response {
     use tracing::info;
     use axum::response::Response;
@@ -890,34 +1200,36 @@ async fn handle_generic_chat_stream(
     use futures::StreamExt;
     use std::convert::Infallible;
 
-    let llm_service = state.llm_service.read().await;
+    let llm_service = state.service().await;
     let model_ref = model_arg.as_deref();
 
     match llm_service
-        .chat_stream_ollama_with_tools(model_ref, messages, tools, llm_connector::StreamFormat::NDJSON)
+        .chat_stream_ollama_with_tools(model_ref, messages, tools, options, llm_connector::StreamFormat::NDJSON)
         .await
     {
-        Ok(rx) => {
-            info!("✅ Chat streaming response started successfully");
+        Ok(served) => {
+            info!("✅ Chat streaming response started successfully (provider: {})", served.provider);
 
-            let body_stream = rx.map(|data| Ok::<_, Infallible>(axum::body::Bytes::from(data)));
+            let body_stream = served.output.map(|data| Ok::<_, Infallible>(axum::body::Bytes::from(data)));
             let body = Body::from_stream(body_stream);
 
-            Response::builder()
+            let response = Response::builder()
                 .status(200)
                 .header("content-type", "application/x-ndjson")
                 .body(body)
-                .unwrap()
+                .unwrap();
+            with_provider_header(response, &served.provider)
         }
         Err(e) => {
             info!("❌ Chat streaming request failed: {:?}", e);
-            Response::builder()
-                .status(500)
+            let response = Response::builder()
+                .status(error_status(&e))
                 .header("content-type", "application/json")
                 .body(Body::from(
-                    serde_json::json!({"error": "Chat request failed"}).to_string(),
+                    serde_json::json!({"error": e.to_string()}).to_string(),
                 ))
-                .unwrap()
+                .unwrap();
+            with_upstream_error(response, &e)
         }
     }
 }
@@ -927,33 +1239,86 @@ async fn handle_generic_chat_nonstream(
     model_arg: Option<String>,
     messages: Vec<llm_connector::types::Message>,
     tools: Option<Vec<llm_connector::types::Tool>>,
+    options: &ChatOptions,
 ) -> Response {
     use tracing::info;
     use axum::response::Response;
     use axum::body::Body;
 
-    let llm_service = state.llm_service.read().await;
+    let llm_service = state.service().await;
     let model_ref = model_arg.as_deref();
 
-    match llm_service.chat(model_ref, messages, tools).await {
-        Ok(response) => {
-            info!("✅ Chat response generated successfully");
-            let ollama_response = convert::response_to_ollama(response);
-            Response::builder()
+    match llm_service.chat(model_ref, messages, tools, options).await {
+        Ok(served) => {
+            info!("✅ Chat response generated successfully (provider: {})", served.provider);
+            let ollama_response = convert::response_to_ollama(served.output);
+            let response = Response::builder()
                 .status(200)
                 .header("content-type", "application/json")
                 .body(Body::from(serde_json::to_string(&ollama_response).unwrap()))
-                .unwrap()
+                .unwrap();
+            with_provider_header(response, &served.provider)
         }
         Err(e) => {
             info!("❌ Chat request failed: {:?}", e);
-            Response::builder()
-                .status(500)
+            let response = Response::builder()
+                .status(error_status(&e))
                 .header("content-type", "application/json")
                 .body(Body::from(
-                    serde_json::json!({"error": "Chat request failed"}).to_string(),
+                    serde_json::json!({"error": e.to_string()}).to_string(),
                 ))
-                .unwrap()
+                .unwrap();
+            with_upstream_error(response, &e)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_request(body: Value) -> OllamaGenerateRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_render_template() {
+        let template = "{{ if .System }}<|system|>{{ .System }}{{ end }}<|user|>{{ .Prompt }}<|assistant|>{{ .Response }}";
+        assert_eq!(
+            render_template(template, "be brief", "hi"),
+            "<|system|>be brief<|user|>hi<|assistant|>"
+        );
+    }
+
+    #[test]
+    fn test_generate_request_to_chat() {
+        let request = generate_request(json!({
+            "model": "llama3",
+            "prompt": "Why is the sky blue?",
+            "system": "Answer in one sentence.",
+            "images": ["iVBORw0KGgo="],
+            "format": "json",
+            "options": {"temperature": 0, "num_predict": 64, "stop": ["\n"], "top_k": 40}
+        }));
+
+        let messages = request.to_messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content_as_text(), "Answer in one sentence.");
+        assert_eq!(messages[1].content.len(), 2);
+        assert!(messages[1].has_images());
+
+        let options = request.chat_options(ReasoningMode::default());
+        assert_eq!(options.temperature, Some(0.0));
+        assert_eq!(options.max_tokens, Some(64));
+        assert_eq!(options.stop, Some(vec!["\n".to_string()]));
+        assert_eq!(options.response_format, Some(OutputFormat::JsonObject));
+
+        let raw = generate_request(json!({"model": "llama3", "prompt": "[INST] hi [/INST]", "system": "x", "raw": true}));
+        let messages = raw.to_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content_as_text(), "[INST] hi [/INST]");
+
+        let unload = generate_request(json!({"model": "llama3", "keep_alive": 0}));
+        assert!(unload.is_load_request() && unload.is_unload());
+    }
+}
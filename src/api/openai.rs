@@ -9,10 +9,12 @@ use futures::StreamExt;
 use serde::Deserialize;
 use serde_json::{json, Value};
 use std::convert::Infallible;
-use tracing::{info, warn, error};
+use tracing::{info, error};
 
 use crate::adapters::{ClientAdapter, FormatDetector};
-use crate::api::{AppState, convert};
+use crate::api::{AppState, convert, error_status, with_provider_header, with_upstream_error};
+use crate::normalizer::{ChatOptions, EmbeddingRequest, OutputFormat};
+use crate::settings::ReasoningMode;
 
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
@@ -31,6 +33,63 @@ pub struct OpenAIChatRequest {
     pub tools: Option<Vec<Value>>,
     #[allow(dead_code)]
     pub tool_choice: Option<Value>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub max_completion_tokens: Option<u32>,
+    /// A single string or an array of up to 4 strings
+    #[serde(default)]
+    pub stop: Option<Value>,
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub response_format: Option<Value>,
+    #[serde(default)]
+    pub n: Option<u32>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub stream_options: Option<Value>,
+    /// llm-link extension: `strip`, `passthrough` or `native`, overriding the app's mode
+    #[serde(default)]
+    pub reasoning_mode: Option<ReasoningMode>,
+}
+
+impl OpenAIChatRequest {
+    /// 提取需要透传给上游的请求参数
+    ///
+    /// `default_reasoning` is the app's reasoning mode, used unless the request sets one.
+    pub fn chat_options(&self, default_reasoning: ReasoningMode) -> ChatOptions {
+        let stop = match &self.stop {
+            Some(Value::String(stop)) => Some(vec![stop.clone()]),
+            Some(Value::Array(stops)) => Some(stops.iter().filter_map(|s| s.as_str().map(str::to_string)).collect()),
+            _ => None,
+        };
+
+        ChatOptions {
+            tool_choice: self.tool_choice.clone().and_then(|choice| serde_json::from_value(choice).ok()),
+            temperature: self.temperature,
+            top_p: self.top_p,
+            // max_completion_tokens 是 max_tokens 的新名字，优先使用
+            max_tokens: self.max_completion_tokens.or(self.max_tokens),
+            stop,
+            user: self.user.clone(),
+            presence_penalty: self.presence_penalty,
+            frequency_penalty: self.frequency_penalty,
+            seed: self.seed,
+            response_format: self.response_format.as_ref().and_then(OutputFormat::from_openai),
+            n: self.n,
+            include_usage: self.stream_options.as_ref()
+                .and_then(|options| options["include_usage"].as_bool())
+                .unwrap_or(false),
+            reasoning: self.reasoning_mode.unwrap_or(default_reasoning),
+            enable_thinking: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,33 +104,10 @@ pub async fn chat(
     State(state): State<AppState>,
     Json(request): Json<OpenAIChatRequest>,
 ) -> Result<Response, StatusCode> {
-    // API Key 校验
-    enforce_api_key(&headers, &state).await?;
-
     info!("📝 Received request - model: {}, stream: {:?}, messages count: {}",
           request.model, request.stream, request.messages.len());
 
-    // 验证模型
-    if !request.model.is_empty() {
-        let validation_result = {
-            let llm_service = state.llm_service.read().await;
-            llm_service.validate_model(&request.model).await
-        };
-
-        match validation_result {
-            Ok(false) => {
-                error!("❌ Model validation failed: model '{}' not found", request.model);
-                return Err(StatusCode::BAD_REQUEST);
-            }
-            Err(e) => {
-                error!("❌ Model validation error: {:?}", e);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-            Ok(true) => {
-                info!("✅ Model '{}' validated successfully", request.model);
-            }
-        }
-    }
+    let options = request.chat_options(state.config.read().await.reasoning_mode());
 
     // 转换消息格式
     match convert::openai_messages_to_llm(request.messages) {
@@ -96,10 +132,10 @@ pub async fn chat(
                 if let Some(ref tools_ref) = tools {
                     info!("🔧 Streaming with {} tools (llm-connector 0.5.4+ fix applied)", tools_ref.len());
                 }
-                handle_streaming_request(headers, state, model, messages, tools).await
+                handle_streaming_request(headers, state, model, messages, tools, options).await
             } else {
                 info!("📝 Using non-streaming mode");
-                handle_non_streaming_request(state, model, messages, tools).await
+                handle_non_streaming_request(state, model, messages, tools, &options).await
             }
         }
         Err(e) => {
@@ -117,6 +153,7 @@ async fn handle_streaming_request(
     model: Option<&str>,
     messages: Vec<llm_connector::types::Message>,
     tools: Option<Vec<llm_connector::types::Tool>>,
+    options: ChatOptions,
 ) -> Result<Response, StatusCode> {
     // 🎯 检测客户端类型（默认使用 OpenAI 适配器）
     let config = state.config.read().await;
@@ -130,23 +167,20 @@ async fn handle_streaming_request(
 
     info!("📡 Starting OpenAI streaming response - Format: {:?} ({})", final_format, content_type);
 
-    let llm_service = state.llm_service.read().await;
-    let stream_result = llm_service.chat_stream_openai(model, messages.clone(), tools.clone(), final_format).await;
-    drop(llm_service); // 显式释放锁
+    let llm_service = state.service().await;
+    let stream_result = llm_service.chat_stream_openai(model, messages, tools, &options, final_format).await;
 
     match stream_result {
-        Ok(rx) => {
-            info!("✅ OpenAI streaming response started successfully");
+        Ok(served) => {
+            info!("✅ OpenAI streaming response started successfully (provider: {})", served.provider);
+            let rx = served.output;
 
             // Get config before entering the map closure and clone it for the closure
             let config = state.config.read().await.clone();
             let adapted_stream = rx.map(move |data| {
                 // SSE 格式的数据以 "data: " 开头，需要先提取 JSON 部分
-                let json_str = if data.starts_with("data: ") {
-                    &data[6..] // 去掉 "data: " 前缀
-                } else {
-                    &data
-                };
+                // 去掉 "data: " 前缀
+                let json_str = data.strip_prefix("data: ").unwrap_or(&data);
 
                 // 跳过空行和 [DONE] 标记
                 if json_str.trim().is_empty() || json_str.trim() == "[DONE]" {
@@ -185,11 +219,12 @@ async fn handle_streaming_request(
                 .body(body)
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-            Ok(response)
+            Ok(with_provider_header(response, &served.provider))
         }
+        // 故障切换已经试过所有可用的后端，不再退回非流式重试
         Err(e) => {
-            warn!("⚠️ OpenAI streaming failed, falling back to non-streaming: {:?}", e);
-            handle_non_streaming_request(state, model, messages, tools).await
+            error!("❌ OpenAI streaming request failed: {:?}", e);
+            Ok(with_upstream_error(openai_error(error_status(&e), e.to_string()), &e))
         }
     }
 }
@@ -201,18 +236,19 @@ async fn handle_non_streaming_request(
     model: Option<&str>,
     messages: Vec<llm_connector::types::Message>,
     tools: Option<Vec<llm_connector::types::Tool>>,
+    options: &ChatOptions,
 ) -> Result<Response, StatusCode> {
-    let llm_service = state.llm_service.read().await;
-    let chat_result = llm_service.chat(model, messages, tools).await;
+    let llm_service = state.service().await;
+    let chat_result = llm_service.chat(model, messages, tools, options).await;
 
     match chat_result {
-        Ok(response) => {
-            let openai_response = convert::response_to_openai(response);
-            Ok(Json(openai_response).into_response())
+        Ok(served) => {
+            let openai_response = convert::response_to_openai(served.output);
+            Ok(with_provider_header(Json(openai_response).into_response(), &served.provider))
         }
         Err(e) => {
             error!("❌ OpenAI chat request failed: {:?}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Ok(with_upstream_error(openai_error(error_status(&e), e.to_string()), &e))
         }
     }
 }
@@ -220,13 +256,10 @@ async fn handle_non_streaming_request(
 /// OpenAI Models API
 #[allow(dead_code)]
 pub async fn models(
-    headers: HeaderMap,
     State(state): State<AppState>,
     Query(_params): Query<OpenAIModelsParams>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    enforce_api_key(&headers, &state).await?;
-
-    let llm_service = state.llm_service.read().await;
+    let llm_service = state.service().await;
     let models_result = llm_service.list_models().await;
 
     match models_result {
@@ -240,19 +273,7 @@ pub async fn models(
                 })
             }).collect();
 
-            let config = state.config.read().await;
-            let current_provider = match &config.llm_backend {
-                crate::settings::LlmBackendSettings::OpenAI { .. } => "openai",
-                crate::settings::LlmBackendSettings::Anthropic { .. } => "anthropic",
-                crate::settings::LlmBackendSettings::Zhipu { .. } => "zhipu",
-                crate::settings::LlmBackendSettings::Ollama { .. } => "ollama",
-                crate::settings::LlmBackendSettings::Aliyun { .. } => "aliyun",
-                crate::settings::LlmBackendSettings::Volcengine { .. } => "volcengine",
-                crate::settings::LlmBackendSettings::Tencent { .. } => "tencent",
-                crate::settings::LlmBackendSettings::Longcat { .. } => "longcat",
-                crate::settings::LlmBackendSettings::Moonshot { .. } => "moonshot",
-                crate::settings::LlmBackendSettings::Minimax { .. } => "minimax",
-            };
+            let current_provider = state.config.read().await.llm_backend.provider_name();
 
             let response = json!({
                 "object": "list",
@@ -265,45 +286,132 @@ pub async fn models(
     }
 }
 
-/// OpenAI API Key 认证
-#[allow(dead_code)]
-async fn enforce_api_key(headers: &HeaderMap, state: &AppState) -> Result<(), StatusCode> {
-    let config = state.config.read().await;
-    if let Some(cfg) = &config.apis.openai {
-        if cfg.enabled {
-            if let Some(expected_key) = cfg.api_key.as_ref() {
-                let header_name = cfg.api_key_header.as_deref().unwrap_or("authorization").to_ascii_lowercase();
-                
-                let value_opt = if header_name == "authorization" {
-                    headers.get(axum::http::header::AUTHORIZATION)
-                } else {
-                    match axum::http::HeaderName::from_bytes(header_name.as_bytes()) {
-                        Ok(name) => headers.get(name),
-                        Err(_) => None,
-                    }
-                };
-
-                if let Some(value) = value_opt {
-                    if let Ok(value_str) = value.to_str() {
-                        let token = if value_str.starts_with("Bearer ") {
-                            &value_str[7..]
-                        } else {
-                            value_str
-                        };
-
-                        if token == expected_key {
-                            info!("✅ OpenAI API key authentication successful");
-                            return Ok(());
-                        }
-                    }
-                }
+#[derive(Debug, Deserialize)]
+pub struct OpenAIEmbeddingsRequest {
+    #[serde(default)]
+    pub model: String,
+    /// A string, an array of strings, or token arrays
+    pub input: Value,
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+    /// `float` (default) or `base64`
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub user: Option<String>,
+}
+
+impl OpenAIEmbeddingsRequest {
+    /// 解析 input；token 数组需要上游的 tokenizer 才能还原，不支持
+    pub fn inputs(&self) -> Result<Vec<String>, String> {
+        match &self.input {
+            Value::String(text) => Ok(vec![text.clone()]),
+            Value::Array(items) if !items.is_empty() => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(text) => Ok(text.clone()),
+                    _ => Err("token array inputs are not supported, send text".to_string()),
+                })
+                .collect(),
+            _ => Err("input must be a non-empty string or array of strings".to_string()),
+        }
+    }
+}
 
-                warn!("🚫 OpenAI API key authentication failed");
-                return Err(StatusCode::UNAUTHORIZED);
+/// Little-endian f32 bytes encoded as standard base64, as OpenAI returns them
+fn encode_embedding_base64(vector: &[f32]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
             }
         }
     }
-    Ok(())
+    out
+}
+
+/// Error response in the OpenAI `{"error": {...}}` shape
+pub(crate) fn openai_error(status: StatusCode, message: String) -> Response {
+    let error_type = match status {
+        StatusCode::BAD_REQUEST => "invalid_request_error",
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::PAYMENT_REQUIRED => "insufficient_quota",
+        StatusCode::FORBIDDEN => "permission_error",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limit_error",
+        _ => "api_error",
+    };
+    let body = json!({
+        "error": {
+            "message": message,
+            "type": error_type,
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
+/// OpenAI Embeddings API
+pub async fn embeddings(
+    State(state): State<AppState>,
+    Json(request): Json<OpenAIEmbeddingsRequest>,
+) -> Result<Response, StatusCode> {
+    let input = match request.inputs() {
+        Ok(input) => input,
+        Err(message) => return Ok(openai_error(StatusCode::BAD_REQUEST, message)),
+    };
+    let base64 = match request.encoding_format.as_deref() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Ok(openai_error(
+                StatusCode::BAD_REQUEST,
+                format!("unsupported encoding_format '{}'", other),
+            ))
+        }
+    };
+    info!("🧮 Embeddings request - model: {}, inputs: {}, dimensions: {:?}",
+          request.model, input.len(), request.dimensions);
+
+    let model = if request.model.is_empty() { None } else { Some(request.model.as_str()) };
+    let embedding_request = EmbeddingRequest { input, dimensions: request.dimensions };
+    let llm_service = state.service().await;
+
+    match llm_service.embed(model, &embedding_request).await {
+        Ok(served) => {
+            let output = served.output;
+            let data: Vec<Value> = output.vectors.iter().enumerate().map(|(index, vector)| {
+                let embedding = if base64 { json!(encode_embedding_base64(vector)) } else { json!(vector) };
+                json!({
+                    "object": "embedding",
+                    "index": index,
+                    "embedding": embedding,
+                })
+            }).collect();
+            let body = json!({
+                "object": "list",
+                "data": data,
+                "model": output.model,
+                "usage": {
+                    "prompt_tokens": output.prompt_tokens,
+                    "total_tokens": output.prompt_tokens,
+                }
+            });
+            Ok(with_provider_header(Json(body).into_response(), &served.provider))
+        }
+        Err(e) => {
+            error!("❌ OpenAI embeddings request failed: {:?}", e);
+            Ok(with_upstream_error(openai_error(error_status(&e), e.to_string()), &e))
+        }
+    }
 }
 
 /// 检测 OpenAI 客户端类型
@@ -312,3 +420,75 @@ fn detect_openai_client(_headers: &HeaderMap, _config: &crate::settings::Setting
     // OpenAI API 总是使用 OpenAI 适配器
     ClientAdapter::OpenAI
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_options_from_request() {
+        let request: OpenAIChatRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [],
+            "temperature": 0,
+            "max_tokens": 100,
+            "max_completion_tokens": 200,
+            "stop": "\n\n",
+            "seed": 42,
+            "tool_choice": "required",
+            "response_format": {"type": "json_object"}
+        }))
+        .unwrap();
+
+        let options = request.chat_options(ReasoningMode::default());
+        let mut chat_request = llm_connector::types::ChatRequest::default();
+        options.apply_to(&mut chat_request);
+
+        assert_eq!(chat_request.temperature, Some(0.0));
+        assert_eq!(chat_request.max_tokens, Some(200));
+        assert_eq!(chat_request.stop, Some(vec!["\n\n".to_string()]));
+        assert_eq!(chat_request.seed, Some(42));
+        assert!(matches!(chat_request.tool_choice, Some(llm_connector::types::ToolChoice::Mode(ref mode)) if mode == "required"));
+        assert_eq!(options.response_format, Some(OutputFormat::JsonObject));
+    }
+
+    #[tokio::test]
+    async fn test_chat_routes_unlisted_models() {
+        use crate::service::Service;
+        use crate::settings::{LlmBackendSettings, MockSettings, Settings};
+
+        let mock = |model: &str| LlmBackendSettings::Mock { model: model.to_string(), mock: MockSettings::default() };
+        let mut service = Service::empty();
+        service.add_backend("first", &mock("mock"), &[]).unwrap();
+        service.add_backend("kimi", &mock("mock"), &["kimi-*".to_string()]).unwrap();
+        let state = AppState::new(service, Settings::default());
+
+        for (model, provider) in [("kimi/moonshot-v1-8k", "kimi"), ("kimi-k2", "kimi"), ("first/anything", "first")] {
+            let request = serde_json::from_value(json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "hi"}]
+            }))
+            .unwrap();
+            let response = chat(HeaderMap::new(), State(state.clone()), Json(request)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", model);
+            assert_eq!(response.headers()[crate::api::PROVIDER_HEADER], provider);
+        }
+    }
+
+    #[test]
+    fn test_embeddings_input_and_base64() {
+        let request: OpenAIEmbeddingsRequest = serde_json::from_value(json!({
+            "model": "text-embedding-3-small",
+            "input": ["a", "b"]
+        }))
+        .unwrap();
+        assert_eq!(request.inputs().unwrap(), vec!["a".to_string(), "b".to_string()]);
+
+        let tokens: OpenAIEmbeddingsRequest = serde_json::from_value(json!({"input": [[1, 2, 3]]})).unwrap();
+        assert!(tokens.inputs().is_err());
+
+        // 1.0f32 = 00 00 80 3f
+        assert_eq!(encode_embedding_base64(&[1.0]), "AACAPw==");
+        assert_eq!(encode_embedding_base64(&[1.0, -2.0]), "AACAPwAAAMA=");
+    }
+}
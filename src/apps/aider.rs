@@ -1,7 +1,7 @@
 use crate::settings::{
     Settings, ServerSettings, LlmBackendSettings, ApiSettings,
     OpenAiApiSettings, OllamaApiSettings, AnthropicApiSettings,
-    ClientAdapterSettings, ZedAdapterSettings,
+    ClientAdapterSettings, RateLimitSettings, ZedAdapterSettings,
 };
 
 /// Aider application configuration
@@ -22,6 +22,7 @@ impl AiderApp {
                 base_url: None,
                 model: "glm-4.6".to_string(),
             },
+            routes: Vec::new(),
             apis: ApiSettings {
                 openai: Some(OpenAiApiSettings {
                     enabled: true,
@@ -40,8 +41,10 @@ impl AiderApp {
                     enabled: false,
                     path: "/anthropic".to_string(),
                     api_key_header: None,
+                    api_key: None,
                 }),
             },
+            rate_limits: RateLimitSettings::default(),
             client_adapters: Some(ClientAdapterSettings {
                 default_adapter: Some("openai".to_string()),
                 force_adapter: Some("openai".to_string()),
@@ -50,6 +53,7 @@ impl AiderApp {
                     force_images_field: Some(false),
                     preferred_format: Some("json".to_string()),
                 }),
+                reasoning_mode: None,
             }),
         }
     }
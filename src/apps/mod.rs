@@ -31,6 +31,7 @@ pub enum SupportedApp {
 
 impl SupportedApp {
     /// Parse application type from string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "codex-cli" | "codex" => Some(Self::CodexCLI),
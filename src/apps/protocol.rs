@@ -1,7 +1,7 @@
 use crate::settings::{
     Settings, ServerSettings, LlmBackendSettings, ApiSettings,
     OpenAiApiSettings, OllamaApiSettings, AnthropicApiSettings,
-    ClientAdapterSettings, ZedAdapterSettings,
+    ClientAdapterSettings, RateLimitSettings, ZedAdapterSettings,
 };
 
 /// Generate protocol combination configuration
@@ -35,6 +35,7 @@ pub fn generate_protocol_config(protocols: &[String], cli_api_key: Option<&str>)
                     enabled: true,
                     path: "/anthropic".to_string(),
                     api_key_header: Some("x-api-key".to_string()),
+                    api_key: cli_api_key.map(|k| k.to_string()),
                 });
             },
             _ => {
@@ -55,11 +56,13 @@ pub fn generate_protocol_config(protocols: &[String], cli_api_key: Option<&str>)
             base_url: Some("https://open.bigmodel.cn/api/paas/v4".to_string()),
             model: "glm-4-flash".to_string(),
         },
+        routes: Vec::new(),
         apis: ApiSettings {
             openai: openai_config,
             ollama: ollama_config,
             anthropic: anthropic_config,
         },
+        rate_limits: RateLimitSettings::default(),
         client_adapters: Some(ClientAdapterSettings {
             default_adapter: Some("auto".to_string()),
             force_adapter: None,
@@ -68,6 +71,7 @@ pub fn generate_protocol_config(protocols: &[String], cli_api_key: Option<&str>)
                 force_images_field: Some(true),
                 preferred_format: Some("ndjson".to_string()),
             }),
+            reasoning_mode: None,
         }),
     }
 }
@@ -1,7 +1,7 @@
 use crate::settings::{
     Settings, ServerSettings, LlmBackendSettings, ApiSettings,
     OpenAiApiSettings, OllamaApiSettings, AnthropicApiSettings,
-    ClientAdapterSettings, ZedAdapterSettings,
+    ClientAdapterSettings, RateLimitSettings, ZedAdapterSettings, ReasoningMode,
 };
 /// Zed.dev application configuration
 pub struct ZedApp;
@@ -19,6 +19,7 @@ impl ZedApp {
                 base_url: Some("http://localhost:11435".to_string()),
                 model: "llama2".to_string(),
             },
+            routes: Vec::new(),
             apis: ApiSettings {
                 openai: Some(OpenAiApiSettings {
                     enabled: false,  // Disabled by default - use --protocols openai to enable
@@ -36,8 +37,10 @@ impl ZedApp {
                     enabled: false,
                     path: "/anthropic".to_string(),
                     api_key_header: None,
+                    api_key: None,
                 }),
             },
+            rate_limits: RateLimitSettings::default(),
             client_adapters: Some(ClientAdapterSettings {
                 default_adapter: Some("zed".to_string()),
                 force_adapter: Some("zed".to_string()),
@@ -46,6 +49,8 @@ impl ZedApp {
                     force_images_field: Some(true),
                     preferred_format: Some("ndjson".to_string()),
                 }),
+                // Zed 不展示思考过程，只返回最终回答
+                reasoning_mode: Some(ReasoningMode::Strip),
             }),
         }
     }
@@ -0,0 +1,627 @@
+use crate::api::auth::KeyScope;
+use crate::api::inspect::RequestInfo;
+use crate::api::{AppState, PROVIDER_HEADER};
+use crate::metrics::{client_adapter, is_stream};
+use crate::usage::UsageScanner;
+use anyhow::{anyhow, Result};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::StreamExt;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+static AUDIT_LOG: OnceCell<AuditLog> = OnceCell::new();
+
+/// API keys and bearer tokens of the common providers and of llm-link itself
+const KEY_PATTERNS: &[&str] = &[
+    r"\b(?:sk|pk|rk)-[A-Za-z0-9_\-]{16,}",
+    r"\bllk-[0-9a-f]{16,}",
+    r"\bAIza[0-9A-Za-z_\-]{35}",
+    r"\bAKIA[0-9A-Z]{16}\b",
+    r"(?i)\bbearer\s+[A-Za-z0-9._~+/\-]+=*",
+];
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}";
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Where audit lines go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+impl AuditTarget {
+    /// `-` is stdout, anything else a file path
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "-" => AuditTarget::Stdout,
+            path => AuditTarget::File(PathBuf::from(path)),
+        }
+    }
+}
+
+/// When the audit log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Daily,
+    /// Once the file would grow past this many bytes
+    Size(u64),
+}
+
+impl Rotation {
+    /// `daily`, or a size such as `500KB`, `100MB`, `1GB`
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("daily") {
+            return Ok(Rotation::Daily);
+        }
+        let upper = value.to_ascii_uppercase();
+        let (number, unit) = match upper.find(|c: char| !c.is_ascii_digit()) {
+            Some(index) => upper.split_at(index),
+            None => (upper.as_str(), "B"),
+        };
+        let multiplier = match unit.trim() {
+            "B" => 1,
+            "K" | "KB" => 1024,
+            "M" | "MB" => 1024 * 1024,
+            "G" | "GB" => 1024 * 1024 * 1024,
+            _ => return Err(anyhow!("Invalid audit log rotation '{}': use 'daily' or a size like '100MB'", value)),
+        };
+        let size: u64 = number
+            .parse()
+            .map_err(|_| anyhow!("Invalid audit log rotation '{}': use 'daily' or a size like '100MB'", value))?;
+        if size == 0 {
+            return Err(anyhow!("Audit log rotation size must be greater than zero"));
+        }
+        Ok(Rotation::Size(size * multiplier))
+    }
+}
+
+/// Replaces secrets and personal data in audit records
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub fn new(api_keys: bool, emails: bool, custom: &[String]) -> Result<Self> {
+        let mut patterns = Vec::new();
+        if api_keys {
+            patterns.extend(KEY_PATTERNS.iter().map(|pattern| Regex::new(pattern).expect("valid key pattern")));
+        }
+        if emails {
+            patterns.push(Regex::new(EMAIL_PATTERN).expect("valid email pattern"));
+        }
+        for pattern in custom {
+            patterns.push(Regex::new(pattern).map_err(|e| anyhow!("Invalid redaction pattern '{}': {}", pattern, e))?);
+        }
+        Ok(Self { patterns })
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for pattern in &self.patterns {
+            if pattern.is_match(&text) {
+                text = pattern.replace_all(&text, REDACTED).into_owned();
+            }
+        }
+        text
+    }
+
+    /// Redact every string in `value`
+    pub fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::Object(map) => map.values_mut().for_each(|item| self.redact_value(item)),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub target: AuditTarget,
+    /// Also record request messages and the response text
+    pub include_messages: bool,
+    pub redactor: Redactor,
+    pub rotation: Option<Rotation>,
+}
+
+struct AuditLog {
+    include_messages: bool,
+    redactor: Redactor,
+    lines: mpsc::Sender<String>,
+}
+
+/// Start the audit log writer; later calls are no-ops
+///
+/// Lines are written from a dedicated thread so requests never wait on disk.
+pub fn init(config: AuditConfig) -> Result<()> {
+    if AUDIT_LOG.get().is_some() {
+        return Ok(());
+    }
+    let mut writer = AuditWriter::open(config.target, config.rotation)?;
+    let (lines, receiver) = mpsc::channel::<String>();
+    std::thread::Builder::new().name("audit-log".to_string()).spawn(move || {
+        for line in receiver {
+            if let Err(e) = writer.write_line(&line, Utc::now()) {
+                error!("❌ Failed to write audit log: {}", e);
+            }
+        }
+    })?;
+
+    let _ = AUDIT_LOG.set(AuditLog {
+        include_messages: config.include_messages,
+        redactor: config.redactor,
+        lines,
+    });
+    Ok(())
+}
+
+/// Audit log destination, rotating files by size or day
+struct AuditWriter {
+    target: AuditTarget,
+    rotation: Option<Rotation>,
+    file: Option<File>,
+    size: u64,
+    day: NaiveDate,
+}
+
+impl AuditWriter {
+    fn open(target: AuditTarget, rotation: Option<Rotation>) -> Result<Self> {
+        let mut writer = Self { target, rotation, file: None, size: 0, day: Utc::now().date_naive() };
+        if let AuditTarget::File(path) = &writer.target {
+            if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            // 沿用已有文件：按它的修改日期判断是否已经跨天
+            if let Ok(metadata) = std::fs::metadata(path) {
+                writer.size = metadata.len();
+                if let Ok(modified) = metadata.modified() {
+                    writer.day = DateTime::<Utc>::from(modified).date_naive();
+                }
+            }
+            writer.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        }
+        Ok(writer)
+    }
+
+    fn write_line(&mut self, line: &str, now: DateTime<Utc>) -> Result<()> {
+        let AuditTarget::File(path) = self.target.clone() else {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", line)?;
+            return Ok(stdout.flush()?);
+        };
+
+        let length = line.len() as u64 + 1;
+        let suffix = match self.rotation {
+            Some(Rotation::Daily) if self.size > 0 && now.date_naive() != self.day => Some(self.day.format("%Y-%m-%d").to_string()),
+            Some(Rotation::Size(max)) if self.size > 0 && self.size + length > max => {
+                Some(now.format("%Y%m%d-%H%M%S").to_string())
+            }
+            _ => None,
+        };
+        if let Some(suffix) = suffix {
+            self.file = None;
+            std::fs::rename(&path, rotated_path(&path, &suffix))?;
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+            self.size = 0;
+        }
+        self.day = now.date_naive();
+
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => self.file.insert(OpenOptions::new().create(true).append(true).open(&path)?),
+        };
+        writeln!(file, "{}", line)?;
+        self.size += length;
+        Ok(())
+    }
+}
+
+/// `audit.log` -> `audit.log.2025-01-31`, numbered if that is taken
+fn rotated_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let candidate = path.with_file_name(format!("{}.{}", name, suffix));
+    if !candidate.exists() {
+        return candidate;
+    }
+    (1..)
+        .map(|n| path.with_file_name(format!("{}.{}.{}", name, suffix, n)))
+        .find(|candidate| !candidate.exists())
+        .expect("a free file name")
+}
+
+#[derive(Debug, Default, Serialize)]
+struct AuditToolCall {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Text and tool calls of a response body
+///
+/// Like [`UsageScanner`] this works line by line, covering the JSON bodies
+/// and the SSE / NDJSON streams of all three frontends.
+#[derive(Debug, Default)]
+struct ResponseScanner {
+    line: Vec<u8>,
+    output: String,
+    tool_calls: BTreeMap<u64, AuditToolCall>,
+}
+
+impl ResponseScanner {
+    fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                self.scan_line(&line);
+            } else {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.scan_line(&line);
+    }
+
+    fn scan_line(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        let line = line.strip_prefix("data:").map(str::trim_start).unwrap_or(line);
+        if !line.starts_with('{') {
+            return;
+        }
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+
+        // OpenAI: message (非流式) 或 delta (流式)
+        if let Some(choice) = value["choices"].get(0) {
+            let message = if choice["delta"].is_object() { &choice["delta"] } else { &choice["message"] };
+            self.push_text(&message["content"]);
+            self.push_tool_calls(&message["tool_calls"], true);
+            return;
+        }
+        // Ollama chat / generate
+        if value["message"].is_object() && value.get("done").is_some() {
+            self.push_text(&value["message"]["content"]);
+            self.push_tool_calls(&value["message"]["tool_calls"], false);
+            return;
+        }
+        self.push_text(&value["response"]);
+
+        // Anthropic
+        let index = value["index"].as_u64().unwrap_or(0);
+        match value["type"].as_str() {
+            Some("message") => {
+                for (index, block) in value["content"].as_array().into_iter().flatten().enumerate() {
+                    self.push_anthropic_block(index as u64, block);
+                }
+            }
+            Some("content_block_start") => self.push_anthropic_block(index, &value["content_block"]),
+            Some("content_block_delta") => match value["delta"]["type"].as_str() {
+                Some("text_delta") => self.push_text(&value["delta"]["text"]),
+                Some("input_json_delta") => {
+                    if let (Some(call), Some(json)) = (self.tool_calls.get_mut(&index), value["delta"]["partial_json"].as_str()) {
+                        call.arguments.push_str(json);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn push_text(&mut self, text: &Value) {
+        if let Some(text) = text.as_str() {
+            self.output.push_str(text);
+        }
+    }
+
+    /// OpenAI / Ollama tool calls; OpenAI streams send them in pieces keyed by `index`
+    fn push_tool_calls(&mut self, calls: &Value, indexed: bool) {
+        for call in calls.as_array().into_iter().flatten() {
+            let index = match call["index"].as_u64() {
+                Some(index) if indexed => index,
+                _ => self.tool_calls.len() as u64,
+            };
+            let entry = self.tool_calls.entry(index).or_default();
+            if let Some(id) = call["id"].as_str().filter(|id| !id.is_empty()) {
+                entry.id = id.to_string();
+            }
+            if let Some(name) = call["function"]["name"].as_str().filter(|name| !name.is_empty()) {
+                entry.name = name.to_string();
+            }
+            match &call["function"]["arguments"] {
+                Value::String(arguments) => entry.arguments.push_str(arguments),
+                Value::Null => {}
+                arguments => entry.arguments = arguments.to_string(),
+            }
+        }
+    }
+
+    fn push_anthropic_block(&mut self, index: u64, block: &Value) {
+        match block["type"].as_str() {
+            Some("text") => self.push_text(&block["text"]),
+            Some("tool_use") => {
+                let input = &block["input"];
+                let arguments = match input {
+                    // 流式时 input 为空对象，参数随 input_json_delta 到达
+                    Value::Object(map) if map.is_empty() => String::new(),
+                    Value::Null => String::new(),
+                    input => input.to_string(),
+                };
+                self.tool_calls.insert(
+                    index,
+                    AuditToolCall {
+                        id: block["id"].as_str().unwrap_or_default().to_string(),
+                        name: block["name"].as_str().unwrap_or_default().to_string(),
+                        arguments,
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AuditUsage {
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct AuditTiming {
+    started_at: DateTime<Utc>,
+    latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_chunk_ms: Option<u64>,
+}
+
+/// One line of the audit log
+#[derive(Debug, Serialize)]
+struct AuditRecord {
+    request_id: String,
+    frontend: &'static str,
+    client_adapter: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    model: Option<String>,
+    provider: Option<String>,
+    stream: bool,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    messages: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    tool_calls: Vec<AuditToolCall>,
+    usage: AuditUsage,
+    timing: AuditTiming,
+}
+
+/// Audit record of a request whose response body is still being sent,
+/// written when the body is dropped
+struct PendingAudit {
+    record: AuditRecord,
+    started: Instant,
+    first_chunk: Option<Duration>,
+    usage: UsageScanner,
+    response: ResponseScanner,
+}
+
+impl Drop for PendingAudit {
+    fn drop(&mut self) {
+        let Some(log) = AUDIT_LOG.get() else {
+            return;
+        };
+        self.usage.finish();
+        self.response.finish();
+
+        let record = &mut self.record;
+        record.timing.latency_ms = self.started.elapsed().as_millis() as u64;
+        record.timing.first_chunk_ms = self.first_chunk.map(|elapsed| elapsed.as_millis() as u64);
+        record.usage = AuditUsage {
+            prompt_tokens: self.usage.prompt_tokens,
+            completion_tokens: self.usage.completion_tokens,
+        };
+        if self.usage.failed && record.status < 400 {
+            record.status = StatusCode::INTERNAL_SERVER_ERROR.as_u16();
+        }
+        record.tool_calls = std::mem::take(&mut self.response.tool_calls).into_values().collect();
+        if log.include_messages {
+            record.output = Some(std::mem::take(&mut self.response.output));
+        }
+
+        match serde_json::to_value(&*record) {
+            Ok(mut value) => {
+                log.redactor.redact_value(&mut value);
+                let _ = log.lines.send(value.to_string());
+            }
+            Err(e) => error!("❌ Failed to serialize audit record: {}", e),
+        }
+    }
+}
+
+/// The conversation part of a request body: `system`, `messages` and `prompt`
+fn request_messages(body: &[u8]) -> Option<Value> {
+    let body = serde_json::from_slice::<Value>(body).ok()?;
+    let messages: serde_json::Map<String, Value> = ["system", "messages", "prompt", "input"]
+        .into_iter()
+        .filter_map(|field| body.get(field).map(|value| (field.to_string(), value.clone())))
+        .collect();
+    (!messages.is_empty()).then_some(Value::Object(messages))
+}
+
+/// Audit log middleware for the inference endpoints of all frontends
+///
+/// Writes one JSON line per request once its response has been sent. The
+/// request id is taken from `x-request-id` (or generated) and echoed back.
+pub async fn record(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(log) = AUDIT_LOG.get() else {
+        return next.run(request).await;
+    };
+    let Some(info) = RequestInfo::inference(&request) else {
+        return next.run(request).await;
+    };
+    let (frontend, model) = (info.frontend, info.model.clone());
+    let messages = if log.include_messages { request_messages(&info.body) } else { None };
+    let adapter = client_adapter(frontend, request.headers(), &*state.config.read().await);
+
+    let started = Instant::now();
+    let started_at = Utc::now();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut response = next.run(request).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    let provider = response
+        .headers()
+        .get(PROVIDER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let key = response.extensions().get::<KeyScope>().map(|scope| scope.name.clone());
+
+    let mut pending = PendingAudit {
+        record: AuditRecord {
+            request_id,
+            frontend: frontend.as_str(),
+            client_adapter: adapter,
+            key,
+            model,
+            provider,
+            stream: is_stream(response.headers()),
+            status: response.status().as_u16(),
+            messages,
+            output: None,
+            tool_calls: Vec::new(),
+            usage: AuditUsage { prompt_tokens: None, completion_tokens: None },
+            timing: AuditTiming { started_at, latency_ms: 0, first_chunk_ms: None },
+        },
+        started,
+        first_chunk: None,
+        usage: UsageScanner::default(),
+        response: ResponseScanner::default(),
+    };
+
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                if pending.first_chunk.is_none() && !bytes.is_empty() {
+                    pending.first_chunk = Some(pending.started.elapsed());
+                }
+                pending.usage.push(bytes);
+                pending.response.push(bytes);
+            }
+            chunk
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_redactor() {
+        let redactor = Redactor::new(true, true, &["ticket-\\d+".to_string()]).unwrap();
+        let mut value = serde_json::json!({
+            "messages": [{"role": "user", "content": "key sk-abcdefghijklmnop1234 from bob@example.com, see ticket-42"}],
+            "auth": "Bearer eyJhbGciOi.abc",
+            "tokens": 12
+        });
+        redactor.redact_value(&mut value);
+        assert_eq!(value["messages"][0]["content"], "key [REDACTED] from [REDACTED], see [REDACTED]");
+        assert_eq!(value["auth"], "[REDACTED]");
+        assert_eq!(value["tokens"], 12);
+
+        let keys_only = Redactor::new(true, false, &[]).unwrap();
+        assert_eq!(keys_only.redact("llk-0123456789abcdef0123 bob@example.com"), "[REDACTED] bob@example.com");
+        assert!(Redactor::new(false, false, &["(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_response_scanner() {
+        // OpenAI stream, tool call arguments split across chunks
+        let mut scanner = ResponseScanner::default();
+        scanner.push(b"data: {\"choices\":[{\"delta\":{\"content\":\"Let me check\"}}]}\n\n");
+        scanner.push(b"data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"{\\\"city\\\"\"}}]}}]}\n\n");
+        scanner.push(b"data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\":\\\"Paris\\\"}\"}}]}}]}\n\ndata: [DONE]\n\n");
+        scanner.finish();
+        assert_eq!(scanner.output, "Let me check");
+        let call = &scanner.tool_calls[&0];
+        assert_eq!((call.id.as_str(), call.name.as_str(), call.arguments.as_str()), ("call_1", "get_weather", "{\"city\":\"Paris\"}"));
+
+        // Anthropic stream
+        let mut scanner = ResponseScanner::default();
+        scanner.push(b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n");
+        scanner.push(b"data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"search\",\"input\":{}}}\n\n");
+        scanner.push(b"data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"q\\\":1}\"}}\n\n");
+        scanner.finish();
+        assert_eq!(scanner.output, "Hi");
+        assert_eq!(scanner.tool_calls[&1].arguments, "{\"q\":1}");
+
+        // Ollama non-stream response without a trailing newline
+        let mut scanner = ResponseScanner::default();
+        scanner.push(b"{\"message\":{\"content\":\"\",\"tool_calls\":[{\"function\":{\"name\":\"ls\",\"arguments\":{\"path\":\"/\"}}}]},\"done\":true}");
+        scanner.finish();
+        assert_eq!(scanner.tool_calls[&0].arguments, "{\"path\":\"/\"}");
+    }
+
+    #[test]
+    fn test_audit_writer_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let day = |d: u32| Utc.with_ymd_and_hms(2025, 1, d, 12, 0, 0).unwrap();
+
+        let mut writer = AuditWriter::open(AuditTarget::File(path.clone()), Some(Rotation::Size(10))).unwrap();
+        writer.write_line("0123456", day(1)).unwrap();
+        writer.write_line("0123456", day(1)).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "0123456\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("audit.log.20250101-120000")).unwrap(), "0123456\n");
+
+        let mut writer = AuditWriter::open(AuditTarget::File(path.clone()), Some(Rotation::Daily)).unwrap();
+        writer.day = day(1).date_naive();
+        writer.write_line("first", day(1)).unwrap();
+        writer.write_line("second", day(2)).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("audit.log.2025-01-01")).unwrap(), "0123456\nfirst\n");
+
+        assert_eq!(Rotation::parse("100MB").unwrap(), Rotation::Size(100 * 1024 * 1024));
+        assert_eq!(Rotation::parse("Daily").unwrap(), Rotation::Daily);
+        assert!(Rotation::parse("often").is_err());
+    }
+}
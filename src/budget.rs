@@ -0,0 +1,164 @@
+use crate::api::auth::{Frontend, KeyScope};
+use crate::api::inspect::RequestInfo;
+use crate::api::AppState;
+use crate::service::RouteFilter;
+use crate::db::Budget;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use tracing::{error, warn};
+
+/// Whether `budget` covers a request made with `key` and routed to `provider`
+fn applies_to(budget: &Budget, key: Option<&str>, provider: Option<&str>) -> bool {
+    let target = if budget.api_key_id.is_some() { key } else { provider };
+    budget.enabled && target == Some(budget.target.as_str())
+}
+
+/// Budget middleware for one frontend
+///
+/// Runs inside [`require_api_key`](crate::api::auth::require_api_key) so the
+/// key is known. Requests whose key has used up its budget are rejected with
+/// a 402 in the frontend's error format. A provider that has used up its
+/// budget is taken out of routing, so failover moves on to the next one and
+/// spend lands on the provider that actually served the request; only when
+/// no provider is left is the request rejected. Crossing a budget's warning
+/// threshold is logged once per period.
+pub async fn enforce(
+    State((state, frontend)): State<(AppState, Frontend)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(db) = state.db.clone() else {
+        return next.run(request).await;
+    };
+    let Some(model) = RequestInfo::inference(&request).map(|info| info.model.clone()) else {
+        return next.run(request).await;
+    };
+
+    let now = Utc::now();
+    let budgets = match db.current_budgets(now).await {
+        Ok(budgets) => budgets,
+        Err(e) => {
+            error!("❌ Failed to load budgets: {}", e);
+            return frontend.error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load budgets".to_string());
+        }
+    };
+    if !budgets.iter().any(|budget| budget.enabled) {
+        return next.run(request).await;
+    }
+
+    let key = request.extensions().get::<KeyScope>().map(|scope| scope.name.clone());
+    let mut providers = Vec::new();
+    if budgets.iter().any(|budget| budget.enabled && budget.provider_id.is_some()) {
+        providers = state.llm_service.read().await.providers_for(model.as_deref());
+    }
+
+    let applicable = budgets.iter().filter(|budget| {
+        applies_to(budget, key.as_deref(), None)
+            || providers.iter().any(|provider| applies_to(budget, None, Some(provider)))
+    });
+    let mut exhausted = Vec::new();
+    for budget in applicable {
+        if budget.is_exhausted() {
+            let message = format!(
+                "The {} budget for {} is exhausted (${:.4} of ${:.4} spent)",
+                budget.period.as_str(),
+                budget.owner(),
+                budget.spent,
+                budget.limit_usd
+            );
+            warn!("💸 {}", message);
+            if budget.api_key_id.is_some() {
+                return frontend.error(StatusCode::PAYMENT_REQUIRED, message);
+            }
+            exhausted.push((budget.target.clone(), message));
+            continue;
+        }
+        if budget.warned_at.is_none() && budget.is_over_threshold() {
+            warn!(
+                "⚠️ Budget for {} crossed its warning threshold: ${:.4} of ${:.4} spent",
+                budget.owner(),
+                budget.spent,
+                budget.limit_usd
+            );
+            if let Err(e) = db.mark_budget_warned(budget.id, now).await {
+                error!("❌ Failed to update budget {}: {}", budget.id, e);
+            }
+        }
+    }
+
+    if exhausted.is_empty() {
+        return next.run(request).await;
+    }
+    if providers.iter().all(|provider| exhausted.iter().any(|(target, _)| target == provider)) {
+        let (_, message) = exhausted.swap_remove(0);
+        return frontend.error(StatusCode::PAYMENT_REQUIRED, message);
+    }
+    let mut filter = RouteFilter::current();
+    filter.excluded.extend(exhausted.into_iter().map(|(provider, _)| provider));
+    filter.scope(next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::BudgetPeriod;
+    use chrono::{DateTime, TimeZone};
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 30, 0).unwrap()
+    }
+
+    fn budget(period: BudgetPeriod, spent: f64) -> Budget {
+        Budget {
+            id: 1,
+            api_key_id: Some(1),
+            provider_id: None,
+            target: "ci".to_string(),
+            period,
+            limit_usd: 10.0,
+            warn_threshold: Some(0.8),
+            auto_reset: true,
+            period_start: period.start_of(at(2025, 1, 31, 12)),
+            warned_at: None,
+            enabled: true,
+            spent,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_budget_periods() {
+        assert_eq!(BudgetPeriod::Daily.start_of(at(2025, 1, 31, 12)), Utc.with_ymd_and_hms(2025, 1, 31, 0, 0, 0).unwrap());
+        assert_eq!(BudgetPeriod::Monthly.start_of(at(2025, 1, 31, 12)), Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(BudgetPeriod::Daily.next_start(at(2025, 1, 31, 12)), Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap());
+        assert_eq!(BudgetPeriod::Monthly.next_start(at(2025, 1, 31, 12)), Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap());
+        assert_eq!(BudgetPeriod::Monthly.next_start(at(2025, 12, 5, 0)), Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap());
+
+        let daily = budget(BudgetPeriod::Daily, 8.5);
+        assert!(daily.is_over_threshold() && !daily.is_exhausted());
+        assert!(!daily.is_due_for_reset(at(2025, 1, 31, 23)));
+        assert!(daily.is_due_for_reset(at(2025, 2, 1, 0)));
+        assert!(!budget(BudgetPeriod::Monthly, 10.0).is_due_for_reset(at(2025, 1, 31, 23)));
+        assert!(budget(BudgetPeriod::Monthly, 10.0).is_exhausted());
+    }
+
+    #[test]
+    fn test_budget_applies_to() {
+        let mut budget = budget(BudgetPeriod::Daily, 0.0);
+        assert!(applies_to(&budget, Some("ci"), Some("ci")));
+        assert!(!applies_to(&budget, Some("other"), Some("ci")));
+        assert!(!applies_to(&budget, None, None));
+
+        budget.api_key_id = None;
+        budget.provider_id = Some(3);
+        assert!(applies_to(&budget, None, Some("ci")));
+        budget.enabled = false;
+        assert!(!applies_to(&budget, None, Some("ci")));
+    }
+}
@@ -0,0 +1,319 @@
+//! Response cache for deterministic requests (`--cache`)
+//!
+//! Requests sent with `temperature: 0` are keyed by a hash of the provider
+//! and the normalized upstream request (model, messages, tools, sampling
+//! parameters). Streaming and non-streaming calls share entries; hits for
+//! streaming callers are replayed as a synthetic stream.
+
+use crate::normalizer::Response;
+use anyhow::{anyhow, Result};
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response as HttpResponse};
+use llm_connector::types::ChatRequest;
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Response header telling whether a cacheable request was served from the cache
+pub const CACHE_HEADER: &str = "x-llm-link-cache";
+
+static CACHE: OnceCell<Arc<ResponseCache>> = OnceCell::new();
+
+tokio::task_local! {
+    /// Lookup result of the request being handled: `Some(true)` for a hit
+    static STATUS: Cell<Option<bool>>;
+}
+
+/// Where cached responses are kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheBackend {
+    /// In-process LRU holding at most this many responses
+    Memory(usize),
+    /// SQLite database file, shared across restarts
+    Sqlite(PathBuf),
+}
+
+impl CacheBackend {
+    /// `memory` or `sqlite:<PATH>`
+    pub fn parse(value: &str, capacity: usize) -> Result<Self> {
+        match value.trim() {
+            "memory" => Ok(CacheBackend::Memory(capacity)),
+            other => match other.strip_prefix("sqlite:") {
+                Some(path) if !path.is_empty() => Ok(CacheBackend::Sqlite(PathBuf::from(path))),
+                _ => Err(anyhow!("Invalid cache backend '{}': use 'memory' or 'sqlite:<PATH>'", value)),
+            },
+        }
+    }
+}
+
+/// A TTL such as `90s`, `30m`, `24h` or `7d`; plain numbers are seconds
+pub fn parse_ttl(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(anyhow!("Invalid cache TTL '{}': use a duration like '30m' or '24h'", value)),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid cache TTL '{}': use a duration like '30m' or '24h'", value))?;
+    Ok(Duration::from_secs(number * multiplier))
+}
+
+/// Cache key of `request`, or `None` if its reply is not deterministic
+///
+/// `stream` and `user` do not change the reply and are left out, so the
+/// same prompt hits the cache whichever way it is sent.
+pub fn key(provider: &str, request: &ChatRequest) -> Option<String> {
+    if request.temperature != Some(0.0) {
+        return None;
+    }
+    let mut normalized = request.clone();
+    normalized.stream = None;
+    normalized.user = None;
+    // Value 的对象键有序，哈希与字段顺序无关
+    let canonical = serde_json::to_value(&normalized).ok()?.to_string();
+    Some(hex::encode(Sha256::digest(format!("{}\n{}", provider, canonical).as_bytes())))
+}
+
+struct MemoryEntry {
+    response: Response,
+    stored: Instant,
+    last_used: u64,
+}
+
+/// LRU map: the least recently used entry is evicted when full
+struct MemoryStore {
+    capacity: usize,
+    entries: HashMap<String, MemoryEntry>,
+    clock: u64,
+}
+
+impl MemoryStore {
+    fn get(&mut self, key: &str, ttl: Duration) -> Option<Response> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        if entry.stored.elapsed() >= ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        entry.last_used = self.clock;
+        Some(entry.response.clone())
+    }
+
+    fn put(&mut self, key: String, response: Response) {
+        self.clock += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, MemoryEntry { response, stored: Instant::now(), last_used: self.clock });
+    }
+}
+
+enum Store {
+    Memory(Mutex<MemoryStore>),
+    Sqlite(SqlitePool),
+}
+
+pub struct ResponseCache {
+    store: Store,
+    ttl: Duration,
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs() as i64).unwrap_or_default()
+}
+
+impl ResponseCache {
+    pub fn memory(capacity: usize, ttl: Duration) -> Self {
+        let store = MemoryStore { capacity: capacity.max(1), entries: HashMap::new(), clock: 0 };
+        Self { store: Store::Memory(Mutex::new(store)), ttl }
+    }
+
+    pub async fn sqlite(path: PathBuf, ttl: Duration) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(&path).create_if_missing(true)).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS response_cache (
+                key TEXT PRIMARY KEY,
+                response TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self { store: Store::Sqlite(pool), ttl })
+    }
+
+    pub async fn open(backend: CacheBackend, ttl: Duration) -> Result<Self> {
+        match backend {
+            CacheBackend::Memory(capacity) => Ok(Self::memory(capacity, ttl)),
+            CacheBackend::Sqlite(path) => Self::sqlite(path, ttl).await,
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Response> {
+        match &self.store {
+            Store::Memory(store) => store.lock().unwrap().get(key, self.ttl),
+            Store::Sqlite(pool) => {
+                let expired_before = unix_now() - self.ttl.as_secs() as i64;
+                let row: Option<String> =
+                    sqlx::query_scalar("SELECT response FROM response_cache WHERE key = ? AND created_at > ?")
+                        .bind(key)
+                        .bind(expired_before)
+                        .fetch_optional(pool)
+                        .await
+                        .map_err(|e| tracing::warn!("⚠️ Response cache lookup failed: {}", e))
+                        .ok()?;
+                serde_json::from_str(&row?).ok()
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, response: &Response) {
+        match &self.store {
+            Store::Memory(store) => store.lock().unwrap().put(key.to_string(), response.clone()),
+            Store::Sqlite(pool) => {
+                let Ok(json) = serde_json::to_string(response) else {
+                    return;
+                };
+                let now = unix_now();
+                let stored = sqlx::query("INSERT OR REPLACE INTO response_cache (key, response, created_at) VALUES (?, ?, ?)")
+                    .bind(key)
+                    .bind(json)
+                    .bind(now)
+                    .execute(pool)
+                    .await
+                    .and(
+                        sqlx::query("DELETE FROM response_cache WHERE created_at <= ?")
+                            .bind(now - self.ttl.as_secs() as i64)
+                            .execute(pool)
+                            .await,
+                    );
+                if let Err(e) = stored {
+                    tracing::warn!("⚠️ Failed to store cached response: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Enable the response cache for all clients created afterwards
+pub async fn init(backend: CacheBackend, ttl: Duration) -> Result<()> {
+    let cache = ResponseCache::open(backend, ttl).await?;
+    let _ = CACHE.set(Arc::new(cache));
+    Ok(())
+}
+
+pub fn configured() -> Option<Arc<ResponseCache>> {
+    CACHE.get().cloned()
+}
+
+/// Note a cache lookup for the request being handled
+pub fn mark(hit: bool) {
+    let _ = STATUS.try_with(|status| status.set(Some(hit)));
+}
+
+/// Adds `x-llm-link-cache: hit|miss` to responses of cacheable requests
+pub async fn annotate(request: Request, next: Next) -> HttpResponse {
+    if CACHE.get().is_none() {
+        return next.run(request).await;
+    }
+    let (mut response, status) = STATUS
+        .scope(Cell::new(None), async {
+            let response = next.run(request).await;
+            (response, STATUS.with(Cell::get))
+        })
+        .await;
+    if let Some(hit) = status {
+        let value = if hit { "hit" } else { "miss" };
+        response.headers_mut().insert(CACHE_HEADER, HeaderValue::from_static(value));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm_connector::types::Message;
+
+    fn response(content: &str) -> Response {
+        serde_json::from_value(serde_json::json!({
+            "content": content,
+            "model": "gpt-4",
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4},
+            "tool_calls": null,
+            "finish_reason": "stop",
+            "reasoning": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_key_normalization() {
+        let request = ChatRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![Message::user("hi")],
+            temperature: Some(0.0),
+            ..Default::default()
+        };
+        let streamed = ChatRequest { stream: Some(true), user: Some("ci".to_string()), ..request.clone() };
+        assert_eq!(key("openai", &request), key("openai", &streamed));
+        assert_ne!(key("openai", &request), key("zhipu", &request));
+        assert_ne!(key("openai", &request), key("openai", &ChatRequest { max_tokens: Some(10), ..request.clone() }));
+        assert_eq!(key("openai", &ChatRequest { temperature: Some(0.7), ..request.clone() }), None);
+        assert_eq!(key("openai", &ChatRequest { temperature: None, ..request }), None);
+
+        assert_eq!(parse_ttl("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_ttl("24h").unwrap(), Duration::from_secs(86400));
+        assert!(parse_ttl("1w").is_err());
+        assert_eq!(CacheBackend::parse("sqlite:/tmp/c.db", 10).unwrap(), CacheBackend::Sqlite("/tmp/c.db".into()));
+        assert!(CacheBackend::parse("redis", 10).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_and_sqlite_backends() {
+        let cache = ResponseCache::memory(2, Duration::from_secs(60));
+        cache.put("a", &response("A")).await;
+        cache.put("b", &response("B")).await;
+        assert!(cache.get("a").await.is_some());
+        // b 最久未使用，被淘汰
+        cache.put("c", &response("C")).await;
+        assert!(cache.get("b").await.is_none());
+        assert_eq!(cache.get("a").await.unwrap().content, "A");
+        assert_eq!(cache.get("c").await.unwrap().content, "C");
+
+        let expired = ResponseCache::memory(2, Duration::ZERO);
+        expired.put("a", &response("A")).await;
+        assert!(expired.get("a").await.is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.db");
+        let cache = ResponseCache::sqlite(path.clone(), Duration::from_secs(60)).await.unwrap();
+        cache.put("a", &response("A")).await;
+        cache.put("a", &response("A2")).await;
+        assert_eq!(cache.get("a").await.unwrap().content, "A2");
+        assert!(cache.get("b").await.is_none());
+        // 重启后仍然命中；TTL 过期后不再命中
+        drop(cache);
+        let reopened = ResponseCache::sqlite(path.clone(), Duration::from_secs(60)).await.unwrap();
+        assert_eq!(reopened.get("a").await.unwrap().usage.total_tokens, 4);
+        let expired = ResponseCache::sqlite(path, Duration::ZERO).await.unwrap();
+        assert!(expired.get("a").await.is_none());
+    }
+}
@@ -33,7 +33,7 @@ pub struct Args {
     #[arg(long = "auth-key")]
     pub auth_key: Option<String>,
 
-    /// Override LLM provider (openai, anthropic, zhipu, ollama)
+    /// Override LLM provider (openai, anthropic, zhipu, ollama, mock)
     #[arg(long)]
     pub provider: Option<String>,
 
@@ -45,6 +45,19 @@ pub struct Args {
     #[arg(long = "api-key")]
     pub llm_api_key: Option<String>,
 
+    /// Extra provider routed by model name: PROVIDER:API_KEY[:PATTERNS]
+    /// (e.g. `--route aliyun:sk-xxx` or `--route moonshot:sk-xxx:kimi-*`).
+    /// Can be repeated. Requests may also use a `provider/model` name.
+    #[arg(long = "route")]
+    pub routes: Vec<String>,
+
+    /// Rate limit: SCOPE:LIMITS, where SCOPE is `global`, `key` (each API key)
+    /// or `model=PATTERN` and LIMITS is `rpm=N,tpm=N,streams=N`
+    /// (e.g. `--rate-limit global:rpm=600` or `--rate-limit model=gpt-4*:streams=2`).
+    /// Can be repeated.
+    #[arg(long = "rate-limit")]
+    pub rate_limits: Vec<String>,
+
     /// Host to bind to (if provided overrides config)
     #[arg(long)]
     pub host: Option<String>,
@@ -56,5 +69,49 @@ pub struct Args {
     /// Log level
     #[arg(long, default_value = "info")]
     pub log_level: Option<String>,
+
+    /// Export traces to an OTLP/HTTP collector (e.g. http://localhost:4318)
+    #[arg(long = "otlp-endpoint")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Write a JSON-lines audit log of inference requests to a file (`-` for stdout)
+    #[arg(long = "audit-log")]
+    pub audit_log: Option<String>,
+
+    /// Include request messages and response text in the audit log
+    #[arg(long = "audit-log-messages")]
+    pub audit_log_messages: bool,
+
+    /// Rotate the audit log file: `daily` or a size such as `100MB`
+    #[arg(long = "audit-log-rotate")]
+    pub audit_log_rotate: Option<String>,
+
+    /// What to redact from the audit log (comma-separated: keys,emails; `none` to disable)
+    #[arg(long = "audit-redact", default_value = "keys,emails")]
+    pub audit_redact: String,
+
+    /// Extra regex whose matches are redacted from the audit log. Can be repeated.
+    #[arg(long = "audit-redact-pattern")]
+    pub audit_redact_patterns: Vec<String>,
+
+    /// Save every upstream request and response (with stream timings) as fixtures in DIR
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<std::path::PathBuf>,
+
+    /// Serve upstream responses from fixtures in DIR instead of calling providers
+    #[arg(long, value_name = "DIR")]
+    pub replay: Option<std::path::PathBuf>,
+
+    /// Cache replies to requests with temperature 0: `memory` or `sqlite:<PATH>`
+    #[arg(long, value_name = "BACKEND")]
+    pub cache: Option<String>,
+
+    /// Maximum number of entries in the memory cache
+    #[arg(long = "cache-size", default_value_t = 1000)]
+    pub cache_size: usize,
+
+    /// How long cached replies are served, e.g. `30m`, `24h` or `7d`
+    #[arg(long = "cache-ttl", default_value = "24h")]
+    pub cache_ttl: String,
 }
 
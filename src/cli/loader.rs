@@ -1,6 +1,6 @@
 use anyhow::Result;
 use tracing::{info, error};
-use crate::settings::Settings;
+use crate::settings::{BackendRouteSettings, LlmBackendSettings, ModelRateLimit, RateLimit, RateLimitSettings, Settings};
 use crate::apps::{SupportedApp, AppConfigGenerator};
 use crate::cli::Args;
 
@@ -9,17 +9,91 @@ pub struct ConfigLoader;
 impl ConfigLoader {
     /// 加载配置（应用模式或协议模式）
     pub fn load_config(args: &Args) -> Result<(Settings, String)> {
-        if let Some(app_name) = &args.app {
-            Self::load_app_config(app_name, args)
+        let (mut config, config_source) = if let Some(app_name) = &args.app {
+            Self::load_app_config(app_name, args)?
         } else if let Some(protocols_str) = &args.protocols {
-            Self::load_protocol_config(protocols_str, args)
+            Self::load_protocol_config(protocols_str, args)?
         } else {
-            Err(anyhow::anyhow!(
+            return Err(anyhow::anyhow!(
                 "Application mode required. Use --app <app-name> or --protocols <protocols>.\n\
                  Available applications: codex-cli, zed\n\
                  Use --list-apps for more information."
-            ))
+            ));
+        };
+
+        config.routes = args.routes.iter()
+            .map(|spec| Self::parse_route(spec))
+            .collect::<Result<Vec<_>>>()?;
+        config.rate_limits = Self::parse_rate_limits(&args.rate_limits)?;
+
+        Ok((config, config_source))
+    }
+
+    /// 解析 --rate-limit 参数: SCOPE:LIMITS（SCOPE 为 global、key 或 model=PATTERN）
+    fn parse_rate_limits(specs: &[String]) -> Result<RateLimitSettings> {
+        let mut rate_limits = RateLimitSettings::default();
+        for spec in specs {
+            let (scope, limits) = spec.split_once(':').ok_or_else(|| anyhow::anyhow!(
+                "Invalid --rate-limit '{}'. Expected SCOPE:LIMITS, e.g. global:rpm=60", spec
+            ))?;
+            let mut limit = RateLimit::default();
+            for item in limits.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+                let (name, value) = item.split_once('=').unwrap_or((item, ""));
+                let value: u32 = value.trim().parse().map_err(|_| anyhow::anyhow!(
+                    "Invalid value in --rate-limit '{}': '{}'", spec, item
+                ))?;
+                match name.trim() {
+                    "rpm" => limit.requests_per_minute = Some(value),
+                    "tpm" => limit.tokens_per_minute = Some(value),
+                    "streams" => limit.max_concurrent_streams = Some(value),
+                    other => return Err(anyhow::anyhow!(
+                        "Unknown limit '{}' in --rate-limit '{}' (expected rpm, tpm or streams)", other, spec
+                    )),
+                }
+            }
+
+            match scope.trim() {
+                "global" => rate_limits.global = limit,
+                "key" => rate_limits.per_key = limit,
+                other => match other.strip_prefix("model=").map(str::trim).filter(|pattern| !pattern.is_empty()) {
+                    Some(pattern) => rate_limits.models.push(ModelRateLimit { model: pattern.to_string(), limit }),
+                    None => return Err(anyhow::anyhow!(
+                        "Unknown scope '{}' in --rate-limit '{}' (expected global, key or model=PATTERN)", other, spec
+                    )),
+                },
+            }
+            info!("🚦 Rate limit: {}", spec);
         }
+        Ok(rate_limits)
+    }
+
+    /// 解析 --route 参数: PROVIDER:API_KEY[:PATTERNS]
+    fn parse_route(spec: &str) -> Result<BackendRouteSettings> {
+        let mut parts = spec.splitn(3, ':');
+        let provider = parts.next().unwrap_or_default().trim();
+        let api_key = parts.next().unwrap_or_default().trim();
+        let models: Vec<String> = parts.next()
+            .map(|patterns| patterns.split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect())
+            .unwrap_or_default();
+
+        if provider.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid --route '{}'. Expected PROVIDER:API_KEY[:PATTERNS]", spec
+            ));
+        }
+        if api_key.is_empty() && provider != "ollama" && provider != "mock" {
+            return Err(anyhow::anyhow!("Missing API key in --route for provider '{}'", provider));
+        }
+
+        info!("🔀 Adding route for provider: {}", provider);
+        Ok(BackendRouteSettings {
+            name: provider.to_string(),
+            models,
+            backend: Self::build_backend(provider, None, api_key)?,
+        })
     }
 
     /// 加载应用模式配置
@@ -51,7 +125,7 @@ impl ConfigLoader {
                     config,
                     Some(provider.as_str()),
                     args.model.as_deref(),
-                    args.llm_api_key.as_deref()
+                    Self::provider_api_key(args)
                 )?;
             }
 
@@ -70,7 +144,7 @@ impl ConfigLoader {
             config,
             Some(provider),
             args.model.as_deref(),
-            args.llm_api_key.as_deref()
+            Self::provider_api_key(args)
         )?;
 
         let config_source = format!("built-in: {} with provider: {}", app.name(), provider);
@@ -102,7 +176,7 @@ impl ConfigLoader {
                 config,
                 Some(provider.as_str()),
                 args.model.as_deref(),
-                args.llm_api_key.as_deref()
+                Self::provider_api_key(args)
             )?;
         }
 
@@ -111,6 +185,11 @@ impl ConfigLoader {
         Ok((config, config_source))
     }
 
+    /// Provider API key from `--api-key`; not needed when replaying fixtures
+    fn provider_api_key(args: &Args) -> Option<&str> {
+        args.llm_api_key.as_deref().or(args.replay.as_ref().map(|_| "replay"))
+    }
+
     /// 要求提供 --provider 参数
     fn require_provider<'a>(app_name: &str, args: &'a Args) -> Result<&'a str> {
         args.provider.as_deref()
@@ -124,6 +203,7 @@ impl ConfigLoader {
                 error!("   --provider aliyun      (requires --api-key)");
                 error!("   --provider minimax     (requires --api-key)");
                 error!("   --provider ollama      (no API key needed)");
+                error!("   --provider mock        (offline, for testing)");
                 error!("");
                 error!("💡 Example:");
                 error!("   ./llm-link --app {} --provider minimax", app_name);
@@ -178,13 +258,12 @@ impl ConfigLoader {
         model: Option<&str>,
         api_key: Option<&str>,
     ) -> Result<Settings> {
-        use crate::settings::LlmBackendSettings;
-
         if let Some(provider_name) = provider {
             info!("🔄 Overriding LLM provider to: {}", provider_name);
 
-            // Determine provider API key strictly from CLI
+            // Determine provider API key strictly from CLI (mock answers locally)
             let provided_key = api_key
+                .or((provider_name == "mock").then_some(""))
                 .map(|key| key.to_string())
                 .ok_or_else(|| {
                     anyhow::anyhow!(
@@ -193,82 +272,7 @@ impl ConfigLoader {
                     )
                 })?;
 
-            // Determine model
-            let model_name = if let Some(m) = model {
-                m.to_string()
-            } else {
-                // Use provider's default model
-                match provider_name {
-                    "openai" => "gpt-4".to_string(),
-                    "anthropic" => "claude-3-5-sonnet-20241022".to_string(),
-                    "zhipu" => "glm-4-flash".to_string(),
-                    "aliyun" => "qwen-max".to_string(),
-                    "volcengine" => "doubao-pro-32k".to_string(),
-                    "tencent" => "hunyuan-lite".to_string(),
-                    "longcat" => "LongCat-Flash-Chat".to_string(),
-                    "moonshot" => "kimi-k2-turbo-preview".to_string(),
-                    "minimax" => "MiniMax-M2".to_string(),
-                    "ollama" => "llama2".to_string(),
-                    _ => return Err(anyhow::anyhow!("Unknown provider: {}", provider_name)),
-                }
-            };
-
-            info!("🔄 Using model: {}", model_name);
-
-            // Create new backend settings based on provider
-            // Ollama 不需要实际使用 API key，但仍要求通过 CLI 传入以保持接口一致
-            let api_key_value = if provider_name == "ollama" {
-                String::new()
-            } else {
-                provided_key.clone()
-            };
-
-            config.llm_backend = match provider_name {
-                "openai" => LlmBackendSettings::OpenAI {
-                    api_key: api_key_value,
-                    base_url: None,
-                    model: model_name,
-                },
-                "anthropic" => LlmBackendSettings::Anthropic {
-                    api_key: api_key_value,
-                    model: model_name,
-                },
-                "zhipu" => LlmBackendSettings::Zhipu {
-                    api_key: api_key_value,
-                    base_url: Some("https://open.bigmodel.cn/api/paas/v4".to_string()),
-                    model: model_name,
-                },
-                "aliyun" => LlmBackendSettings::Aliyun {
-                    api_key: api_key_value,
-                    model: model_name,
-                },
-                "volcengine" => LlmBackendSettings::Volcengine {
-                    api_key: api_key_value,
-                    model: model_name,
-                },
-                "tencent" => LlmBackendSettings::Tencent {
-                    api_key: api_key_value,
-                    model: model_name,
-                },
-                "longcat" => LlmBackendSettings::Longcat {
-                    api_key: api_key_value,
-                    model: model_name,
-                },
-                "moonshot" => LlmBackendSettings::Moonshot {
-                    api_key: api_key_value,
-                    model: model_name,
-                },
-                "minimax" => LlmBackendSettings::Minimax {
-                    api_key: api_key_value,
-                    model: model_name,
-                },
-                "ollama" => LlmBackendSettings::Ollama {
-                    base_url: std::env::var("OLLAMA_BASE_URL").ok()
-                        .or(Some("http://localhost:11434".to_string())),
-                    model: model_name,
-                },
-                _ => return Err(anyhow::anyhow!("Unknown provider: {}", provider_name)),
-            };
+            config.llm_backend = Self::build_backend(provider_name, model, &provided_key)?;
         } else if let Some(model_name) = model {
             // Only model override, keep existing provider
             info!("🔄 Overriding model to: {}", model_name);
@@ -283,12 +287,100 @@ impl ConfigLoader {
                 LlmBackendSettings::Moonshot { model, .. } => *model = model_name.to_string(),
                 LlmBackendSettings::Minimax { model, .. } => *model = model_name.to_string(),
                 LlmBackendSettings::Ollama { model, .. } => *model = model_name.to_string(),
+                LlmBackendSettings::Mock { model, .. } => *model = model_name.to_string(),
             }
         }
 
         Ok(config)
     }
 
+    /// 根据 provider 名称构建 backend 配置（未指定 model 时使用默认 model）
+    fn build_backend(provider_name: &str, model: Option<&str>, api_key: &str) -> Result<LlmBackendSettings> {
+        // Determine model
+        let model_name = if let Some(m) = model {
+            m.to_string()
+        } else {
+            // Use provider's default model
+            match provider_name {
+                "openai" => "gpt-4".to_string(),
+                "anthropic" => "claude-3-5-sonnet-20241022".to_string(),
+                "zhipu" => "glm-4-flash".to_string(),
+                "aliyun" => "qwen-max".to_string(),
+                "volcengine" => "doubao-pro-32k".to_string(),
+                "tencent" => "hunyuan-lite".to_string(),
+                "longcat" => "LongCat-Flash-Chat".to_string(),
+                "moonshot" => "kimi-k2-turbo-preview".to_string(),
+                "minimax" => "MiniMax-M2".to_string(),
+                "ollama" => "llama2".to_string(),
+                "mock" => "mock".to_string(),
+                _ => return Err(anyhow::anyhow!("Unknown provider: {}", provider_name)),
+            }
+        };
+
+        info!("🔄 Using model: {}", model_name);
+
+        // Create new backend settings based on provider
+        // Ollama 不需要实际使用 API key，但仍要求通过 CLI 传入以保持接口一致
+        let api_key_value = if provider_name == "ollama" {
+            String::new()
+        } else {
+            api_key.to_string()
+        };
+
+        let backend = match provider_name {
+            "openai" => LlmBackendSettings::OpenAI {
+                api_key: api_key_value,
+                base_url: None,
+                model: model_name,
+            },
+            "anthropic" => LlmBackendSettings::Anthropic {
+                api_key: api_key_value,
+                model: model_name,
+            },
+            "zhipu" => LlmBackendSettings::Zhipu {
+                api_key: api_key_value,
+                base_url: Some("https://open.bigmodel.cn/api/paas/v4".to_string()),
+                model: model_name,
+            },
+            "aliyun" => LlmBackendSettings::Aliyun {
+                api_key: api_key_value,
+                model: model_name,
+            },
+            "volcengine" => LlmBackendSettings::Volcengine {
+                api_key: api_key_value,
+                model: model_name,
+            },
+            "tencent" => LlmBackendSettings::Tencent {
+                api_key: api_key_value,
+                model: model_name,
+            },
+            "longcat" => LlmBackendSettings::Longcat {
+                api_key: api_key_value,
+                model: model_name,
+            },
+            "moonshot" => LlmBackendSettings::Moonshot {
+                api_key: api_key_value,
+                model: model_name,
+            },
+            "minimax" => LlmBackendSettings::Minimax {
+                api_key: api_key_value,
+                model: model_name,
+            },
+            "ollama" => LlmBackendSettings::Ollama {
+                base_url: std::env::var("OLLAMA_BASE_URL").ok()
+                    .or(Some("http://localhost:11434".to_string())),
+                model: model_name,
+            },
+            "mock" => LlmBackendSettings::Mock {
+                model: model_name,
+                mock: Default::default(),
+            },
+            _ => return Err(anyhow::anyhow!("Unknown provider: {}", provider_name)),
+        };
+
+        Ok(backend)
+    }
+
     /// 应用命令行参数覆盖
     pub fn apply_cli_overrides(mut config: Settings, args: &Args) -> Settings {
         if let Some(host) = &args.host {
@@ -304,3 +396,21 @@ impl ConfigLoader {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate_limits() {
+        let specs = ["global:rpm=600".to_string(), "key:rpm=60,tpm=50000".to_string(), "model=gpt-4*:streams=2".to_string()];
+        let rate_limits = ConfigLoader::parse_rate_limits(&specs).unwrap();
+        assert_eq!(rate_limits.global.requests_per_minute, Some(600));
+        assert_eq!(rate_limits.per_key.tokens_per_minute, Some(50000));
+        assert_eq!(rate_limits.models[0].model, "gpt-4*");
+        assert_eq!(rate_limits.models[0].limit.max_concurrent_streams, Some(2));
+
+        assert!(ConfigLoader::parse_rate_limits(&["global".to_string()]).is_err());
+        assert!(ConfigLoader::parse_rate_limits(&["global:rph=1".to_string()]).is_err());
+        assert!(ConfigLoader::parse_rate_limits(&["user:rpm=1".to_string()]).is_err());
+    }
+}
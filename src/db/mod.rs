@@ -12,12 +12,12 @@ pub async fn initialize_database(db_path: &Path) -> Result<SqlitePool, sqlx::Err
     // Ensure data directory exists (synchronously)
     if let Some(parent) = db_path.parent() {
         std::fs::create_dir_all(parent)
-            .map_err(|e| sqlx::Error::Io(e))?;
+            .map_err(sqlx::Error::Io)?;
     }
 
     // Log current working directory for debugging
     let current_dir = std::env::current_dir()
-        .map_err(|e| sqlx::Error::Io(e))?;
+        .map_err(sqlx::Error::Io)?;
     tracing::info!("Current working directory: {:?}", current_dir);
     tracing::info!("Attempting to create database at: {:?}", db_path);
 
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveTime, Utc};
+use crate::settings::{LlmBackendSettings, RateLimit};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Provider {
@@ -34,18 +36,225 @@ pub struct UpdateProvider {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub key: String,
-    pub value: String,
+pub struct ProviderStats {
+    pub total: usize,
+    pub enabled: usize,
+    pub disabled: usize,
+}
+
+/// Virtual API key for LLM Link's own HTTP APIs
+///
+/// Only the SHA-256 hash of the secret is stored, and it is never loaded
+/// back. Empty `models`, `providers` and `frontends` lists mean no restriction.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    /// First characters of the secret, to tell keys apart in listings
+    pub key_prefix: String,
+    pub models: Json<Vec<String>>,
+    pub providers: Json<Vec<String>>,
+    pub frontends: Json<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub enabled: bool,
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+    pub max_concurrent_streams: Option<u32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProviderStats {
-    pub total: usize,
-    pub enabled: usize,
-    pub disabled: usize,
+pub struct NewApiKey {
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub models: Vec<String>,
+    pub providers: Vec<String>,
+    pub frontends: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub enabled: bool,
+    pub rate_limit: RateLimit,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateApiKey {
+    pub name: Option<String>,
+    pub models: Option<Vec<String>>,
+    pub providers: Option<Vec<String>>,
+    pub frontends: Option<Vec<String>>,
+    /// `Some(None)` clears the expiry
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    pub enabled: Option<bool>,
+    /// Same as `expires_at`: `Some(None)` removes a limit
+    pub requests_per_minute: Option<Option<u32>>,
+    pub tokens_per_minute: Option<Option<u32>>,
+    pub max_concurrent_streams: Option<Option<u32>>,
+}
+
+impl ApiKey {
+    /// Whether the key has passed its expiry time
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Limits set on the key itself
+    pub fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            requests_per_minute: self.requests_per_minute,
+            tokens_per_minute: self.tokens_per_minute,
+            max_concurrent_streams: self.max_concurrent_streams,
+        }
+    }
+}
+
+/// Usage of one inference request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewUsage {
+    pub key_name: Option<String>,
+    pub frontend: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub latency_ms: u64,
+    pub status: u16,
+    /// USD, `None` when the model has no pricing
+    pub cost: Option<f64>,
+}
+
+/// Dimension usage can be aggregated by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroup {
+    Day,
+    Key,
+    Model,
+    Provider,
+}
+
+/// Aggregated usage; grouping columns that were not requested are `None`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UsageReportRow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub day: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    pub requests: i64,
+    pub errors: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost: f64,
+    pub avg_latency_ms: f64,
+}
+
+/// Length of a budget period, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    Daily,
+    Monthly,
+}
+
+impl BudgetPeriod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BudgetPeriod::Daily => "daily",
+            BudgetPeriod::Monthly => "monthly",
+        }
+    }
+
+    /// Start of the period `at` falls in
+    pub fn start_of(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let day = match self {
+            BudgetPeriod::Daily => at.date_naive(),
+            BudgetPeriod::Monthly => at.date_naive().with_day(1).unwrap_or(at.date_naive()),
+        };
+        day.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    /// Start of the period after the one `at` falls in
+    pub fn next_start(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let start = self.start_of(at);
+        match self {
+            BudgetPeriod::Daily => start + Duration::days(1),
+            BudgetPeriod::Monthly => start.checked_add_months(Months::new(1)).unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+}
+
+/// Spend budget on a virtual API key or a provider
+///
+/// Spend is the cost recorded in `usage` since `period_start`. With
+/// `auto_reset` the budget rolls over at the start of each period,
+/// otherwise it only resets through the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Budget {
+    pub id: i64,
+    pub api_key_id: Option<i64>,
+    pub provider_id: Option<i64>,
+    /// Name of the key or provider
+    pub target: String,
+    pub period: BudgetPeriod,
+    pub limit_usd: f64,
+    /// Fraction of the limit that logs a warning, e.g. `0.8`
+    pub warn_threshold: Option<f64>,
+    pub auto_reset: bool,
+    pub period_start: DateTime<Utc>,
+    pub warned_at: Option<DateTime<Utc>>,
+    pub enabled: bool,
+    pub spent: f64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Budget {
+    /// Human-readable owner, e.g. `key 'ci'`
+    pub fn owner(&self) -> String {
+        let kind = if self.api_key_id.is_some() { "key" } else { "provider" };
+        format!("{} '{}'", kind, self.target)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.spent >= self.limit_usd
+    }
+
+    /// Whether spend has crossed the warning threshold
+    pub fn is_over_threshold(&self) -> bool {
+        self.warn_threshold
+            .is_some_and(|threshold| self.spent >= self.limit_usd * threshold)
+    }
+
+    /// Whether an auto-reset budget has reached its next period
+    pub fn is_due_for_reset(&self, now: DateTime<Utc>) -> bool {
+        self.auto_reset && now >= self.period.next_start(self.period_start)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewBudget {
+    pub api_key_id: Option<i64>,
+    pub provider_id: Option<i64>,
+    pub period: BudgetPeriod,
+    pub limit_usd: f64,
+    pub warn_threshold: Option<f64>,
+    pub auto_reset: bool,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateBudget {
+    pub period: Option<BudgetPeriod>,
+    pub limit_usd: Option<f64>,
+    /// `Some(None)` removes the warning threshold
+    pub warn_threshold: Option<Option<f64>>,
+    pub auto_reset: Option<bool>,
+    pub enabled: Option<bool>,
 }
 
 impl Provider {
@@ -80,6 +289,26 @@ impl Provider {
     }
 }
 
+impl Provider {
+    /// Convert this row into backend settings usable by the LLM service
+    pub fn to_backend_settings(&self) -> anyhow::Result<LlmBackendSettings> {
+        let config: serde_json::Value = serde_json::from_str(&self.config)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON config for provider '{}': {}", self.name, e))?;
+        LlmBackendSettings::from_provider_config(&self.provider_type, &config)
+    }
+
+    /// Model names or glob patterns routed to this provider
+    ///
+    /// Read from an optional `"models": ["glm-*", ...]` entry in the config.
+    pub fn model_patterns(&self) -> Vec<String> {
+        serde_json::from_str::<serde_json::Value>(&self.config)
+            .ok()
+            .and_then(|config| config.get("models").cloned())
+            .and_then(|models| serde_json::from_value(models).ok())
+            .unwrap_or_default()
+    }
+}
+
 impl NewProvider {
     pub fn new(name: String, provider_type: String, config: String) -> Self {
         Self {
@@ -91,3 +320,32 @@ impl NewProvider {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_to_backend_settings() {
+        let mut provider = Provider::new(
+            "primary".to_string(),
+            "zhipu".to_string(),
+            r#"{"api_key": "key", "model": "glm-4-flash"}"#.to_string(),
+        );
+        match provider.to_backend_settings().unwrap() {
+            LlmBackendSettings::Zhipu { api_key, base_url, model } => {
+                assert_eq!(api_key, "key");
+                assert_eq!(base_url, None);
+                assert_eq!(model, "glm-4-flash");
+            }
+            other => panic!("unexpected backend: {:?}", other),
+        }
+
+        provider.provider_type = "openai".to_string();
+        provider.config = r#"{"model": "gpt-4o"}"#.to_string();
+        assert!(provider.to_backend_settings().is_err());
+
+        provider.provider_type = "unknown".to_string();
+        assert!(provider.to_backend_settings().is_err());
+    }
+}
@@ -1,19 +1,43 @@
 use sqlx::SqlitePool;
 use std::path::Path;
 use tracing::info;
-use crate::db::{initialize_database, Provider, NewProvider, UpdateProvider, ProviderStats};
+use crate::db::{initialize_database, Provider, NewProvider, UpdateProvider, ProviderStats, ApiKey, NewApiKey, UpdateApiKey, NewUsage, UsageGroup, UsageReportRow, Budget, NewBudget, UpdateBudget};
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
 use anyhow::Result;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Budgets with the name of their key or provider and the spend of the current period
+const BUDGET_SELECT: &str = r#"
+    SELECT
+        b.*,
+        COALESCE(k.name, p.name, '') AS target,
+        COALESCE((
+            SELECT SUM(u.cost) FROM usage u
+            WHERE u.created_at >= datetime(b.period_start)
+              AND ((b.api_key_id IS NOT NULL AND u.key_name = k.name)
+                OR (b.provider_id IS NOT NULL AND u.provider = p.name))
+        ), 0.0) AS spent
+    FROM budgets b
+    LEFT JOIN api_keys k ON k.id = b.api_key_id
+    LEFT JOIN providers p ON p.id = b.provider_id
+"#;
 
 #[derive(Clone)]
 pub struct DatabasePool {
     pool: SqlitePool,
+    /// Bumped whenever a provider row changes so listeners can reload
+    provider_changes: Arc<watch::Sender<u64>>,
+    /// Bumped whenever a configuration value is stored
+    config_changes: Arc<watch::Sender<u64>>,
 }
 
 impl DatabasePool {
     /// Create new database pool with migrations
     pub async fn new(db_path: &Path) -> Result<Self> {
         let pool = initialize_database(db_path).await?;
-        Ok(Self { pool })
+        Ok(Self::from_pool(pool))
     }
 
     /// Create in-memory database pool for Phase 1 fallback
@@ -24,7 +48,31 @@ impl DatabasePool {
         // Run migrations on in-memory database
         sqlx::migrate!("./migrations").run(&pool).await?;
         
-        Ok(Self { pool })
+        Ok(Self::from_pool(pool))
+    }
+
+    fn from_pool(pool: SqlitePool) -> Self {
+        let (provider_changes, _) = watch::channel(0);
+        let (config_changes, _) = watch::channel(0);
+        Self {
+            pool,
+            provider_changes: Arc::new(provider_changes),
+            config_changes: Arc::new(config_changes),
+        }
+    }
+
+    /// Subscribe to provider changes (create, update, delete, toggle)
+    pub fn subscribe_provider_changes(&self) -> watch::Receiver<u64> {
+        self.provider_changes.subscribe()
+    }
+
+    fn notify_provider_change(&self) {
+        self.provider_changes.send_modify(|version| *version += 1);
+    }
+
+    /// Subscribe to configuration value changes
+    pub fn subscribe_config_changes(&self) -> watch::Receiver<u64> {
+        self.config_changes.subscribe()
     }
 
     /// Get the underlying SqlitePool
@@ -49,6 +97,7 @@ impl DatabasePool {
         .execute(&self.pool)
         .await?;
 
+        self.notify_provider_change();
         Ok(result.last_insert_rowid())
     }
 
@@ -100,7 +149,6 @@ impl DatabasePool {
     }
 
     /// Get enabled providers
-    #[allow(dead_code)] // Will be used in Phase 3 for multi-provider routing
     pub async fn get_enabled_providers(&self) -> Result<Vec<Provider>> {
         let providers = sqlx::query_as::<_, Provider>(
             r#"
@@ -163,6 +211,9 @@ impl DatabasePool {
         query.push_bind(id);
 
         let result = query.build().execute(&self.pool).await?;
+        if result.rows_affected() > 0 {
+            self.notify_provider_change();
+        }
         Ok(result.rows_affected() > 0)
     }
 
@@ -173,6 +224,9 @@ impl DatabasePool {
             .execute(&self.pool)
             .await?;
 
+        if result.rows_affected() > 0 {
+            self.notify_provider_change();
+        }
         Ok(result.rows_affected() > 0)
     }
 
@@ -195,6 +249,7 @@ impl DatabasePool {
         .execute(&self.pool)
         .await?;
 
+        self.config_changes.send_modify(|version| *version += 1);
         Ok(())
     }
 
@@ -204,7 +259,7 @@ impl DatabasePool {
             "SELECT value FROM config WHERE key = ?"
         )
         .bind(key)
-        .fetch_one(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
 
         Ok(value)
@@ -225,6 +280,9 @@ impl DatabasePool {
         .execute(&self.pool)
         .await?;
 
+        if result.rows_affected() > 0 {
+            self.notify_provider_change();
+        }
         Ok(result.rows_affected() > 0)
     }
 
@@ -244,4 +302,339 @@ impl DatabasePool {
             disabled: (total - enabled) as usize,
         })
     }
+
+    /// Create a new virtual API key
+    pub async fn create_api_key(&self, key: NewApiKey) -> Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO api_keys (
+                name, key_prefix, key_hash, models, providers, frontends, expires_at, enabled,
+                requests_per_minute, tokens_per_minute, max_concurrent_streams
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&key.name)
+        .bind(&key.key_prefix)
+        .bind(&key.key_hash)
+        .bind(Json(&key.models))
+        .bind(Json(&key.providers))
+        .bind(Json(&key.frontends))
+        .bind(key.expires_at)
+        .bind(key.enabled)
+        .bind(key.rate_limit.requests_per_minute)
+        .bind(key.rate_limit.tokens_per_minute)
+        .bind(key.rate_limit.max_concurrent_streams)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Get all virtual API keys
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys ORDER BY created_at ASC, id ASC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(keys)
+    }
+
+    /// Get virtual API key by ID
+    pub async fn get_api_key(&self, id: i64) -> Result<Option<ApiKey>> {
+        let key = sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(key)
+    }
+
+    /// Look up a virtual API key by the hash of its secret
+    pub async fn find_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let key = sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE key_hash = ?")
+            .bind(key_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(key)
+    }
+
+    /// Whether any virtual API key exists
+    pub async fn has_api_keys(&self) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM api_keys")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Update virtual API key
+    pub async fn update_api_key(&self, id: i64, update: UpdateApiKey) -> Result<bool> {
+        let mut query = sqlx::QueryBuilder::new("UPDATE api_keys SET updated_at = CURRENT_TIMESTAMP");
+        let mut has_updates = false;
+
+        if let Some(name) = &update.name {
+            query.push(", name = ");
+            query.push_bind(name);
+            has_updates = true;
+        }
+        if let Some(models) = &update.models {
+            query.push(", models = ");
+            query.push_bind(Json(models));
+            has_updates = true;
+        }
+        if let Some(providers) = &update.providers {
+            query.push(", providers = ");
+            query.push_bind(Json(providers));
+            has_updates = true;
+        }
+        if let Some(frontends) = &update.frontends {
+            query.push(", frontends = ");
+            query.push_bind(Json(frontends));
+            has_updates = true;
+        }
+        if let Some(expires_at) = update.expires_at {
+            query.push(", expires_at = ");
+            query.push_bind(expires_at);
+            has_updates = true;
+        }
+        if let Some(enabled) = update.enabled {
+            query.push(", enabled = ");
+            query.push_bind(enabled);
+            has_updates = true;
+        }
+        for (column, limit) in [
+            ("requests_per_minute", update.requests_per_minute),
+            ("tokens_per_minute", update.tokens_per_minute),
+            ("max_concurrent_streams", update.max_concurrent_streams),
+        ] {
+            if let Some(limit) = limit {
+                query.push(format!(", {} = ", column));
+                query.push_bind(limit);
+                has_updates = true;
+            }
+        }
+
+        if !has_updates {
+            return Ok(false);
+        }
+
+        query.push(" WHERE id = ");
+        query.push_bind(id);
+
+        let result = query.build().execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete virtual API key
+    pub async fn delete_api_key(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record the usage of one request
+    pub async fn record_usage(&self, usage: NewUsage) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO usage (key_name, frontend, provider, model, prompt_tokens, completion_tokens, latency_ms, status, cost)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&usage.key_name)
+        .bind(&usage.frontend)
+        .bind(&usage.provider)
+        .bind(&usage.model)
+        .bind(usage.prompt_tokens as i64)
+        .bind(usage.completion_tokens as i64)
+        .bind(usage.latency_ms as i64)
+        .bind(usage.status)
+        .bind(usage.cost)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Aggregate usage by `groups` for the days `from..=to` (`YYYY-MM-DD`, UTC)
+    pub async fn usage_report(
+        &self,
+        groups: &[UsageGroup],
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<Vec<UsageReportRow>> {
+        let column = |group: UsageGroup, expr: &str| {
+            if groups.contains(&group) { expr.to_string() } else { "NULL".to_string() }
+        };
+        let columns = [
+            format!("{} AS day", column(UsageGroup::Day, "date(created_at)")),
+            format!("{} AS key", column(UsageGroup::Key, "key_name")),
+            format!("{} AS model", column(UsageGroup::Model, "model")),
+            format!("{} AS provider", column(UsageGroup::Provider, "provider")),
+        ];
+
+        let mut query = sqlx::QueryBuilder::new(format!(
+            r#"
+            SELECT
+                {},
+                COUNT(*) AS requests,
+                COALESCE(SUM(status >= 400), 0) AS errors,
+                COALESCE(SUM(prompt_tokens), 0) AS prompt_tokens,
+                COALESCE(SUM(completion_tokens), 0) AS completion_tokens,
+                COALESCE(SUM(cost), 0.0) AS cost,
+                COALESCE(AVG(latency_ms), 0.0) AS avg_latency_ms
+            FROM usage
+            WHERE 1 = 1
+            "#,
+            columns.join(", ")
+        ));
+        if let Some(from) = from {
+            query.push(" AND date(created_at) >= ");
+            query.push_bind(from);
+        }
+        if let Some(to) = to {
+            query.push(" AND date(created_at) <= ");
+            query.push_bind(to);
+        }
+        query.push(" GROUP BY 1, 2, 3, 4 ORDER BY 1, 2, 3, 4");
+
+        let rows = query.build_query_as::<UsageReportRow>().fetch_all(&self.pool).await?;
+        Ok(rows)
+    }
+
+    /// Create a budget; its first period starts now
+    pub async fn create_budget(&self, budget: NewBudget) -> Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO budgets (api_key_id, provider_id, period, limit_usd, warn_threshold, auto_reset, period_start, enabled)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(budget.api_key_id)
+        .bind(budget.provider_id)
+        .bind(budget.period)
+        .bind(budget.limit_usd)
+        .bind(budget.warn_threshold)
+        .bind(budget.auto_reset)
+        .bind(budget.period.start_of(Utc::now()))
+        .bind(budget.enabled)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Get all budgets with their current spend
+    pub async fn list_budgets(&self) -> Result<Vec<Budget>> {
+        let budgets = sqlx::query_as::<_, Budget>(&format!("{} ORDER BY b.id ASC", BUDGET_SELECT))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(budgets)
+    }
+
+    /// Get budget by ID
+    pub async fn get_budget(&self, id: i64) -> Result<Option<Budget>> {
+        let budget = sqlx::query_as::<_, Budget>(&format!("{} WHERE b.id = ?", BUDGET_SELECT))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(budget)
+    }
+
+    /// Get all budgets, first rolling over auto-reset budgets whose period has ended
+    pub async fn current_budgets(&self, now: DateTime<Utc>) -> Result<Vec<Budget>> {
+        let budgets = self.list_budgets().await?;
+        let due: Vec<&Budget> = budgets.iter().filter(|budget| budget.is_due_for_reset(now)).collect();
+        if due.is_empty() {
+            return Ok(budgets);
+        }
+
+        for budget in due {
+            self.reset_budget(budget.id, budget.period.start_of(now)).await?;
+            info!("🔄 Budget for {} rolled over to a new {} period", budget.owner(), budget.period.as_str());
+        }
+        self.list_budgets().await
+    }
+
+    /// Update budget
+    pub async fn update_budget(&self, id: i64, update: UpdateBudget) -> Result<bool> {
+        let mut query = sqlx::QueryBuilder::new("UPDATE budgets SET updated_at = CURRENT_TIMESTAMP");
+        let mut has_updates = false;
+
+        if let Some(period) = update.period {
+            query.push(", period = ");
+            query.push_bind(period);
+            has_updates = true;
+        }
+        if let Some(limit_usd) = update.limit_usd {
+            query.push(", limit_usd = ");
+            query.push_bind(limit_usd);
+            has_updates = true;
+        }
+        if let Some(warn_threshold) = update.warn_threshold {
+            query.push(", warn_threshold = ");
+            query.push_bind(warn_threshold);
+            has_updates = true;
+        }
+        if let Some(auto_reset) = update.auto_reset {
+            query.push(", auto_reset = ");
+            query.push_bind(auto_reset);
+            has_updates = true;
+        }
+        if let Some(enabled) = update.enabled {
+            query.push(", enabled = ");
+            query.push_bind(enabled);
+            has_updates = true;
+        }
+
+        if !has_updates {
+            return Ok(false);
+        }
+
+        query.push(" WHERE id = ");
+        query.push_bind(id);
+
+        let result = query.build().execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Start a new budget period at `period_start`
+    pub async fn reset_budget(&self, id: i64, period_start: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE budgets SET period_start = ?, warned_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+        )
+        .bind(period_start)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remember that the warning threshold was crossed in the current period
+    pub async fn mark_budget_warned(&self, id: i64, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query("UPDATE budgets SET warned_at = ? WHERE id = ?")
+            .bind(at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete budget
+    pub async fn delete_budget(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM budgets WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
 }
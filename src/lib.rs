@@ -6,3 +6,12 @@ pub mod apps;
 pub mod api;
 pub mod models;
 pub mod provider;
+pub mod tokenizer;
+pub mod db;
+pub mod ratelimit;
+pub mod usage;
+pub mod budget;
+pub mod metrics;
+pub mod telemetry;
+pub mod audit;
+pub mod cache;
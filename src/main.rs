@@ -8,6 +8,14 @@ mod api;
 mod models;
 mod cli;
 mod provider;
+mod tokenizer;
+mod ratelimit;
+mod usage;
+mod budget;
+mod metrics;
+mod telemetry;
+mod audit;
+mod cache;
 
 // New modules for multi-mode support
 mod db;
@@ -16,6 +24,7 @@ mod admin;
 
 use anyhow::Result;
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
     extract::Request,
@@ -24,6 +33,7 @@ use axum::{
 use clap::Parser;
 use settings::Settings;
 use api::{AppState, health_check, info};
+use api::auth::{require_api_key, Frontend};
 use api::config::{get_current_config, update_config_for_restart, validate_key, validate_key_for_update, update_key, switch_provider, get_pid, shutdown, get_health, init_instance_id};
 use tower::ServiceBuilder;
 use tower_http::{
@@ -33,7 +43,7 @@ use tower_http::{
 };
 use tracing::{info, error, warn, Span};
 use std::time::Duration;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 use cli::{Args, ConfigLoader, list_applications, show_application_info};
 
 // Import new modules
@@ -46,7 +56,7 @@ async fn main() -> Result<()> {
     let args = Args::parse();
 
     // Initialize logging
-    initialize_logging(&args);
+    initialize_logging(&args)?;
     
     // Initialize instance ID for tracking restarts
     init_instance_id();
@@ -62,6 +72,11 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    metrics::install()?;
+    initialize_audit_log(&args)?;
+    initialize_fixtures(&args)?;
+    initialize_cache(&args).await?;
+
     // Get run mode (default to multi for better UX)
     let run_mode = args.mode.unwrap_or_default();
     
@@ -156,16 +171,121 @@ async fn run_multi_mode(args: Args) -> Result<()> {
         }
     });
     
-    // TODO: Start main API server (placeholder for now)
-    info!("📡 Main API server will be implemented in Phase 2");
+    // Build the main API server from enabled providers
+    let config = build_multi_mode_settings(&args);
+    let app_state = AppState::new(service::Service::empty(), config.clone()).with_database(db_pool.clone());
+    app_state.replace_llm_service(load_provider_service(&db_pool).await).await;
+
+    // Rebuild the service whenever providers are changed through the admin interface
+    spawn_provider_sync(db_pool.clone(), app_state.clone());
+    load_rate_limits(&db_pool, &app_state).await;
+    spawn_rate_limit_sync(db_pool.clone(), app_state.clone());
+
+    let app = build_multi_mode_app(app_state, &config);
     info!("🎉 Multi-mode setup complete. Admin interface is running.");
-    
-    // Wait for admin server (in real implementation, this would also start the main API)
-    admin_handle.await?;
+
+    tokio::select! {
+        result = start_server(app, &config) => result?,
+        result = admin_handle => result?,
+    }
     
     Ok(())
 }
 
+/// Settings for the multi-mode API server
+///
+/// All three frontends are enabled. Ollama routes are mounted at the root so
+/// clients that expect a local Ollama (`/api/tags`, `/api/chat`) work as-is.
+fn build_multi_mode_settings(args: &Args) -> Settings {
+    let mut config = Settings::default();
+    config.server.host = args.host.clone().unwrap_or_else(|| "0.0.0.0".to_string());
+    config.server.port = args.port.unwrap_or(config.server.port);
+    if let Some(ollama) = config.apis.ollama.as_mut() {
+        ollama.path = String::new();
+    }
+    if let Some(openai) = config.apis.openai.as_mut() {
+        openai.api_key = args.auth_key.clone();
+    }
+    if let Some(anthropic) = config.apis.anthropic.as_mut() {
+        anthropic.api_key = args.auth_key.clone();
+    }
+    config
+}
+
+/// Build an LLM service from the enabled provider rows, in priority order
+///
+/// Rows that cannot be turned into a backend are skipped with a warning so
+/// one bad provider does not take the whole proxy down.
+async fn load_provider_service(db_pool: &DatabasePool) -> service::Service {
+    let mut llm_service = service::Service::empty();
+
+    let providers = match db_pool.get_enabled_providers().await {
+        Ok(providers) => providers,
+        Err(e) => {
+            error!("❌ Failed to load enabled providers: {}", e);
+            return llm_service;
+        }
+    };
+
+    for provider in providers {
+        let result = provider
+            .to_backend_settings()
+            .and_then(|backend| llm_service.add_backend(&provider.name, &backend, &provider.model_patterns()));
+        if let Err(e) = result {
+            warn!("⚠️ Skipping provider '{}': {}", provider.name, e);
+        }
+    }
+
+    if llm_service.backend_names().is_empty() {
+        warn!("⚠️ No enabled providers; API requests will fail until one is configured");
+    } else {
+        info!("✅ Serving providers (by priority): {}", llm_service.backend_names().join(", "));
+    }
+
+    llm_service
+}
+
+/// Watch the providers table and hot-apply changes to the running API server
+fn spawn_provider_sync(db_pool: DatabasePool, app_state: AppState) {
+    let mut changes = db_pool.subscribe_provider_changes();
+    tokio::spawn(async move {
+        while changes.changed().await.is_ok() {
+            info!("🔄 Providers changed, reloading LLM service");
+            let llm_service = load_provider_service(&db_pool).await;
+            app_state.replace_llm_service(llm_service).await;
+        }
+    });
+}
+
+/// Apply the rate limits stored under the `rate_limits` config key
+async fn load_rate_limits(db_pool: &DatabasePool, app_state: &AppState) {
+    let rate_limits = match db_pool.get_config(admin::RATE_LIMITS_CONFIG_KEY).await {
+        Ok(Some(value)) => match serde_json::from_str(&value) {
+            Ok(rate_limits) => rate_limits,
+            Err(e) => {
+                warn!("⚠️ Ignoring invalid rate limit configuration: {}", e);
+                return;
+            }
+        },
+        Ok(None) => settings::RateLimitSettings::default(),
+        Err(e) => {
+            error!("❌ Failed to load rate limits: {}", e);
+            return;
+        }
+    };
+    app_state.config.write().await.rate_limits = rate_limits;
+}
+
+/// Watch the config table and hot-apply rate limit changes
+fn spawn_rate_limit_sync(db_pool: DatabasePool, app_state: AppState) {
+    let mut changes = db_pool.subscribe_config_changes();
+    tokio::spawn(async move {
+        while changes.changed().await.is_ok() {
+            load_rate_limits(&db_pool, &app_state).await;
+        }
+    });
+}
+
 /// Try to initialize file-based database
 async fn try_file_database() -> Result<DatabasePool> {
     info!("Attempting file-based database initialization...");
@@ -269,18 +389,87 @@ async fn test_sqlite_connection(db_path: &std::path::Path) -> Result<()> {
 }
 
 /// Initialize logging system
-fn initialize_logging(args: &Args) {
+fn initialize_logging(args: &Args) -> Result<()> {
     let log_level = args.log_level.clone()
         .or_else(|| std::env::var("LLM_LINK_LOG_LEVEL").ok())
         .unwrap_or_else(|| "info".to_string());
 
+    // 日志级别只作用于控制台输出，OTLP 导出有自己的过滤
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(log_level));
+    let otlp = args.otlp_endpoint.as_deref().map(telemetry::init).transpose()?;
+
+    // 审计日志写到 stdout 时，普通日志改走 stderr
+    let audit_to_stdout = args.audit_log.as_deref() == Some("-");
+    let console = tracing_subscriber::fmt::layer()
+        .with_writer(move || -> Box<dyn std::io::Write> {
+            if audit_to_stdout { Box::new(std::io::stderr()) } else { Box::new(std::io::stdout()) }
+        })
+        .with_filter(filter);
+
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(log_level)),
-        )
-        .with(tracing_subscriber::fmt::layer())
+        .with(console)
+        .with(otlp)
         .init();
+
+    if let Some(endpoint) = &args.otlp_endpoint {
+        info!("🔭 Exporting traces to OTLP collector at {}", endpoint);
+    }
+    Ok(())
+}
+
+/// Start the audit log if `--audit-log` is given
+fn initialize_audit_log(args: &Args) -> Result<()> {
+    let Some(target) = &args.audit_log else {
+        return Ok(());
+    };
+
+    let mut redact_keys = false;
+    let mut redact_emails = false;
+    for item in args.audit_redact.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        match item {
+            "keys" => redact_keys = true,
+            "emails" => redact_emails = true,
+            "none" => {}
+            other => anyhow::bail!("Unknown --audit-redact value '{}' (expected keys, emails or none)", other),
+        }
+    }
+
+    audit::init(audit::AuditConfig {
+        target: audit::AuditTarget::parse(target),
+        include_messages: args.audit_log_messages,
+        redactor: audit::Redactor::new(redact_keys, redact_emails, &args.audit_redact_patterns)?,
+        rotation: args.audit_log_rotate.as_deref().map(audit::Rotation::parse).transpose()?,
+    })?;
+    info!("📝 Writing audit log to {}", if target == "-" { "stdout" } else { target });
+    Ok(())
+}
+
+/// Record or replay upstream traffic (`--record` / `--replay`)
+fn initialize_fixtures(args: &Args) -> Result<()> {
+    if let Some(dir) = &args.record {
+        normalizer::init_fixtures(normalizer::Fixtures::Record(dir.clone()))?;
+        info!("📼 Recording upstream traffic to {}", dir.display());
+    } else if let Some(dir) = &args.replay {
+        normalizer::init_fixtures(normalizer::Fixtures::Replay(dir.clone()))?;
+        info!("📼 Replaying upstream traffic from {} (providers are not called)", dir.display());
+    }
+    Ok(())
+}
+
+/// Cache replies to deterministic requests (`--cache`)
+async fn initialize_cache(args: &Args) -> Result<()> {
+    let Some(backend) = &args.cache else {
+        return Ok(());
+    };
+    let backend = cache::CacheBackend::parse(backend, args.cache_size)?;
+    let ttl = cache::parse_ttl(&args.cache_ttl)?;
+    cache::init(backend.clone(), ttl).await?;
+    match backend {
+        cache::CacheBackend::Memory(size) => info!("💾 Caching responses in memory (up to {} entries, TTL {}s)", size, ttl.as_secs()),
+        cache::CacheBackend::Sqlite(path) => info!("💾 Caching responses in {} (TTL {}s)", path.display(), ttl.as_secs()),
+    }
+    Ok(())
 }
 
 /// Log configuration information
@@ -305,7 +494,10 @@ fn log_configuration(config: &Settings, config_source: &str) {
 /// Initialize LLM service
 fn initialize_llm_service(config: &Settings) -> Result<service::Service> {
     info!("🔧 Initializing LLM service...");
-    let llm_service = service::Service::new(&config.llm_backend)?;
+    let llm_service = service::Service::with_routes(&config.llm_backend, &config.routes)?;
+    for route in &config.routes {
+        info!("🔀 Routing {:?} to provider '{}'", route.models, route.name);
+    }
     info!("✅ LLM service initialized successfully");
     Ok(llm_service)
 }
@@ -321,7 +513,6 @@ fn build_single_mode_app(app_state: AppState, config: &Settings) -> Router {
                     info!("🌐 ======================================");
                     info!("🌐 Incoming request: {} {}", request.method(), request.uri());
                     info!("📋 Full URI: {}", request.uri());
-                    info!("📋 Headers: {:?}", request.headers());
                     info!("📋 User-Agent: {:?}", request.headers().get("user-agent"));
                     info!("📋 Host: {:?}", request.headers().get("host"));
                     info!("📋 Accept: {:?}", request.headers().get("accept"));
@@ -347,6 +538,36 @@ fn build_single_mode_app(app_state: AppState, config: &Settings) -> Router {
         )
 }
 
+/// Build multi mode application and add middleware
+fn build_multi_mode_app(app_state: AppState, config: &Settings) -> Router {
+    info!("🏗️ Building multi-mode application routes...");
+
+    let basic_routes = Router::new()
+        .route("/", get(|| async { "LLM Link is running in multi mode" }))
+        .route("/health", get(|| async { health_check().await }))
+        .route("/metrics", get(metrics::render));
+
+    let stateful_routes = Router::new()
+        .route("/api/health", get(get_health))
+        .route("/api/info", get(info))
+        .with_state(app_state.clone());
+
+    let app = basic_routes.merge(stateful_routes);
+    merge_api_routes(app, app_state.clone(), config)
+        .fallback(unmatched_route)
+        .layer(
+            ServiceBuilder::new()
+                .layer(TraceLayer::new_for_http())
+                .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
+                .layer(middleware::from_fn_with_state(app_state.clone(), api::inspect::parse))
+                .layer(middleware::from_fn(telemetry::trace))
+                .layer(middleware::from_fn_with_state(app_state.clone(), metrics::track))
+                .layer(middleware::from_fn_with_state(app_state.clone(), audit::record))
+                .layer(middleware::from_fn_with_state(app_state, usage::record))
+                .layer(middleware::from_fn(cache::annotate)),
+        )
+}
+
 /// Start server
 async fn start_server(app: Router, config: &Settings) -> Result<()> {
     let bind_addr = format!("{}:{}", config.server.host, config.server.port);
@@ -375,7 +596,8 @@ fn build_single_mode_routes(state: AppState, config: &Settings) -> Router {
         .route("/debug", get(|| {
             info!("🐛 Debug endpoint accessed");
             async { api::debug_test().await }
-        }));
+        }))
+        .route("/metrics", get(metrics::render));
 
     // Create routes that require state
     let stateful_routes = Router::new()
@@ -392,13 +614,37 @@ fn build_single_mode_routes(state: AppState, config: &Settings) -> Router {
         .with_state(state.clone());
 
     // Merge routes
-    let mut app = basic_routes.merge(stateful_routes);
+    let app = basic_routes.merge(stateful_routes);
+    let mut app = merge_api_routes(app, state.clone(), config);
+
+    // Add catch-all route for debugging
+    app = app.fallback(unmatched_route);
+
+    // Apply middleware at the end
+    app.layer(
+        ServiceBuilder::new()
+            .layer(TraceLayer::new_for_http())
+            .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
+            .layer(middleware::from_fn_with_state(state.clone(), api::inspect::parse))
+            .layer(middleware::from_fn(telemetry::trace))
+            .layer(middleware::from_fn_with_state(state.clone(), metrics::track))
+            .layer(middleware::from_fn_with_state(state.clone(), audit::record))
+            // 单机模式通常没有数据库，此时 usage::record 直接放行
+            .layer(middleware::from_fn_with_state(state, usage::record))
+            .layer(middleware::from_fn(cache::annotate)),
+    )
+}
 
+/// Add the OpenAI, Ollama and Anthropic frontends enabled in `config`
+fn merge_api_routes(mut app: Router, state: AppState, config: &Settings) -> Router {
     // Add Ollama API endpoints
     if let Some(ollama_config) = &config.apis.ollama {
         if ollama_config.enabled {
             info!("Enabling Ollama API on path: {}", ollama_config.path);
-            let ollama_routes = api::ollama::build_ollama_routes(state.clone(), ollama_config);
+            let ollama_routes = api::ollama::build_ollama_routes(state.clone(), ollama_config)
+                .route_layer(middleware::from_fn_with_state((state.clone(), Frontend::Ollama), budget::enforce))
+                .route_layer(middleware::from_fn_with_state(state.clone(), ratelimit::enforce))
+                .route_layer(middleware::from_fn_with_state((state.clone(), Frontend::Ollama), require_api_key));
             app = app.merge(ollama_routes);
         }
     }
@@ -409,8 +655,12 @@ fn build_single_mode_routes(state: AppState, config: &Settings) -> Router {
             info!("Enabling OpenAI API on path: {}", openai_config.path);
             let openai_routes = Router::new()
                 .route(&format!("{}/chat/completions", openai_config.path), post(api::openai::chat))
+                .route(&format!("{}/embeddings", openai_config.path), post(api::openai::embeddings))
                 .route(&format!("{}/models", openai_config.path), get(api::openai::models))
                 .route(&format!("{}/models/:model", openai_config.path), get(api::openai::models))
+                .route_layer(middleware::from_fn_with_state((state.clone(), Frontend::OpenAI), budget::enforce))
+                .route_layer(middleware::from_fn_with_state(state.clone(), ratelimit::enforce))
+                .route_layer(middleware::from_fn_with_state((state.clone(), Frontend::OpenAI), require_api_key))
                 .with_state(state.clone());
             app = app.merge(openai_routes);
         }
@@ -424,32 +674,57 @@ fn build_single_mode_routes(state: AppState, config: &Settings) -> Router {
                 .route(&format!("{}/v1/messages", anthropic_config.path), post(api::anthropic::messages))
                 .route(&format!("{}/v1/messages/count_tokens", anthropic_config.path), post(api::anthropic::count_tokens))
                 .route(&format!("{}/v1/models", anthropic_config.path), get(api::anthropic::models))
+                .route_layer(middleware::from_fn_with_state((state.clone(), Frontend::Anthropic), budget::enforce))
+                .route_layer(middleware::from_fn_with_state(state.clone(), ratelimit::enforce))
+                .route_layer(middleware::from_fn_with_state((state.clone(), Frontend::Anthropic), require_api_key))
                 .with_state(state.clone());
             app = app.merge(anthropic_routes);
         }
     }
 
-    // Add catch-all route for debugging
-    app = app.fallback(|request: axum::extract::Request| async move {
-        error!("🚫 ======================================");
-        error!("🚫 UNMATCHED ROUTE ACCESSED!");
-        error!("🚫 Method: {}", request.method());
-        error!("🚫 URI: {}", request.uri());
-        error!("🚫 Full URI: {}", request.uri());
-        error!("🚫 Headers: {:?}", request.headers());
-        error!("🚫 User-Agent: {:?}", request.headers().get("user-agent"));
-        error!("🚫 Host: {:?}", request.headers().get("host"));
-        error!("🚫 Accept: {:?}", request.headers().get("accept"));
-        error!("🚫 Content-Type: {:?}", request.headers().get("content-type"));
-        error!("🚫 Content-Length: {:?}", request.headers().get("content-length"));
-        error!("🚫 ======================================");
-        axum::http::StatusCode::NOT_FOUND
-    });
+    app
+}
 
-    // Apply middleware at the end
-    app.layer(
-        ServiceBuilder::new()
-            .layer(TraceLayer::new_for_http())
-            .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any)),
-    )
+/// Catch-all handler for debugging unmatched routes
+async fn unmatched_route(request: axum::extract::Request) -> axum::http::StatusCode {
+    error!("🚫 ======================================");
+    error!("🚫 UNMATCHED ROUTE ACCESSED!");
+    error!("🚫 Method: {}", request.method());
+    error!("🚫 URI: {}", request.uri());
+    error!("🚫 Full URI: {}", request.uri());
+    error!("🚫 User-Agent: {:?}", request.headers().get("user-agent"));
+    error!("🚫 Host: {:?}", request.headers().get("host"));
+    error!("🚫 Accept: {:?}", request.headers().get("accept"));
+    error!("🚫 Content-Type: {:?}", request.headers().get("content-type"));
+    error!("🚫 Content-Length: {:?}", request.headers().get("content-length"));
+    error!("🚫 ======================================");
+    axum::http::StatusCode::NOT_FOUND
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use settings::{LlmBackendSettings, MockSettings, RateLimit};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_single_mode_rate_limit() {
+        let backend = LlmBackendSettings::Mock { model: "mock".to_string(), mock: MockSettings::default() };
+        let mut config = Settings { llm_backend: backend.clone(), ..Default::default() };
+        config.rate_limits.global = RateLimit { requests_per_minute: Some(1), ..Default::default() };
+        let state = AppState::new(service::Service::new(&backend).unwrap(), config.clone());
+        let app = build_single_mode_app(state, &config);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let http = reqwest::Client::new();
+        let body = json!({"model": "mock", "messages": [{"role": "user", "content": "hi"}]});
+        let first = http.post(&url).json(&body).send().await.unwrap();
+        assert_eq!(first.status(), 200);
+        assert_eq!(first.headers()["x-ratelimit-remaining-requests"], "0");
+        let second = http.post(&url).json(&body).send().await.unwrap();
+        assert_eq!(second.status(), 429);
+        assert!(second.headers().contains_key("retry-after"));
+    }
 }
@@ -0,0 +1,248 @@
+use crate::adapters::ClientAdapter;
+use crate::api::auth::Frontend;
+use crate::api::ollama::detect_ollama_client;
+use crate::api::inspect::RequestInfo;
+use crate::api::{AppState, UpstreamError, PROVIDER_HEADER};
+use crate::settings::Settings;
+use crate::usage::UsageScanner;
+use ::metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+use std::time::{Duration, Instant};
+
+static HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
+
+/// Histogram buckets in seconds, from a quick completion to a long stream
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Install the Prometheus recorder
+///
+/// Must be called inside the Tokio runtime; later calls are no-ops.
+pub fn install() -> anyhow::Result<()> {
+    if HANDLE.get().is_some() {
+        return Ok(());
+    }
+    let handle = PrometheusBuilder::new()
+        .set_buckets(LATENCY_BUCKETS)?
+        .install_recorder()?;
+    describe();
+
+    // 定期清理，避免直方图样本无限增长
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(10));
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    let _ = HANDLE.set(handle);
+    Ok(())
+}
+
+fn describe() {
+    describe_counter!("llm_link_requests_total", "Inference requests by response status");
+    describe_histogram!("llm_link_request_duration_seconds", Unit::Seconds, "Time until the response body finished");
+    describe_histogram!(
+        "llm_link_time_to_first_token_seconds",
+        Unit::Seconds,
+        "Time until the first chunk of a streamed response"
+    );
+    describe_counter!("llm_link_prompt_tokens_total", "Prompt tokens reported by providers");
+    describe_counter!("llm_link_completion_tokens_total", "Completion tokens reported by providers");
+    describe_counter!("llm_link_upstream_errors_total", "Failed upstream requests by error class");
+    describe_gauge!("llm_link_active_streams", "Streamed responses currently being sent");
+}
+
+/// `GET /metrics` in the Prometheus text format
+pub async fn render() -> Response {
+    match HANDLE.get() {
+        Some(handle) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            handle.render(),
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, "Metrics are not enabled").into_response(),
+    }
+}
+
+/// Client adapter label: detected for Ollama, fixed for the other frontends
+pub(crate) fn client_adapter(frontend: Frontend, headers: &HeaderMap, config: &Settings) -> &'static str {
+    match frontend {
+        Frontend::OpenAI => ClientAdapter::OpenAI.as_str(),
+        Frontend::Ollama => detect_ollama_client(headers, config).as_str(),
+        Frontend::Anthropic => "none",
+    }
+}
+
+pub(crate) fn is_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("event-stream") || value.contains("ndjson"))
+}
+
+/// Metrics of one request, recorded when its response body is dropped
+struct RequestMetrics {
+    labels: Vec<(&'static str, String)>,
+    status: StatusCode,
+    upstream_error: Option<&'static str>,
+    stream: bool,
+    started: Instant,
+    first_chunk: Option<Duration>,
+    estimated_prompt_tokens: u32,
+    scanner: UsageScanner,
+}
+
+impl RequestMetrics {
+    fn with_label(&self, name: &'static str, value: impl Into<String>) -> Vec<(&'static str, String)> {
+        let mut labels = self.labels.clone();
+        labels.push((name, value.into()));
+        labels
+    }
+}
+
+impl Drop for RequestMetrics {
+    fn drop(&mut self) {
+        self.scanner.finish();
+        let labels = &self.labels;
+
+        counter!("llm_link_requests_total", &self.with_label("status", self.status.as_str())).increment(1);
+        histogram!("llm_link_request_duration_seconds", labels).record(self.started.elapsed().as_secs_f64());
+        if self.stream {
+            gauge!("llm_link_active_streams", labels).decrement(1.0);
+            if let Some(first_chunk) = self.first_chunk {
+                histogram!("llm_link_time_to_first_token_seconds", labels).record(first_chunk.as_secs_f64());
+            }
+        }
+
+        if self.status.is_success() {
+            let prompt_tokens = self.scanner.prompt_tokens.unwrap_or(self.estimated_prompt_tokens.into());
+            counter!("llm_link_prompt_tokens_total", labels).increment(prompt_tokens);
+            counter!("llm_link_completion_tokens_total", labels).increment(self.scanner.completion_tokens.unwrap_or(0));
+        }
+
+        // 流式响应中途出错时状态码已经是 200
+        let error_class = self.upstream_error.or(self.scanner.failed.then_some("stream"));
+        if let Some(class) = error_class {
+            counter!("llm_link_upstream_errors_total", &self.with_label("class", class)).increment(1);
+        }
+    }
+}
+
+/// Metrics middleware for the inference endpoints of all frontends
+///
+/// Labels every request with its frontend, provider, model and client adapter.
+pub async fn track(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(info) = RequestInfo::inference(&request) else {
+        return next.run(request).await;
+    };
+    let (frontend, model, estimated_prompt_tokens) = (info.frontend, info.model.clone(), info.prompt_tokens);
+    let adapter = client_adapter(frontend, request.headers(), &*state.config.read().await);
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    // 上游失败时响应里没有 provider 头，按路由推断
+    let provider = match response.headers().get(PROVIDER_HEADER).and_then(|value| value.to_str().ok()) {
+        Some(provider) => Some(provider.to_string()),
+        None => state.llm_service.read().await.provider_for(model.as_deref()),
+    };
+    let labels = vec![
+        ("frontend", frontend.as_str().to_string()),
+        ("provider", provider.unwrap_or_else(|| "unknown".to_string())),
+        ("model", model.unwrap_or_else(|| "unknown".to_string())),
+        ("adapter", adapter.to_string()),
+    ];
+    let stream = is_stream(response.headers());
+    if stream {
+        gauge!("llm_link_active_streams", &labels).increment(1.0);
+    }
+
+    let mut metrics = RequestMetrics {
+        labels,
+        status: response.status(),
+        upstream_error: response.extensions().get::<UpstreamError>().map(|error| error.0),
+        stream,
+        started,
+        first_chunk: None,
+        estimated_prompt_tokens,
+        scanner: UsageScanner::default(),
+    };
+
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                if metrics.first_chunk.is_none() && !bytes.is_empty() {
+                    metrics.first_chunk = Some(metrics.started.elapsed());
+                }
+                metrics.scanner.push(bytes);
+            }
+            chunk
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_metrics() {
+        let recorder = PrometheusBuilder::new().set_buckets(LATENCY_BUCKETS).unwrap().build_recorder();
+        let handle = recorder.handle();
+        let labels = vec![
+            ("frontend", "openai".to_string()),
+            ("provider", "zhipu".to_string()),
+            ("model", "glm-4".to_string()),
+            ("adapter", "openai".to_string()),
+        ];
+
+        ::metrics::with_local_recorder(&recorder, || {
+            gauge!("llm_link_active_streams", &labels).increment(1.0);
+            let mut streamed = RequestMetrics {
+                labels: labels.clone(),
+                status: StatusCode::OK,
+                upstream_error: None,
+                stream: true,
+                started: Instant::now(),
+                first_chunk: Some(Duration::from_millis(20)),
+                estimated_prompt_tokens: 3,
+                scanner: UsageScanner::default(),
+            };
+            streamed.scanner.push(b"data: {\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":5}}\n\n");
+            drop(streamed);
+
+            drop(RequestMetrics {
+                labels: labels.clone(),
+                status: StatusCode::TOO_MANY_REQUESTS,
+                upstream_error: Some("rate_limit"),
+                stream: false,
+                started: Instant::now(),
+                first_chunk: None,
+                estimated_prompt_tokens: 3,
+                scanner: UsageScanner::default(),
+            });
+        });
+
+        let output = handle.render();
+        let labels = r#"frontend="openai",provider="zhipu",model="glm-4",adapter="openai""#;
+        assert!(output.contains(&format!("llm_link_requests_total{{{},status=\"200\"}} 1", labels)));
+        assert!(output.contains(&format!("llm_link_requests_total{{{},status=\"429\"}} 1", labels)));
+        assert!(output.contains(&format!("llm_link_prompt_tokens_total{{{}}} 12", labels)));
+        assert!(output.contains(&format!("llm_link_completion_tokens_total{{{}}} 5", labels)));
+        assert!(output.contains(&format!("llm_link_upstream_errors_total{{{},class=\"rate_limit\"}} 1", labels)));
+        assert!(output.contains(&format!("llm_link_active_streams{{{}}} 0", labels)));
+        assert!(output.contains(&format!("llm_link_time_to_first_token_seconds_count{{{}}} 1", labels)));
+        assert!(output.contains(&format!("llm_link_request_duration_seconds_bucket{{{},le=\"0.05\"}} 2", labels)));
+    }
+}
@@ -1,18 +1,13 @@
 use clap::ValueEnum;
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
 pub enum RunMode {
     /// Single provider mode with YAML config (traditional)
     Single,
     /// Multi-provider mode with database and web interface
-    Multi,
-}
-
-impl Default for RunMode {
-    fn default() -> Self {
-        Self::Multi  // Default to multi-mode for better UX
-    }
+    #[default]
+    Multi,  // Default to multi-mode for better UX
 }
 
 impl fmt::Display for RunMode {
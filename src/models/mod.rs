@@ -9,17 +9,54 @@ pub struct ModelInfo {
     pub description: String,
     #[serde(default)]
     pub supports_tools: bool,
-    #[serde(default = "default_context_length")]
-    pub context_length: u32,
+    /// Whether the model accepts image input
+    #[serde(default)]
+    pub supports_vision: bool,
+    /// Context window in tokens, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
+}
+
+/// Price in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input: f64,
+    #[serde(default)]
+    pub output: f64,
 }
 
-fn default_context_length() -> u32 {
-    4096
+impl ModelPricing {
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input + completion_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// Context window assumed for models without a `context_length`
+pub const DEFAULT_CONTEXT_LENGTH: u32 = 4096;
+
+/// Embedding model metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingModelInfo {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Default output dimensions
+    pub dimensions: u32,
+    /// Whether the output size can be reduced with `dimensions`
+    #[serde(default)]
+    pub supports_dimensions: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderModels {
     pub models: Vec<ModelInfo>,
+    #[serde(default)]
+    pub embedding_models: Vec<EmbeddingModelInfo>,
 }
 
 /// Models configuration using HashMap for flexible provider support
@@ -65,6 +102,29 @@ impl ModelsConfig {
             .unwrap_or_default()
     }
 
+    /// Get embedding models for a specific provider
+    pub fn get_embedding_models_for_provider(&self, provider: &str) -> Vec<EmbeddingModelInfo> {
+        self.providers
+            .get(&provider.to_lowercase())
+            .map(|p| p.embedding_models.clone())
+            .unwrap_or_default()
+    }
+
+    /// Price of a model or embedding model, by id across all providers
+    ///
+    /// A `provider/` prefix on the name is ignored.
+    pub fn pricing(&self, model: &str) -> Option<ModelPricing> {
+        let model = model.rsplit('/').next().unwrap_or(model);
+        self.providers.values().find_map(|provider| {
+            provider
+                .models
+                .iter()
+                .find(|m| m.id == model)
+                .and_then(|m| m.pricing)
+                .or_else(|| provider.embedding_models.iter().find(|m| m.id == model).and_then(|m| m.pricing))
+        })
+    }
+
     /// Get all provider names
     #[allow(dead_code)]
     pub fn get_all_providers(&self) -> Vec<String> {
@@ -84,23 +144,30 @@ impl Default for ModelsConfig {
                     name: "GPT-4o".to_string(),
                     description: "GPT-4 Omni model".to_string(),
                     supports_tools: true,
-                    context_length: 128000,
+                    supports_vision: true,
+                    context_length: Some(128000),
+                    pricing: None,
                 },
                 ModelInfo {
                     id: "gpt-4".to_string(),
                     name: "GPT-4".to_string(),
                     description: "Most capable GPT-4 model".to_string(),
                     supports_tools: true,
-                    context_length: 8192,
+                    supports_vision: false,
+                    context_length: Some(8192),
+                    pricing: None,
                 },
                 ModelInfo {
                     id: "gpt-3.5-turbo".to_string(),
                     name: "GPT-3.5 Turbo".to_string(),
                     description: "Fast and efficient model".to_string(),
                     supports_tools: true,
-                    context_length: 16385,
+                    supports_vision: false,
+                    context_length: Some(16385),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         // Anthropic
@@ -111,16 +178,21 @@ impl Default for ModelsConfig {
                     name: "Claude 3.5 Sonnet".to_string(),
                     description: "Latest Claude 3.5 Sonnet model".to_string(),
                     supports_tools: true,
-                    context_length: 200000,
+                    supports_vision: true,
+                    context_length: Some(200000),
+                    pricing: None,
                 },
                 ModelInfo {
                     id: "claude-3-haiku-20240307".to_string(),
                     name: "Claude 3 Haiku".to_string(),
                     description: "Fast Claude 3 model".to_string(),
                     supports_tools: true,
-                    context_length: 200000,
+                    supports_vision: true,
+                    context_length: Some(200000),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         // Zhipu
@@ -131,16 +203,21 @@ impl Default for ModelsConfig {
                     name: "GLM-4 Flash".to_string(),
                     description: "Fast GLM-4 model".to_string(),
                     supports_tools: true,
-                    context_length: 128000,
+                    supports_vision: false,
+                    context_length: Some(128000),
+                    pricing: None,
                 },
                 ModelInfo {
                     id: "glm-4".to_string(),
                     name: "GLM-4".to_string(),
                     description: "Standard GLM-4 model".to_string(),
                     supports_tools: true,
-                    context_length: 128000,
+                    supports_vision: false,
+                    context_length: Some(128000),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         // Ollama
@@ -151,16 +228,21 @@ impl Default for ModelsConfig {
                     name: "Llama 3.2".to_string(),
                     description: "Latest Llama model".to_string(),
                     supports_tools: false,
-                    context_length: 128000,
+                    supports_vision: false,
+                    context_length: Some(128000),
+                    pricing: None,
                 },
                 ModelInfo {
                     id: "llama2".to_string(),
                     name: "Llama 2".to_string(),
                     description: "Stable Llama 2 model".to_string(),
                     supports_tools: false,
-                    context_length: 4096,
+                    supports_vision: false,
+                    context_length: Some(4096),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         // Aliyun
@@ -171,16 +253,21 @@ impl Default for ModelsConfig {
                     name: "Qwen Turbo".to_string(),
                     description: "Fast Qwen model".to_string(),
                     supports_tools: true,
-                    context_length: 8192,
+                    supports_vision: false,
+                    context_length: Some(8192),
+                    pricing: None,
                 },
                 ModelInfo {
                     id: "qwen-plus".to_string(),
                     name: "Qwen Plus".to_string(),
                     description: "Enhanced Qwen model".to_string(),
                     supports_tools: true,
-                    context_length: 32768,
+                    supports_vision: false,
+                    context_length: Some(32768),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         // Volcengine
@@ -191,9 +278,12 @@ impl Default for ModelsConfig {
                     name: "Doubao Pro".to_string(),
                     description: "Volcengine Doubao model".to_string(),
                     supports_tools: true,
-                    context_length: 32768,
+                    supports_vision: false,
+                    context_length: Some(32768),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         // Tencent
@@ -204,9 +294,12 @@ impl Default for ModelsConfig {
                     name: "Hunyuan Lite".to_string(),
                     description: "Tencent Hunyuan Lite model".to_string(),
                     supports_tools: true,
-                    context_length: 256000,
+                    supports_vision: false,
+                    context_length: Some(256000),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         // Longcat
@@ -217,9 +310,12 @@ impl Default for ModelsConfig {
                     name: "LongCat Flash Chat".to_string(),
                     description: "High-performance general dialogue model".to_string(),
                     supports_tools: false,
-                    context_length: 4096,
+                    supports_vision: false,
+                    context_length: Some(4096),
+                    pricing: None,
                 },
             ],
+            embedding_models: Vec::new(),
         });
 
         Self { providers }
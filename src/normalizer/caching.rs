@@ -0,0 +1,232 @@
+use super::reasoning::chunk_parts;
+use super::tool_calls::ToolCallAccumulator;
+use super::types::{Response, Usage};
+use super::Client;
+use crate::cache::{self, ResponseCache};
+use crate::tokenizer::counter_for_model;
+use llm_connector::error::LlmConnectorError;
+use llm_connector::types::{ChatRequest, ChatStream, StreamingResponse};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Cache entry a request maps to (`--cache`)
+pub(super) struct CacheSlot {
+    cache: Arc<ResponseCache>,
+    key: String,
+}
+
+impl CacheSlot {
+    pub(super) async fn store(&self, response: &Response) {
+        self.cache.put(&self.key, response).await;
+    }
+
+    /// Pass `stream` through, caching the assembled reply once it completes
+    pub(super) fn capture(self, request: &ChatRequest, stream: ChatStream) -> ChatStream {
+        use futures_util::StreamExt;
+
+        let mut capture = StreamCapture {
+            slot: Some(self),
+            request: request.clone(),
+            model: request.model.clone(),
+            content: String::new(),
+            reasoning: String::new(),
+            tool_calls: ToolCallAccumulator::default(),
+            finish_reason: None,
+            usage: None,
+            failed: false,
+        };
+        Box::pin(stream.map(move |item| {
+            capture.push(&item);
+            item
+        }))
+    }
+}
+
+impl Client {
+    /// The cache entry for `request`, if caching is on and the reply is deterministic
+    pub(super) fn cache_slot(&self, request: &ChatRequest) -> Option<CacheSlot> {
+        let cache = self.cache.clone()?;
+        let key = cache::key(&self.cache_scope(), request)?;
+        Some(CacheSlot { cache, key })
+    }
+
+    /// Which backend a cache entry belongs to
+    ///
+    /// The provider type alone is not enough: two backends of the same type
+    /// can point at different `base_url`s or accounts.
+    fn cache_scope(&self) -> String {
+        let settings = serde_json::to_string(&self.backend).unwrap_or_default();
+        format!("{}:{}", self.backend.provider_name(), hex::encode(Sha256::digest(settings.as_bytes())))
+    }
+
+    /// Look `slot` up and note the outcome for the `x-llm-link-cache` header
+    pub(super) async fn cached_response(&self, slot: Option<&CacheSlot>) -> Option<Response> {
+        let slot = slot?;
+        let cached = slot.cache.get(&slot.key).await;
+        cache::mark(cached.is_some());
+        if cached.is_some() {
+            tracing::info!("💾 Serving cached response");
+        }
+        cached
+    }
+}
+
+/// Collects a streamed reply; stored on drop if the stream finished cleanly
+struct StreamCapture {
+    slot: Option<CacheSlot>,
+    request: ChatRequest,
+    model: String,
+    content: String,
+    reasoning: String,
+    tool_calls: ToolCallAccumulator,
+    finish_reason: Option<String>,
+    usage: Option<Usage>,
+    failed: bool,
+}
+
+impl StreamCapture {
+    fn push(&mut self, item: &Result<StreamingResponse, LlmConnectorError>) {
+        let chunk = match item {
+            Ok(chunk) => chunk,
+            Err(_) => {
+                self.failed = true;
+                return;
+            }
+        };
+        if !chunk.model.is_empty() {
+            self.model = chunk.model.clone();
+        }
+        let (content, reasoning) = chunk_parts(chunk);
+        self.content.push_str(content);
+        if let Some(reasoning) = reasoning {
+            self.reasoning.push_str(reasoning);
+        }
+        if let Some(choice) = chunk.choices.first() {
+            if let Some(tool_calls) = choice.delta.tool_calls.as_deref() {
+                self.tool_calls.push(tool_calls);
+            }
+            if choice.finish_reason.is_some() {
+                self.finish_reason = choice.finish_reason.clone();
+            }
+        }
+        if let Some(usage) = &chunk.usage {
+            self.usage = Some(Usage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                total_tokens: usage.total_tokens,
+            });
+        }
+    }
+}
+
+impl Drop for StreamCapture {
+    fn drop(&mut self) {
+        // 出错、客户端中途断开或没有 finish_reason 的流不缓存
+        if self.failed || self.finish_reason.is_none() {
+            return;
+        }
+        let Some(slot) = self.slot.take() else {
+            return;
+        };
+        let usage = self.usage.take().unwrap_or_else(|| {
+            // 上游没有报告用量时按本地估算
+            let counter = counter_for_model(&self.model);
+            let prompt_tokens = counter.count_request(&self.request.messages, self.request.tools.as_deref());
+            let completion_tokens = counter.count_text(&self.content) + counter.count_text(&self.tool_calls.text());
+            Usage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }
+        });
+        let tool_calls = (!self.tool_calls.is_empty())
+            .then(|| serde_json::to_value(self.tool_calls.to_tool_calls()).ok())
+            .flatten();
+        let response = Response {
+            content: std::mem::take(&mut self.content),
+            model: std::mem::take(&mut self.model),
+            usage,
+            tool_calls,
+            finish_reason: self.finish_reason.take(),
+            reasoning: Some(std::mem::take(&mut self.reasoning)).filter(|reasoning| !reasoning.is_empty()),
+        };
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move { slot.store(&response).await });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{LlmBackendSettings, MockSettings};
+    use futures_util::StreamExt;
+    use llm_connector::types::Message;
+    use std::time::Duration;
+
+    fn request(text: &str) -> ChatRequest {
+        ChatRequest {
+            model: "mock".to_string(),
+            messages: vec![Message::user(text)],
+            temperature: Some(0.0),
+            ..Default::default()
+        }
+    }
+
+    async fn collect(client: &Client, request: &ChatRequest) -> Vec<StreamingResponse> {
+        let stream = client.open_stream(request).await.unwrap();
+        stream.map(|chunk| chunk.unwrap()).collect().await
+    }
+
+    #[tokio::test]
+    async fn test_cached_replies_against_mock_backend() {
+        let backend = LlmBackendSettings::Mock { model: "mock".to_string(), mock: MockSettings::default() };
+        let mut client = Client::new(&backend).unwrap();
+        let cache = Arc::new(ResponseCache::memory(10, Duration::from_secs(60)));
+        client.cache = Some(cache.clone());
+
+        // 非流式的结果被缓存，流式请求以单个合成块重放
+        let hello = request("hello cache");
+        assert_eq!(client.send_chat(&hello).await.unwrap().content, "hello cache");
+        let key = client.cache_slot(&hello).unwrap().key;
+        assert!(cache.get(&key).await.is_some());
+        let chunks = collect(&client, &ChatRequest { stream: Some(true), ..hello }).await;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, "hello cache");
+
+        // 完整的流在结束后写入缓存
+        let streamed = request("streamed before it was cached");
+        assert!(collect(&client, &streamed).await.len() > 1);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let cached = cache.get(&client.cache_slot(&streamed).unwrap().key).await.unwrap();
+        assert_eq!(cached.content, "streamed before it was cached");
+        assert!(cached.usage.total_tokens > 0);
+        assert_eq!(collect(&client, &streamed).await.len(), 1);
+
+        // 中断的流不缓存；非确定性请求不查缓存
+        let broken = request("broken [mock:disconnect=1]");
+        let _ = client.open_stream(&broken).await.unwrap().collect::<Vec<_>>().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(cache.get(&client.cache_slot(&broken).unwrap().key).await.is_none());
+        assert!(client.cache_slot(&ChatRequest { temperature: Some(0.5), ..request("hi") }).is_none());
+
+        // 同类型的不同后端不共享缓存
+        let fixed = |response: &str| LlmBackendSettings::Mock {
+            model: "mock".to_string(),
+            mock: MockSettings { response: Some(response.to_string()), ..Default::default() },
+        };
+        let (mut first, mut second) = (Client::new(&fixed("first")).unwrap(), Client::new(&fixed("second")).unwrap());
+        first.cache = Some(cache.clone());
+        second.cache = Some(cache.clone());
+        let same = request("same prompt");
+        assert_eq!(first.send_chat(&same).await.unwrap().content, "first");
+        assert_eq!(second.send_chat(&same).await.unwrap().content, "second");
+
+        let openai = |base_url: &str| LlmBackendSettings::OpenAI {
+            api_key: "key".to_string(),
+            base_url: Some(base_url.to_string()),
+            model: "gpt-4o".to_string(),
+        };
+        let mut a = Client::new(&openai("https://a.example/v1")).unwrap();
+        let mut b = Client::new(&openai("https://b.example/v1")).unwrap();
+        a.cache = Some(cache.clone());
+        b.cache = Some(cache);
+        assert_ne!(a.cache_slot(&same).unwrap().key, b.cache_slot(&same).unwrap().key);
+    }
+}
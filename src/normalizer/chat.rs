@@ -1,7 +1,11 @@
 use super::Client;
-use crate::normalizer::types::{Response, Usage};
-use anyhow::{anyhow, Result};
+use crate::normalizer::types::{ChatOptions, Response, Usage};
+use super::errors::connector_error;
+use super::reasoning::apply_reasoning_mode;
+use crate::telemetry;
+use anyhow::Result;
 use llm_connector::types::ChatRequest;
+use tracing::Instrument;
 
 impl Client {
     /// Send a non-streaming chat request to the LLM
@@ -11,51 +15,90 @@ impl Client {
         model: &str,
         messages: Vec<llm_connector::types::Message>,
         tools: Option<Vec<llm_connector::types::Tool>>,
+        options: &ChatOptions,
     ) -> Result<Response> {
         // Messages are already in llm-connector format
-        let request = ChatRequest {
+        let mut request = ChatRequest {
             model: model.to_string(),
             messages,
             tools,
             ..Default::default()
         };
+        options.apply_to(&mut request);
+        self.prepare_images(&mut request)?;
+        self.check_context_length(&request)?;
 
-        let response = self.llm_client.chat(&request).await
-            .map_err(|e| anyhow!("LLM connector error: {}", e))?;
+        let mut response = match &options.response_format {
+            Some(format) => self.complete_structured(request, format).await?,
+            None => self.send_chat(&request).await?,
+        };
+        apply_reasoning_mode(options.reasoning, &mut response);
+        Ok(response)
+    }
+
+    /// Span of an upstream call for `request`
+    pub(crate) fn upstream_span(&self, request: &ChatRequest) -> tracing::Span {
+        let span = telemetry::upstream_span(self.backend.provider_name(), "chat", &request.model);
+        if let Some(max_tokens) = request.max_tokens {
+            span.record("gen_ai.request.max_tokens", i64::from(max_tokens));
+        }
+        if let Some(temperature) = request.temperature {
+            span.record("gen_ai.request.temperature", f64::from(temperature));
+        }
+        span
+    }
+
+    /// Send a prepared request and extract the reply
+    pub(crate) async fn send_chat(&self, request: &ChatRequest) -> Result<Response> {
+        self.dialect().warn_dropped(self.backend.provider_name(), request);
+        let cache_slot = self.cache_slot(request);
+        if let Some(cached) = self.cached_response(cache_slot.as_ref()).await {
+            return Ok(cached);
+        }
+
+        let span = self.upstream_span(request);
+        let response = match self.upstream_chat(request).instrument(span.clone()).await {
+            Ok(response) => response,
+            Err(e) => {
+                let e = connector_error("LLM connector error", e);
+                telemetry::record_error(&span, &e);
+                return Err(e);
+            }
+        };
 
         // Extract content and usage information
         let (prompt_tokens, completion_tokens, total_tokens) = response.get_usage_safe();
 
         // Extract content and tool_calls from choices[0].message or response.content
-        let (content, tool_calls) = if let Some(choice) = response.choices.first() {
+        let (content, reasoning, tool_calls) = if let Some(choice) = response.choices.first() {
             let msg = &choice.message;
 
-            // Extract content (could be in content, reasoning_content, reasoning, etc.)
-            let content = if msg.is_text_only() && !msg.content_as_text().is_empty() {
-                msg.content_as_text()
-            } else if let Some(reasoning) = &msg.reasoning_content {
-                reasoning.clone()
-            } else if let Some(reasoning) = &msg.reasoning {
-                reasoning.clone()
-            } else {
-                String::new()
-            };
+            // Reasoning (reasoning_content, reasoning, thinking, ...) is kept apart
+            // from the answer; the request's ReasoningMode decides what is returned
+            let content = if msg.is_text_only() { msg.content_as_text() } else { String::new() };
+            let reasoning = msg.reasoning_any().map(str::to_string)
+                .or_else(|| response.reasoning_content.clone());
 
             // Extract tool_calls if present
             let tool_calls = msg.tool_calls.as_ref()
                 .and_then(|tc| serde_json::to_value(tc).ok());
 
-            (content, tool_calls)
+            (content, reasoning, tool_calls)
         } else if !response.content.is_empty() {
             // Fallback: some providers (like Aliyun in llm-connector 0.4.16)
             // put content directly in response.content instead of choices
-            tracing::info!("📦 Using response.content: '{}'", response.content);
-            (response.content.clone(), None)
+            tracing::debug!("📦 Using response.content ({} chars)", response.content.len());
+            (response.content.clone(), response.reasoning_content.clone(), None)
         } else {
-            (String::new(), None)
+            (String::new(), None, None)
         };
 
-        Ok(Response {
+        let finish_reason = response.choices.first().and_then(|choice| choice.finish_reason.clone());
+        span.record("gen_ai.response.model", response.model.as_str());
+        telemetry::record_usage(&span, prompt_tokens, completion_tokens);
+        telemetry::record_finish_reasons(&span, finish_reason.as_deref());
+
+        let response = Response {
             content,
             model: response.model,
             usage: Usage {
@@ -64,7 +107,13 @@ impl Client {
                 total_tokens,
             },
             tool_calls,
-        })
+            finish_reason,
+            reasoning,
+        };
+        if let Some(slot) = &cache_slot {
+            slot.store(&response).await;
+        }
+        Ok(response)
     }
 }
 
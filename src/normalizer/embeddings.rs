@@ -0,0 +1,222 @@
+use super::errors::connector_error;
+use super::Client;
+use crate::models::EmbeddingModelInfo;
+use crate::normalizer::types::{EmbeddingRequest, Embeddings};
+use crate::settings::LlmBackendSettings;
+use crate::tokenizer::counter_for_model;
+use anyhow::{anyhow, Result};
+use llm_connector::error::LlmConnectorError;
+use serde_json::{json, Value};
+
+/// Wire format of an embeddings endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EmbeddingsApi {
+    /// `POST /embeddings` with `{model, input, dimensions}`
+    OpenAi,
+    /// Ollama native `POST /api/embed`
+    Ollama,
+}
+
+impl Client {
+    /// Embeddings endpoint of this backend, `None` if the provider has none
+    fn embeddings_endpoint(&self) -> Option<(String, Option<&str>, EmbeddingsApi)> {
+        match &self.backend {
+            LlmBackendSettings::OpenAI { api_key, base_url, .. } => {
+                let base = base_url.as_deref().unwrap_or("https://api.openai.com");
+                Some((format!("{}/v1/embeddings", base.trim_end_matches('/')), Some(api_key), EmbeddingsApi::OpenAi))
+            }
+            LlmBackendSettings::Zhipu { api_key, base_url, .. } => {
+                let base = base_url.as_deref().unwrap_or("https://open.bigmodel.cn/api/paas/v4");
+                Some((format!("{}/embeddings", base.trim_end_matches('/')), Some(api_key), EmbeddingsApi::OpenAi))
+            }
+            LlmBackendSettings::Aliyun { api_key, .. } => Some((
+                "https://dashscope.aliyuncs.com/compatible-mode/v1/embeddings".to_string(),
+                Some(api_key),
+                EmbeddingsApi::OpenAi,
+            )),
+            LlmBackendSettings::Volcengine { api_key, .. } => Some((
+                "https://ark.cn-beijing.volces.com/api/v3/embeddings".to_string(),
+                Some(api_key),
+                EmbeddingsApi::OpenAi,
+            )),
+            LlmBackendSettings::Tencent { api_key, .. } => Some((
+                "https://api.hunyuan.cloud.tencent.com/v1/embeddings".to_string(),
+                Some(api_key),
+                EmbeddingsApi::OpenAi,
+            )),
+            LlmBackendSettings::Ollama { base_url, .. } => {
+                let base = base_url.as_deref().unwrap_or("http://localhost:11434");
+                Some((format!("{}/api/embed", base.trim_end_matches('/')), None, EmbeddingsApi::Ollama))
+            }
+            // Anthropic / Moonshot / Minimax / Longcat / mock 没有 embeddings 接口
+            LlmBackendSettings::Anthropic { .. }
+            | LlmBackendSettings::Longcat { .. }
+            | LlmBackendSettings::Moonshot { .. }
+            | LlmBackendSettings::Minimax { .. }
+            | LlmBackendSettings::Mock { .. } => None,
+        }
+    }
+
+    /// Whether this backend's provider offers an embeddings endpoint
+    pub fn supports_embeddings(&self) -> bool {
+        self.embeddings_endpoint().is_some()
+    }
+
+    /// Embedding models for this backend's provider from models.yaml
+    pub fn embedding_model_infos(&self) -> Vec<EmbeddingModelInfo> {
+        self.models_config.get_embedding_models_for_provider(self.backend.provider_name())
+    }
+
+    /// Whether `model` is one of the provider's embedding models in models.yaml
+    pub fn knows_embedding_model(&self, model: &str) -> bool {
+        self.embedding_model_infos().iter().any(|info| info.id == model)
+    }
+
+    /// First embedding model listed for this provider
+    pub fn default_embedding_model(&self) -> Option<String> {
+        self.embedding_model_infos().into_iter().next().map(|info| info.id)
+    }
+
+    /// Embed a batch of inputs with `model`
+    pub async fn embed(&self, model: &str, request: &EmbeddingRequest) -> Result<Embeddings> {
+        let (url, api_key, api) = self.embeddings_endpoint().ok_or_else(|| {
+            anyhow!("Provider '{}' does not support embeddings", self.backend.provider_name())
+        })?;
+
+        let mut dimensions = request.dimensions;
+        if let Some(info) = self.embedding_model_infos().iter().find(|info| info.id == model) {
+            if dimensions.is_some() && !info.supports_dimensions {
+                tracing::warn!("⚠️ Model '{}' does not support custom dimensions, ignoring", model);
+                dimensions = None;
+            }
+        }
+
+        let mut body = json!({
+            "model": model,
+            "input": request.input,
+        });
+        if let Some(dimensions) = dimensions {
+            body["dimensions"] = json!(dimensions);
+        }
+        if api == EmbeddingsApi::OpenAi {
+            body["encoding_format"] = json!("float");
+        }
+
+        let mut http_request = self.http.post(&url).json(&body);
+        if let Some(api_key) = api_key {
+            http_request = http_request.bearer_auth(api_key);
+        }
+        let response = http_request.send().await.map_err(|e| {
+            let error = if e.is_timeout() {
+                LlmConnectorError::TimeoutError(e.to_string())
+            } else if e.is_connect() {
+                LlmConnectorError::ConnectionError(e.to_string())
+            } else {
+                LlmConnectorError::NetworkError(e.to_string())
+            };
+            connector_error("Embeddings request failed", error)
+        })?;
+
+        let status = response.status();
+        let text = response.text().await.map_err(|e| {
+            connector_error("Embeddings request failed", LlmConnectorError::NetworkError(e.to_string()))
+        })?;
+        if !status.is_success() {
+            return Err(connector_error(
+                "Embeddings request failed",
+                LlmConnectorError::from_status_code(status.as_u16(), text),
+            ));
+        }
+
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid embeddings response: {}", e))?;
+        let (vectors, prompt_tokens) = match api {
+            EmbeddingsApi::OpenAi => parse_openai_embeddings(&value)?,
+            EmbeddingsApi::Ollama => parse_ollama_embeddings(&value)?,
+        };
+        if vectors.len() != request.input.len() {
+            return Err(anyhow!(
+                "Embeddings response has {} vectors for {} inputs",
+                vectors.len(),
+                request.input.len()
+            ));
+        }
+
+        // 上游未返回 usage 时按模型 tokenizer 估算
+        let prompt_tokens = prompt_tokens.unwrap_or_else(|| {
+            let counter = counter_for_model(model);
+            request.input.iter().map(|input| counter.count_text(input)).sum()
+        });
+
+        Ok(Embeddings {
+            model: value["model"].as_str().unwrap_or(model).to_string(),
+            vectors,
+            prompt_tokens,
+        })
+    }
+}
+
+fn parse_vector(value: &Value) -> Result<Vec<f32>> {
+    value
+        .as_array()
+        .ok_or_else(|| anyhow!("Invalid embeddings response: embedding is not an array"))?
+        .iter()
+        .map(|x| {
+            x.as_f64()
+                .map(|x| x as f32)
+                .ok_or_else(|| anyhow!("Invalid embeddings response: non-numeric value"))
+        })
+        .collect()
+}
+
+/// `{data: [{index, embedding}], usage: {prompt_tokens}}`
+fn parse_openai_embeddings(value: &Value) -> Result<(Vec<Vec<f32>>, Option<u32>)> {
+    let data = value["data"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Invalid embeddings response: missing data"))?;
+
+    // data 不保证按 index 排序
+    let mut indexed = data
+        .iter()
+        .enumerate()
+        .map(|(position, item)| {
+            let index = item["index"].as_u64().map_or(position, |i| i as usize);
+            Ok((index, parse_vector(&item["embedding"])?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    indexed.sort_by_key(|(index, _)| *index);
+
+    let prompt_tokens = value["usage"]["prompt_tokens"].as_u64().map(|t| t as u32);
+    Ok((indexed.into_iter().map(|(_, vector)| vector).collect(), prompt_tokens))
+}
+
+/// `{embeddings: [[...]], prompt_eval_count}`
+fn parse_ollama_embeddings(value: &Value) -> Result<(Vec<Vec<f32>>, Option<u32>)> {
+    let vectors = value["embeddings"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Invalid embeddings response: missing embeddings"))?
+        .iter()
+        .map(parse_vector)
+        .collect::<Result<Vec<_>>>()?;
+    let prompt_tokens = value["prompt_eval_count"].as_u64().map(|t| t as u32);
+    Ok((vectors, prompt_tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_openai_embeddings_sorts_by_index() {
+        let value = json!({
+            "data": [
+                {"object": "embedding", "index": 1, "embedding": [0.5, 0.25]},
+                {"object": "embedding", "index": 0, "embedding": [1.0, -1.0]}
+            ],
+            "usage": {"prompt_tokens": 7, "total_tokens": 7}
+        });
+        let (vectors, prompt_tokens) = parse_openai_embeddings(&value).unwrap();
+        assert_eq!(vectors, vec![vec![1.0, -1.0], vec![0.5, 0.25]]);
+        assert_eq!(prompt_tokens, Some(7));
+    }
+}
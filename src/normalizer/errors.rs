@@ -0,0 +1,66 @@
+use llm_connector::error::LlmConnectorError;
+
+/// Wrap an llm-connector error, keeping the original type in the error chain
+///
+/// The message still reads like `"<context>: <error>"`, but callers can
+/// downcast to `LlmConnectorError` to decide how to react (e.g. failover).
+pub fn connector_error(context: &str, e: LlmConnectorError) -> anyhow::Error {
+    let message = format!("{}: {}", context, e);
+    anyhow::Error::new(e).context(message)
+}
+
+/// Whether an error is worth retrying against another provider
+///
+/// Covers connect errors, timeouts, 429s and 5xx responses. Errors caused
+/// by the request itself (bad request, auth, unknown model) are not retried.
+pub fn is_failover_error(err: &anyhow::Error) -> bool {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<LlmConnectorError>())
+        .map(|e| match e {
+            LlmConnectorError::RateLimitError(_)
+            | LlmConnectorError::NetworkError(_)
+            | LlmConnectorError::ProviderError(_)
+            | LlmConnectorError::ServerError(_)
+            | LlmConnectorError::TimeoutError(_)
+            | LlmConnectorError::ConnectionError(_)
+            | LlmConnectorError::MaxRetriesExceeded(_)
+            | LlmConnectorError::HttpError(_) => true,
+            other => other.status_code() >= 500 && other.status_code() != 501,
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failover_classification() {
+        let retry = [
+            LlmConnectorError::RateLimitError("slow down".into()),
+            LlmConnectorError::ServerError("boom".into()),
+            LlmConnectorError::TimeoutError("timed out".into()),
+            LlmConnectorError::ConnectionError("refused".into()),
+        ];
+        for e in retry {
+            assert!(is_failover_error(&connector_error("LLM connector error", e)));
+        }
+
+        let no_retry = [
+            LlmConnectorError::AuthenticationError("bad key".into()),
+            LlmConnectorError::InvalidRequest("bad body".into()),
+            LlmConnectorError::UnsupportedModel("nope".into()),
+        ];
+        for e in no_retry {
+            assert!(!is_failover_error(&connector_error("LLM connector error", e)));
+        }
+
+        assert!(!is_failover_error(&anyhow::anyhow!("plain error")));
+    }
+
+    #[test]
+    fn test_connector_error_message() {
+        let err = connector_error("LLM connector error", LlmConnectorError::ServerError("boom".into()));
+        assert_eq!(err.to_string(), "LLM connector error: Server error: boom");
+    }
+}
@@ -0,0 +1,338 @@
+//! Recording and replay of upstream traffic (`--record` / `--replay`)
+//!
+//! Every upstream chat request is stored with its response, or its stream
+//! chunk by chunk with timings, as one JSON file per request. Replay serves
+//! these files instead of calling the provider, so the conversions in
+//! `stream.rs` can be tested without network access or API keys.
+
+use super::Client;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use llm_connector::error::LlmConnectorError;
+use llm_connector::types::{ChatRequest, ChatResponse, ChatStream, StreamingResponse};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+static CONFIGURED: OnceCell<Fixtures> = OnceCell::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fixtures {
+    /// Call providers and save every exchange to the directory
+    Record(PathBuf),
+    /// Serve saved exchanges from the directory instead of calling providers
+    Replay(PathBuf),
+}
+
+/// Set the fixture mode for all clients created afterwards
+pub fn init(fixtures: Fixtures) -> anyhow::Result<()> {
+    if let Fixtures::Record(dir) = &fixtures {
+        std::fs::create_dir_all(dir)?;
+    }
+    let _ = CONFIGURED.set(fixtures);
+    Ok(())
+}
+
+pub(super) fn configured() -> Option<Fixtures> {
+    CONFIGURED.get().cloned()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedError {
+    status: u16,
+    message: String,
+}
+
+impl From<&LlmConnectorError> for RecordedError {
+    fn from(error: &LlmConnectorError) -> Self {
+        Self { status: error.status_code(), message: error.to_string() }
+    }
+}
+
+impl From<RecordedError> for LlmConnectorError {
+    fn from(error: RecordedError) -> Self {
+        LlmConnectorError::from_status_code(error.status, error.message)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedChunk {
+    /// Milliseconds since the previous chunk, or since the request for the first one
+    delay_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chunk: Option<StreamingResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<RecordedError>,
+}
+
+impl RecordedChunk {
+    fn into_item(self) -> Result<StreamingResponse, LlmConnectorError> {
+        match (self.chunk, self.error) {
+            (Some(chunk), _) => Ok(chunk),
+            (None, Some(error)) => Err(error.into()),
+            (None, None) => Err(LlmConnectorError::ParseError("Empty chunk in fixture".to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Exchange {
+    Response(ChatResponse),
+    Stream(Vec<RecordedChunk>),
+    Error(RecordedError),
+}
+
+/// One fixture file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fixture {
+    provider: String,
+    recorded_at: DateTime<Utc>,
+    request: ChatRequest,
+    #[serde(flatten)]
+    exchange: Exchange,
+}
+
+/// `{provider}-{model}-{hash}.json`, the hash covering the whole request
+fn fixture_path(dir: &Path, provider: &str, request: &ChatRequest) -> PathBuf {
+    // 先转成 Value：对象的键有序，哈希与字段顺序无关
+    let canonical = serde_json::to_value(request).map(|value| value.to_string()).unwrap_or_default();
+    let hash = hex::encode(Sha256::digest(format!("{}\n{}", provider, canonical).as_bytes()));
+    let model: String = request
+        .model
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' })
+        .collect();
+    dir.join(format!("{}-{}-{}.json", provider, model, &hash[..16]))
+}
+
+fn save(dir: &Path, provider: &str, request: &ChatRequest, exchange: Exchange) {
+    let path = fixture_path(dir, provider, request);
+    let fixture = Fixture {
+        provider: provider.to_string(),
+        recorded_at: Utc::now(),
+        request: request.clone(),
+        exchange,
+    };
+    let written = serde_json::to_string_pretty(&fixture)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| std::fs::write(&path, json).map_err(anyhow::Error::from));
+    match written {
+        Ok(()) => tracing::info!("📼 Recorded upstream exchange to {}", path.display()),
+        Err(e) => tracing::error!("❌ Failed to record fixture {}: {}", path.display(), e),
+    }
+}
+
+fn load(dir: &Path, provider: &str, request: &ChatRequest) -> Result<Exchange, LlmConnectorError> {
+    let path = fixture_path(dir, provider, request);
+    let json = std::fs::read_to_string(&path).map_err(|_| {
+        LlmConnectorError::ConfigError(format!(
+            "No recorded fixture for this {} request to model '{}' (expected {})",
+            provider,
+            request.model,
+            path.display()
+        ))
+    })?;
+    let fixture: Fixture = serde_json::from_str(&json)
+        .map_err(|e| LlmConnectorError::ParseError(format!("Invalid fixture {}: {}", path.display(), e)))?;
+    tracing::info!("📼 Replaying upstream exchange from {}", path.display());
+    Ok(fixture.exchange)
+}
+
+/// Streamed chunks collected while recording, saved when the stream is dropped
+struct StreamRecorder {
+    dir: PathBuf,
+    provider: &'static str,
+    request: ChatRequest,
+    last: Instant,
+    chunks: Vec<RecordedChunk>,
+}
+
+impl StreamRecorder {
+    fn push(&mut self, item: &Result<StreamingResponse, LlmConnectorError>) {
+        let delay_ms = self.last.elapsed().as_millis() as u64;
+        self.last = Instant::now();
+        let (chunk, error) = match item {
+            Ok(chunk) => (Some(chunk.clone()), None),
+            Err(e) => (None, Some(RecordedError::from(e))),
+        };
+        self.chunks.push(RecordedChunk { delay_ms, chunk, error });
+    }
+}
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        let chunks = std::mem::take(&mut self.chunks);
+        save(&self.dir, self.provider, &self.request, Exchange::Stream(chunks));
+    }
+}
+
+/// Replay recorded chunks with their original timing
+fn replay_stream(chunks: Vec<RecordedChunk>) -> ChatStream {
+    Box::pin(futures_util::stream::iter(chunks).then(|recorded| async move {
+        tokio::time::sleep(Duration::from_millis(recorded.delay_ms)).await;
+        recorded.into_item()
+    }))
+}
+
+impl Client {
+    /// `llm_client.chat`, recorded or replayed according to `--record` / `--replay`
+    pub(super) async fn upstream_chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmConnectorError> {
+        let provider = self.backend.provider_name();
+        match &self.fixtures {
+            None => self.llm_client.chat(request).await,
+            Some(Fixtures::Replay(dir)) => match load(dir, provider, request)? {
+                Exchange::Response(response) => Ok(response),
+                Exchange::Error(error) => Err(error.into()),
+                Exchange::Stream(_) => Err(LlmConnectorError::ConfigError(
+                    "Fixture holds a streamed response, but the request is not streaming".to_string(),
+                )),
+            },
+            Some(Fixtures::Record(dir)) => {
+                let result = self.llm_client.chat(request).await;
+                let exchange = match &result {
+                    Ok(response) => Exchange::Response(response.clone()),
+                    Err(e) => Exchange::Error(e.into()),
+                };
+                save(dir, provider, request, exchange);
+                result
+            }
+        }
+    }
+
+    /// `llm_client.chat_stream`, recorded or replayed according to `--record` / `--replay`
+    pub(super) async fn upstream_chat_stream(&self, request: &ChatRequest) -> Result<ChatStream, LlmConnectorError> {
+        let provider = self.backend.provider_name();
+        match &self.fixtures {
+            None => self.llm_client.chat_stream(request).await,
+            Some(Fixtures::Replay(dir)) => match load(dir, provider, request)? {
+                Exchange::Stream(chunks) => Ok(replay_stream(chunks)),
+                Exchange::Error(error) => Err(error.into()),
+                Exchange::Response(_) => Err(LlmConnectorError::ConfigError(
+                    "Fixture holds a complete response, but the request is streaming".to_string(),
+                )),
+            },
+            Some(Fixtures::Record(dir)) => {
+                let started = Instant::now();
+                let stream = match self.llm_client.chat_stream(request).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        save(dir, provider, request, Exchange::Error((&e).into()));
+                        return Err(e);
+                    }
+                };
+                let mut recorder = StreamRecorder {
+                    dir: dir.clone(),
+                    provider,
+                    request: request.clone(),
+                    last: started,
+                    chunks: Vec::new(),
+                };
+                Ok(Box::pin(stream.map(move |item| {
+                    recorder.push(&item);
+                    item
+                })))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalizer::ChatOptions;
+    use crate::settings::LlmBackendSettings;
+    use axum::{routing::post, Router};
+    use llm_connector::types::Message;
+    use llm_connector::StreamFormat;
+
+    const SSE_BODY: &str = concat!(
+        "data: {\"id\":\"x\",\"object\":\"chat.completion.chunk\",\"created\":0,\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hello\"},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"x\",\"object\":\"chat.completion.chunk\",\"created\":0,\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\" world\"},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"x\",\"object\":\"chat.completion.chunk\",\"created\":0,\"model\":\"gpt-4\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        "data: [DONE]\n\n",
+    );
+
+    fn client(base_url: String, fixtures: Fixtures) -> Client {
+        let mut client = Client::new(&LlmBackendSettings::OpenAI {
+            api_key: "test".to_string(),
+            base_url: Some(base_url),
+            model: "gpt-4".to_string(),
+        })
+        .unwrap();
+        client.fixtures = Some(fixtures);
+        client
+    }
+
+    /// Codex (OpenAI SSE) and Zed (Ollama NDJSON) output of one request
+    async fn convert(client: &Client) -> (String, String) {
+        let messages = vec![Message::user("hi")];
+        let options = ChatOptions::default();
+        let openai = client
+            .chat_stream_openai("gpt-4", messages.clone(), None, &options, StreamFormat::SSE)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        let ollama = client
+            .chat_stream_with_format_and_tools("gpt-4", messages, None, &options, StreamFormat::NDJSON)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        (openai, ollama)
+    }
+
+    /// `created_at` / `created` change between runs
+    fn without_timestamps(output: &str) -> String {
+        output
+            .lines()
+            .map(|line| {
+                let json = line.strip_prefix("data: ").unwrap_or(line);
+                match serde_json::from_str::<serde_json::Value>(json) {
+                    Ok(mut value) if value.is_object() => {
+                        value.as_object_mut().unwrap().remove("created");
+                        value.as_object_mut().unwrap().remove("created_at");
+                        value.to_string()
+                    }
+                    _ => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay_stream() {
+        let upstream = Router::new().fallback(post(|| async { ([("content-type", "text/event-stream")], SSE_BODY) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, upstream).await.unwrap() });
+
+        let dir = tempfile::tempdir().unwrap();
+        let recording = client(format!("http://{}", addr), Fixtures::Record(dir.path().to_path_buf()));
+        let (recorded_openai, recorded_ollama) = convert(&recording).await;
+        // 两个请求的 ChatRequest 相同，共用一个 fixture
+        let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+
+        // 回放时上游地址不可用，也不需要 API key
+        let replaying = client("http://127.0.0.1:9".to_string(), Fixtures::Replay(dir.path().to_path_buf()));
+        let (replayed_openai, replayed_ollama) = convert(&replaying).await;
+        assert!(replayed_openai.contains("Hello") && replayed_openai.ends_with("data: [DONE]\n\n"));
+        assert!(replayed_ollama.contains("\"done\":true"));
+        assert_eq!(without_timestamps(&replayed_openai), without_timestamps(&recorded_openai));
+        assert_eq!(without_timestamps(&replayed_ollama), without_timestamps(&recorded_ollama));
+
+        // 没有录制过的请求
+        let error = replaying
+            .chat_stream_openai("gpt-4", vec![Message::user("other")], None, &ChatOptions::default(), StreamFormat::SSE)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("No recorded fixture"), "{}", error);
+    }
+}
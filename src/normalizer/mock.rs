@@ -0,0 +1,409 @@
+//! Offline `mock` backend
+//!
+//! Answers chat requests locally, with the configured reply or by echoing
+//! the last user message, so every frontend and client adapter can be
+//! exercised without network access. Directives in the last user message
+//! change a single request:
+//!
+//! - `[mock:tool]` / `[mock:tool=NAME]`: call the first (or the named) tool
+//! - `[mock:error=STATUS]`: fail like an upstream returning STATUS (429, 500, ...)
+//! - `[mock:disconnect=N]`: break the stream after N chunks
+//!
+//! Like Anthropic, the mock cuts the reply at the first of the request's
+//! stop sequences and reports `stop_sequence` as the finish reason.
+
+use crate::settings::MockSettings;
+use crate::tokenizer::counter_for_model;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use llm_connector::error::LlmConnectorError;
+use llm_connector::types::{
+    ChatRequest, ChatResponse, ChatStream, Choice, Delta, FunctionCall, Message, MessageBlock, Role,
+    StreamingChoice, StreamingResponse, Tool, ToolCall, Usage,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::any::Any;
+use std::time::Duration;
+
+const DEFAULT_CHUNK_SIZE: usize = 8;
+
+static DIRECTIVE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[mock:(\w+)(?:=([^\]]*))?\]").expect("valid directive pattern"));
+
+/// What the mock answers to one request
+#[derive(Debug, Default)]
+struct Script {
+    text: String,
+    tool_call: Option<ToolCall>,
+    error_status: Option<u16>,
+    disconnect_after: Option<usize>,
+    hit_stop_sequence: bool,
+}
+
+impl Script {
+    fn finish_reason(&self) -> &'static str {
+        if self.tool_call.is_some() {
+            "tool_calls"
+        } else if self.hit_stop_sequence {
+            "stop_sequence"
+        } else {
+            "stop"
+        }
+    }
+}
+
+/// llm-connector provider behind `LlmBackendSettings::Mock`
+pub struct MockProvider {
+    settings: MockSettings,
+}
+
+impl MockProvider {
+    pub fn new(settings: MockSettings) -> Self {
+        Self { settings }
+    }
+
+    fn script(&self, request: &ChatRequest) -> Script {
+        let prompt = request
+            .messages
+            .iter()
+            .rev()
+            .find(|message| message.role == Role::User)
+            .map(Message::content_as_text)
+            .unwrap_or_default();
+
+        let mut script = Script {
+            error_status: self.settings.error_status,
+            disconnect_after: self.settings.disconnect_after,
+            ..Default::default()
+        };
+        for directive in DIRECTIVE.captures_iter(&prompt) {
+            let value = directive.get(2).map(|value| value.as_str().trim());
+            match &directive[1] {
+                "tool" => script.tool_call = tool_call(request.tools.as_deref().unwrap_or_default(), value),
+                "error" => script.error_status = value.and_then(|value| value.parse().ok()).or(Some(500)),
+                "disconnect" => script.disconnect_after = Some(value.and_then(|value| value.parse().ok()).unwrap_or(0)),
+                other => tracing::warn!("⚠️ Unknown mock directive: {}", other),
+            }
+        }
+
+        // 调用工具时不带文本，和真实模型一致
+        if script.tool_call.is_none() {
+            script.text = match &self.settings.response {
+                Some(response) => response.clone(),
+                None => DIRECTIVE.replace_all(&prompt, "").trim().to_string(),
+            };
+            let stops = request.stop.iter().flatten().filter(|stop| !stop.is_empty());
+            if let Some(cut) = stops.filter_map(|stop| script.text.find(stop.as_str())).min() {
+                script.text.truncate(cut);
+                script.hit_stop_sequence = true;
+            }
+        }
+        script
+    }
+
+    fn usage(request: &ChatRequest, script: &Script) -> Usage {
+        let counter = counter_for_model(&request.model);
+        let prompt_tokens = counter.count_request(&request.messages, request.tools.as_deref());
+        let completion_tokens = counter.count_text(&script.text)
+            + script
+                .tool_call
+                .as_ref()
+                .map_or(0, |call| counter.count_text(&call.function.name) + counter.count_text(&call.function.arguments));
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            prompt_cache_hit_tokens: None,
+            prompt_cache_miss_tokens: None,
+            prompt_tokens_details: None,
+            completion_tokens_details: None,
+        }
+    }
+
+    async fn wait(&self) {
+        if self.settings.latency_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(self.settings.latency_ms)).await;
+        }
+    }
+}
+
+/// Call of the tool named `name`, or of the first tool, with placeholder arguments
+fn tool_call(tools: &[Tool], name: Option<&str>) -> Option<ToolCall> {
+    let tool = match name.filter(|name| !name.is_empty()) {
+        Some(name) => tools.iter().find(|tool| tool.function.name == name),
+        None => tools.first(),
+    };
+    let Some(tool) = tool else {
+        tracing::warn!("⚠️ Mock tool call requested, but the request has no matching tool");
+        return None;
+    };
+    Some(ToolCall {
+        id: "call_mock_0".to_string(),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: tool.function.name.clone(),
+            arguments: placeholder_arguments(&tool.function.parameters).to_string(),
+        },
+        index: Some(0),
+    })
+}
+
+/// A value for every required parameter, by its JSON schema type
+fn placeholder_arguments(schema: &Value) -> Value {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let required = schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str);
+    let mut arguments = Map::new();
+    for name in required {
+        let kind = properties
+            .and_then(|properties| properties.get(name))
+            .and_then(|property| property.get("type"))
+            .and_then(Value::as_str);
+        let value = match kind {
+            Some("integer") | Some("number") => Value::from(1),
+            Some("boolean") => Value::Bool(true),
+            Some("array") => Value::Array(Vec::new()),
+            Some("object") => Value::Object(Map::new()),
+            _ => Value::String("mock".to_string()),
+        };
+        arguments.insert(name.to_string(), value);
+    }
+    Value::Object(arguments)
+}
+
+fn upstream_error(status: u16) -> LlmConnectorError {
+    LlmConnectorError::from_status_code(status, format!("Mock upstream returned {}", status))
+}
+
+fn chunk(model: &str, delta: Delta, finish_reason: Option<&str>, usage: Option<Usage>) -> StreamingResponse {
+    StreamingResponse {
+        id: "chatcmpl-mock".to_string(),
+        created: chrono::Utc::now().timestamp() as u64,
+        model: model.to_string(),
+        content: delta.content.clone().unwrap_or_default(),
+        choices: vec![StreamingChoice {
+            index: 0,
+            delta,
+            finish_reason: finish_reason.map(str::to_string),
+            logprobs: None,
+        }],
+        usage,
+        ..Default::default()
+    }
+}
+
+#[async_trait]
+impl llm_connector::Provider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmConnectorError> {
+        let script = self.script(request);
+        self.wait().await;
+        if let Some(status) = script.error_status {
+            return Err(upstream_error(status));
+        }
+
+        let usage = Self::usage(request, &script);
+        let content = if script.text.is_empty() { Vec::new() } else { vec![MessageBlock::text(script.text.clone())] };
+        Ok(ChatResponse {
+            id: "chatcmpl-mock".to_string(),
+            object: "chat.completion".to_string(),
+            created: chrono::Utc::now().timestamp() as u64,
+            model: request.model.clone(),
+            choices: vec![Choice {
+                index: 0,
+                message: Message {
+                    role: Role::Assistant,
+                    content,
+                    tool_calls: script.tool_call.clone().map(|call| vec![call]),
+                    ..Default::default()
+                },
+                finish_reason: Some(script.finish_reason().to_string()),
+                logprobs: None,
+            }],
+            content: script.text,
+            usage: Some(usage),
+            ..Default::default()
+        })
+    }
+
+    async fn chat_stream(&self, request: &ChatRequest) -> Result<ChatStream, LlmConnectorError> {
+        let script = self.script(request);
+        if let Some(status) = script.error_status {
+            self.wait().await;
+            return Err(upstream_error(status));
+        }
+
+        let chunk_size = self.settings.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+        let characters: Vec<char> = script.text.chars().collect();
+        let mut items: Vec<Result<StreamingResponse, LlmConnectorError>> = characters
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(index, piece)| {
+                let delta = Delta {
+                    role: (index == 0).then_some(Role::Assistant),
+                    content: Some(piece.iter().collect()),
+                    ..Default::default()
+                };
+                Ok(chunk(&request.model, delta, None, None))
+            })
+            .collect();
+        if let Some(call) = &script.tool_call {
+            let delta = Delta { tool_calls: Some(vec![call.clone()]), ..Default::default() };
+            items.push(Ok(chunk(&request.model, delta, None, None)));
+        }
+        let usage = Self::usage(request, &script);
+        items.push(Ok(chunk(&request.model, Delta::default(), Some(script.finish_reason()), Some(usage))));
+
+        if let Some(after) = script.disconnect_after.filter(|after| *after < items.len()) {
+            items.truncate(after);
+            items.push(Err(LlmConnectorError::StreamingError(format!(
+                "Mock stream disconnected after {} chunks",
+                after
+            ))));
+        }
+
+        let latency = Duration::from_millis(self.settings.latency_ms);
+        Ok(Box::pin(futures_util::stream::iter(items).then(move |item| async move {
+            tokio::time::sleep(latency).await;
+            item
+        })))
+    }
+
+    async fn models(&self) -> Result<Vec<String>, LlmConnectorError> {
+        Ok(Vec::new())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::AppState;
+    use crate::service::Service;
+    use crate::settings::{LlmBackendSettings, Settings};
+    use axum::routing::post;
+    use axum::Router;
+    use llm_connector::types::Function;
+    use llm_connector::Provider as _;
+    use serde_json::json;
+
+    fn request(prompt: &str) -> ChatRequest {
+        ChatRequest {
+            model: "mock".to_string(),
+            messages: vec![Message::user(prompt)],
+            tools: Some(vec![Tool {
+                tool_type: "function".to_string(),
+                function: Function {
+                    name: "get_weather".to_string(),
+                    description: None,
+                    parameters: json!({
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}, "days": {"type": "integer"}},
+                        "required": ["city", "days"]
+                    }),
+                },
+            }]),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_provider_scripts() {
+        let mock = MockProvider::new(MockSettings { chunk_size: Some(4), ..Default::default() });
+
+        let response = mock.chat(&request("hello mock")).await.unwrap();
+        assert_eq!(response.content, "hello mock");
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+        assert!(response.usage.unwrap().prompt_tokens > 0);
+
+        let response = mock.chat(&request("weather? [mock:tool]")).await.unwrap();
+        let call = &response.choices[0].message.tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.function.name, "get_weather");
+        assert_eq!(call.function.arguments, r#"{"city":"mock","days":1}"#);
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("tool_calls"));
+
+        let error = mock.chat(&request("[mock:error=429]")).await.unwrap_err();
+        assert_eq!(error.status_code(), 429);
+
+        // "hello mock" 按 4 个字符切成 3 块，再加结束块
+        let chunks: Vec<_> = mock.chat_stream(&request("hello mock")).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0].as_ref().unwrap().content, "hell");
+        assert!(chunks[3].as_ref().unwrap().usage.is_some());
+
+        let chunks: Vec<_> = mock.chat_stream(&request("hello mock [mock:disconnect=2]")).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 3);
+        assert!(matches!(chunks[2], Err(LlmConnectorError::StreamingError(_))));
+
+        let settings = LlmBackendSettings::from_provider_config("mock", &json!({"model": "mock", "response": "fixed"})).unwrap();
+        assert!(matches!(settings, LlmBackendSettings::Mock { mock, .. } if mock.response.as_deref() == Some("fixed")));
+    }
+
+    #[tokio::test]
+    async fn test_frontends_against_mock_backend() {
+        let backend = LlmBackendSettings::Mock { model: "mock".to_string(), mock: MockSettings::default() };
+        let config = Settings { llm_backend: backend.clone(), ..Default::default() };
+        let state = AppState::new(Service::new(&backend).unwrap(), config.clone());
+        let app = crate::api::ollama::build_ollama_routes(state.clone(), config.apis.ollama.as_ref().unwrap())
+            .merge(
+                Router::new()
+                    .route("/v1/chat/completions", post(crate::api::openai::chat))
+                    .route("/anthropic/v1/messages", post(crate::api::anthropic::messages))
+                    .with_state(state),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let http = reqwest::Client::new();
+        let post = |path: &str, body: serde_json::Value| http.post(format!("{}{}", base, path)).json(&body).send();
+        let tools = json!([{"type": "function", "function": {"name": "get_weather", "parameters": {"type": "object"}}}]);
+
+        // Codex / OpenAI
+        let response: serde_json::Value = post("/v1/chat/completions", json!({"model": "mock", "messages": [{"role": "user", "content": "hi codex"}]}))
+            .await.unwrap().json().await.unwrap();
+        assert_eq!(response["choices"][0]["message"]["content"], "hi codex");
+        let stream = post("/v1/chat/completions", json!({"model": "mock", "stream": true, "tools": tools, "messages": [{"role": "user", "content": "[mock:tool]"}]}))
+            .await.unwrap().text().await.unwrap();
+        assert!(stream.contains("call_mock_0") && stream.ends_with("data: [DONE]\n\n"), "{}", stream);
+        let failed = post("/v1/chat/completions", json!({"model": "mock", "stream": true, "messages": [{"role": "user", "content": "[mock:error=429]"}]}))
+            .await.unwrap();
+        assert_eq!(failed.status(), 429);
+        assert!(failed.text().await.unwrap().contains("\"error\""));
+        let broken = post("/v1/chat/completions", json!({"model": "mock", "stream": true, "messages": [{"role": "user", "content": "hello codex [mock:disconnect=1]"}]}))
+            .await.unwrap().text().await.unwrap();
+        assert!(broken.contains("\"error\"") && !broken.contains("[DONE]") && !broken.contains("\"finish_reason\":\"stop\""), "{}", broken);
+
+        // Zed / Ollama
+        let stream = post("/ollama/api/chat", json!({"model": "mock", "stream": true, "messages": [{"role": "user", "content": "hi zed"}]}))
+            .await.unwrap().text().await.unwrap();
+        assert!(stream.contains("hi zed") && stream.contains("\"done\":true"), "{}", stream);
+        let limited = post("/ollama/api/chat", json!({"model": "mock", "messages": [{"role": "user", "content": "[mock:error=429]"}]}))
+            .await.unwrap();
+        assert_eq!(limited.status(), 429);
+
+        // Anthropic
+        let stream = post("/anthropic/v1/messages", json!({"model": "mock", "max_tokens": 64, "stream": true, "messages": [{"role": "user", "content": "hi claude"}]}))
+            .await.unwrap().text().await.unwrap();
+        assert!(stream.contains("content_block_delta") && stream.contains("message_stop"), "{}", stream);
+        let stream = post("/anthropic/v1/messages", json!({"model": "mock", "max_tokens": 64, "stream": true, "stop_sequences": ["END"], "messages": [{"role": "user", "content": "hi END claude"}]}))
+            .await.unwrap().text().await.unwrap();
+        assert!(stream.contains(r#""stop_reason":"stop_sequence","stop_sequence":"END""#), "{}", stream);
+        assert!(!stream.contains("claude"), "{}", stream);
+        let broken = post("/anthropic/v1/messages", json!({"model": "mock", "max_tokens": 64, "stream": true, "messages": [{"role": "user", "content": "hello claude [mock:disconnect=1]"}]}))
+            .await.unwrap().text().await.unwrap();
+        assert!(broken.contains("event: error") && !broken.contains("message_stop"), "{}", broken);
+        let response: serde_json::Value = post("/anthropic/v1/messages", json!({"model": "mock", "max_tokens": 64, "stop_sequences": ["END"], "messages": [{"role": "user", "content": "hi END claude"}]}))
+            .await.unwrap().json().await.unwrap();
+        assert_eq!((response["stop_reason"].as_str(), response["stop_sequence"].as_str()), (Some("stop_sequence"), Some("END")));
+        let failed = post("/anthropic/v1/messages", json!({"model": "mock", "max_tokens": 64, "messages": [{"role": "user", "content": "[mock:error=500]"}]}))
+            .await.unwrap();
+        assert_eq!(failed.status(), 500);
+    }
+}
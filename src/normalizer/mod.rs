@@ -1,60 +1,80 @@
+mod caching;
 mod chat;
+mod embeddings;
+mod errors;
+mod fixtures;
+mod mock;
 mod models;
+mod reasoning;
 mod stream;
+mod structured;
+mod tool_calls;
 mod types;
+mod vision;
+mod wire;
 mod model_resolver;
 
-pub use types::{Model, Response};
+pub use errors::is_failover_error;
+pub use fixtures::{init as init_fixtures, Fixtures};
+pub use mock::MockProvider;
+pub use structured::OutputFormat;
+pub use vision::image_block_from_url;
+pub use types::{normalize_finish_reason, ChatOptions, EmbeddingRequest, Embeddings, Model, Response};
 
 use crate::models::ModelsConfig;
 use crate::settings::LlmBackendSettings;
 use anyhow::Result;
-use llm_connector::LlmClient;
+use llm_connector::{providers, LlmClient};
+use std::sync::Arc;
 
 /// Unified LLM client that wraps llm-connector for all providers
 pub struct Client {
     backend: LlmBackendSettings,
     llm_client: LlmClient,
     models_config: ModelsConfig,
+    /// Plain HTTP client for endpoints llm-connector does not cover (embeddings)
+    http: reqwest::Client,
+    /// Record or replay upstream chat traffic (`--record` / `--replay`)
+    fixtures: Option<Fixtures>,
+    /// Cache for deterministic requests (`--cache`)
+    cache: Option<Arc<crate::cache::ResponseCache>>,
 }
 
 impl Client {
     /// Create a new client with the specified backend configuration
     pub fn new(config: &LlmBackendSettings) -> Result<Self> {
+        // stop / user / seed / response_format 由 wire 补进请求体
+        let dialect = wire::Dialect::of(config);
         let llm_client = match config {
             LlmBackendSettings::OpenAI {
                 api_key, base_url, ..
             } => {
-                // Use 30 second timeout for better reliability
                 if let Some(base_url) = base_url {
-                    LlmClient::openai_compatible(api_key, base_url, "openai")?
+                    wire::client(providers::openai_compatible(api_key, base_url, "openai")?, dialect)
                 } else {
-                    LlmClient::openai(api_key)?
+                    wire::client(providers::openai(api_key)?, dialect)
                 }
             }
-            LlmBackendSettings::Anthropic { api_key, .. } => {
-                // Use 30 second timeout for better reliability
-                LlmClient::anthropic(api_key)?
-            }
-            LlmBackendSettings::Aliyun { api_key, .. } => LlmClient::aliyun(api_key)?,
+            LlmBackendSettings::Anthropic { api_key, .. } => wire::client(providers::anthropic(api_key)?, dialect),
+            LlmBackendSettings::Aliyun { api_key, .. } => wire::aliyun_client(api_key)?,
             LlmBackendSettings::Zhipu { api_key, .. } => {
                 // Use Zhipu OpenAI compatible mode for better reliability
-                LlmClient::zhipu_openai_compatible(api_key)?
+                wire::client(providers::zhipu_openai_compatible(api_key)?, dialect)
             }
-            LlmBackendSettings::Volcengine { api_key, .. } => LlmClient::volcengine(api_key)?,
-            LlmBackendSettings::Tencent { api_key, .. } => LlmClient::tencent(api_key)?,
+            LlmBackendSettings::Volcengine { api_key, .. } => wire::client(providers::volcengine(api_key)?, dialect),
+            LlmBackendSettings::Tencent { api_key, .. } => wire::client(providers::tencent(api_key)?, dialect),
             LlmBackendSettings::Longcat { api_key, .. } => {
                 // Longcat uses OpenAI compatible API
-                LlmClient::openai_compatible(api_key, "https://api.longcat.chat/v1", "longcat")?
+                wire::client(providers::openai_compatible(api_key, "https://api.longcat.chat/v1", "longcat")?, dialect)
             }
             LlmBackendSettings::Moonshot { api_key, .. } => {
                 // Moonshot uses OpenAI compatible API
-                LlmClient::openai_compatible(api_key, "https://api.moonshot.cn/v1", "moonshot")?
+                wire::client(providers::openai_compatible(api_key, "https://api.moonshot.cn/v1", "moonshot")?, dialect)
             }
             LlmBackendSettings::Minimax { api_key, .. } => {
                 // Minimax uses OpenAI compatible API
                 // Use the global endpoint (api.minimax.io) instead of mainland (api.minimaxi.com)
-                LlmClient::openai_compatible(api_key, "https://api.minimax.io/v1", "minimax")?
+                wire::client(providers::openai_compatible(api_key, "https://api.minimax.io/v1", "minimax")?, dialect)
             }
             LlmBackendSettings::Ollama { base_url, .. } => {
                 if base_url.is_some() {
@@ -65,15 +85,32 @@ impl Client {
                     LlmClient::ollama()?
                 }
             }
+            LlmBackendSettings::Mock { mock, .. } => {
+                // Answers locally, no network access
+                LlmClient::from_provider(Arc::new(MockProvider::new(mock.clone())))
+            }
         };
 
         // Load models configuration
         let models_config = ModelsConfig::load_with_fallback();
 
+        // Same defaults as llm-connector: 60s timeout, no system proxy
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(60))
+            .no_proxy()
+            .build()?;
+
         Ok(Self {
             backend: config.clone(),
             llm_client,
             models_config,
+            http,
+            fixtures: fixtures::configured(),
+            cache: crate::cache::configured(),
         })
     }
+
+    fn dialect(&self) -> wire::Dialect {
+        wire::Dialect::of(&self.backend)
+    }
 }
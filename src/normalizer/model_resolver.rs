@@ -60,20 +60,23 @@ impl Client {
     ///
     /// `requested` 是协议层传入的 model（逻辑名或 ep-*），
     /// `default_model` 是后端配置中的默认模型（通常来自 CLI --model）。
+    #[tracing::instrument(
+        skip_all,
+        fields(
+            gen_ai.system = self.backend.provider_name(),
+            gen_ai.request.model = requested,
+            llm_link.resolved_model = tracing::field::Empty,
+        )
+    )]
     pub fn resolve_model(&self, requested: &str, default_model: &str) -> String {
+        let resolved = self.resolve_backend_model(requested, default_model);
+        tracing::Span::current().record("llm_link.resolved_model", resolved.as_str());
+        resolved
+    }
+
+    fn resolve_backend_model(&self, requested: &str, default_model: &str) -> String {
         // Step 1: 本地 overrides（最高优先级）
-        let provider_name = match &self.backend {
-            LlmBackendSettings::OpenAI { .. } => "openai",
-            LlmBackendSettings::Anthropic { .. } => "anthropic",
-            LlmBackendSettings::Ollama { .. } => "ollama",
-            LlmBackendSettings::Zhipu { .. } => "zhipu",
-            LlmBackendSettings::Aliyun { .. } => "aliyun",
-            LlmBackendSettings::Volcengine { .. } => "volcengine",
-            LlmBackendSettings::Tencent { .. } => "tencent",
-            LlmBackendSettings::Longcat { .. } => "longcat",
-            LlmBackendSettings::Moonshot { .. } => "moonshot",
-            LlmBackendSettings::Minimax { .. } => "minimax",
-        };
+        let provider_name = self.backend.provider_name();
 
         if let Some(overridden) = MODEL_OVERRIDES.resolve_override(provider_name, requested) {
             // 命中本地覆盖，只记录逻辑层信息，避免泄露具体 endpoint
@@ -1,24 +1,17 @@
 use super::Client;
+use crate::models::ModelInfo;
 use crate::normalizer::types::Model;
 use crate::settings::LlmBackendSettings;
+use crate::tokenizer::counter_for_model;
 use anyhow::Result;
+use llm_connector::error::LlmConnectorError;
+use llm_connector::types::ChatRequest;
 use llm_connector::Provider;
 
 impl Client {
     /// List available models
     pub async fn list_models(&self) -> Result<Vec<Model>> {
-        let provider_name = match &self.backend {
-            LlmBackendSettings::OpenAI { .. } => "openai",
-            LlmBackendSettings::Anthropic { .. } => "anthropic",
-            LlmBackendSettings::Zhipu { .. } => "zhipu",
-            LlmBackendSettings::Ollama { .. } => "ollama",
-            LlmBackendSettings::Aliyun { .. } => "aliyun",
-            LlmBackendSettings::Volcengine { .. } => "volcengine",
-            LlmBackendSettings::Tencent { .. } => "tencent",
-            LlmBackendSettings::Longcat { .. } => "longcat",
-            LlmBackendSettings::Moonshot { .. } => "moonshot",
-            LlmBackendSettings::Minimax { .. } => "minimax",
-        };
+        let provider_name = self.backend.provider_name();
 
         // Special handling for Ollama - get actual installed models
         if provider_name == "ollama" {
@@ -62,6 +55,7 @@ impl Client {
                 LlmBackendSettings::Longcat { model, .. } => model.clone(),
                 LlmBackendSettings::Moonshot { model, .. } => model.clone(),
                 LlmBackendSettings::Minimax { model, .. } => model.clone(),
+                LlmBackendSettings::Mock { model, .. } => model.clone(),
             };
 
             Ok(vec![Model { id: fallback_model }])
@@ -69,5 +63,77 @@ impl Client {
             Ok(models)
         }
     }
+
+    /// Whether this backend is known to serve `model`
+    ///
+    /// Checks the configured model and the provider's list in models.yaml,
+    /// without calling the upstream API.
+    pub fn knows_model(&self, model: &str) -> bool {
+        self.backend.get_model() == model
+            || self
+                .models_config
+                .get_models_for_provider(self.backend.provider_name())
+                .iter()
+                .any(|info| info.id == model)
+    }
+
+    /// Model metadata for this backend's provider from models.yaml
+    pub fn model_infos(&self) -> Vec<ModelInfo> {
+        self.models_config.get_models_for_provider(self.backend.provider_name())
+    }
+
+    /// Reject a request that does not fit the model's context window
+    ///
+    /// Fails with `InvalidRequest` (a 400 for the client) when the prompt
+    /// plus `max_tokens` is over the `context_length` in models.yaml. Models
+    /// without one are not checked.
+    pub(crate) fn check_context_length(&self, request: &ChatRequest) -> Result<()> {
+        let Some(context_length) = self
+            .model_infos()
+            .into_iter()
+            .find(|info| info.id == request.model)
+            .and_then(|info| info.context_length)
+        else {
+            return Ok(());
+        };
+        let prompt_tokens = counter_for_model(&request.model)
+            .count_request(&request.messages, request.tools.as_deref());
+        let requested = prompt_tokens + request.max_tokens.unwrap_or(0);
+        if requested > context_length {
+            return Err(LlmConnectorError::InvalidRequest(format!(
+                "Request for '{}' needs {} tokens ({} prompt + {} max_tokens), but the context length is {}",
+                request.model, requested, prompt_tokens, request.max_tokens.unwrap_or(0), context_length
+            ))
+            .into());
+        }
+        Ok(())
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::error_status;
+    use axum::http::StatusCode;
+    use llm_connector::types::Message;
+
+    #[test]
+    fn test_context_length_rejected() {
+        let backend = LlmBackendSettings::Zhipu { api_key: "test".to_string(), base_url: None, model: "glm-4.5-flash".to_string() };
+        let client = Client::new(&backend).unwrap();
+        let request = |model: &str, max_tokens: u32| ChatRequest {
+            model: model.to_string(),
+            messages: vec![Message::user("hello")],
+            max_tokens: Some(max_tokens),
+            ..Default::default()
+        };
+
+        assert!(client.check_context_length(&request("glm-4.5-flash", 1000)).is_ok());
+        // 128000 上下文放不下 128000 的 max_tokens 加上提示
+        let err = client.check_context_length(&request("glm-4.5-flash", 128_000)).unwrap_err();
+        assert_eq!(error_status(&err), StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("context length is 128000"));
+        // models.yaml 中没有上下文长度的模型不检查
+        assert!(client.check_context_length(&request("glm-4", 1_000_000)).is_ok());
+    }
+}
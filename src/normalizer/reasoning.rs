@@ -0,0 +1,371 @@
+use super::types::Response;
+use crate::settings::ReasoningMode;
+use llm_connector::types::StreamingResponse;
+
+/// Filter out <think> tags from content
+/// GLM-4.6 and similar models may include reasoning process in <think></think> tags
+pub(crate) fn filter_think_tags(content: &str) -> String {
+    // Same rules as the streaming parser; whitespace is kept as-is
+    split_think_tags(content).1
+}
+
+/// Split content into (reasoning, answer) at `<think>...</think>` blocks
+fn split_think_tags(content: &str) -> (String, String) {
+    let mut parser = ThinkTagParser::default();
+    let (mut reasoning, mut answer) = parser.push(content);
+    let (rest_reasoning, rest_answer) = parser.finish();
+    reasoning.push_str(&rest_reasoning);
+    answer.push_str(&rest_answer);
+    (reasoning, answer)
+}
+
+const THINK_OPEN: &str = "<think>";
+const THINK_CLOSE: &str = "</think>";
+
+/// Incremental `<think>` tag parser for streamed content
+///
+/// Chunks can end in the middle of a tag (`<thi` + `nk>`), so a trailing
+/// partial tag is held back until the next chunk shows what it is. Follows
+/// the rules of `filter_think_tags`: an unclosed block runs to the end of the
+/// stream, nested and stray tags are dropped.
+#[derive(Debug, Default)]
+pub(crate) struct ThinkTagParser {
+    in_think: bool,
+    /// Possible start of a tag, carried over from the previous chunk
+    pending: String,
+}
+
+impl ThinkTagParser {
+    /// Feed one chunk, returning the (reasoning, answer) text it completes
+    pub(crate) fn push(&mut self, text: &str) -> (String, String) {
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.push_str(text);
+
+        let mut reasoning = String::new();
+        let mut answer = String::new();
+        let mut rest = buffer.as_str();
+
+        loop {
+            let open = rest.find(THINK_OPEN);
+            let close = rest.find(THINK_CLOSE);
+            let (start, tag) = match (open, close) {
+                (Some(open), Some(close)) if close < open => (close, THINK_CLOSE),
+                (Some(open), _) => (open, THINK_OPEN),
+                (None, Some(close)) => (close, THINK_CLOSE),
+                (None, None) => break,
+            };
+            self.emit(&rest[..start], &mut reasoning, &mut answer);
+            // 嵌套的 <think> 与多余的 </think> 直接丢弃
+            self.in_think = tag == THINK_OPEN;
+            rest = &rest[start + tag.len()..];
+        }
+
+        let keep = partial_tag_len(rest);
+        self.emit(&rest[..rest.len() - keep], &mut reasoning, &mut answer);
+        self.pending = rest[rest.len() - keep..].to_string();
+
+        (reasoning, answer)
+    }
+
+    /// Flush text held back at the end of the stream
+    pub(crate) fn finish(&mut self) -> (String, String) {
+        let pending = std::mem::take(&mut self.pending);
+        let mut reasoning = String::new();
+        let mut answer = String::new();
+        self.emit(&pending, &mut reasoning, &mut answer);
+        (reasoning, answer)
+    }
+
+    fn emit(&self, text: &str, reasoning: &mut String, answer: &mut String) {
+        if self.in_think {
+            reasoning.push_str(text);
+        } else {
+            answer.push_str(text);
+        }
+    }
+}
+
+/// Length of the longest suffix of `text` that could start a think tag
+fn partial_tag_len(text: &str) -> usize {
+    let longest = THINK_CLOSE.len() - 1;
+    (1..=longest.min(text.len()))
+        .rev()
+        .find(|&len| {
+            let start = text.len() - len;
+            text.is_char_boundary(start)
+                && (THINK_OPEN.starts_with(&text[start..]) || THINK_CLOSE.starts_with(&text[start..]))
+        })
+        .unwrap_or(0)
+}
+
+/// Move inline `<think>` blocks of a response into its reasoning
+pub(crate) fn take_inline_reasoning(response: &mut Response) {
+    let (inline, answer) = split_think_tags(&response.content);
+    if !inline.is_empty() {
+        response.reasoning = Some(response.reasoning.take().unwrap_or_default() + &inline);
+        response.content = answer;
+    }
+}
+
+/// Answer text and reasoning text of one stream chunk
+///
+/// llm-connector fills the `content` convenience field from the reasoning
+/// fields when a delta has no content, so the delta is read directly.
+pub(crate) fn chunk_parts(chunk: &StreamingResponse) -> (&str, Option<&str>) {
+    let Some(choice) = chunk.choices.first() else {
+        return (chunk.content.as_str(), chunk.reasoning_content.as_deref());
+    };
+    let reasoning = choice
+        .delta
+        .reasoning_any()
+        .or(chunk.reasoning_content.as_deref())
+        .filter(|reasoning| !reasoning.is_empty());
+    let content = match choice.delta.content.as_deref() {
+        Some(content) => content,
+        None if reasoning.is_none() => chunk.content.as_str(),
+        None => "",
+    };
+    (content, reasoning)
+}
+
+/// Apply the reasoning mode to a complete response
+pub(crate) fn apply_reasoning_mode(mode: ReasoningMode, response: &mut Response) {
+    let reasoning = response.reasoning.take().filter(|reasoning| !reasoning.is_empty());
+    match mode {
+        ReasoningMode::Strip => {
+            response.content = filter_think_tags(&response.content);
+            // 兼容旧行为：只有思考内容、没有回答时，把思考内容作为回答返回
+            if response.content.is_empty() && response.tool_calls.is_none() {
+                response.content = reasoning.unwrap_or_default();
+            }
+        }
+        ReasoningMode::Passthrough => {
+            if let Some(reasoning) = reasoning {
+                response.content = format!("<think>{}</think>{}", reasoning, response.content);
+            }
+        }
+        ReasoningMode::Native => {
+            response.reasoning = reasoning;
+            take_inline_reasoning(response);
+        }
+    }
+}
+
+/// Per-stream reasoning handling
+///
+/// Turns the content / reasoning of each delta into the (reasoning, answer)
+/// text to send for the stream's `ReasoningMode`. Inline `<think>` blocks are
+/// tracked across chunks by a `ThinkTagParser`.
+pub(crate) struct ReasoningFilter {
+    mode: ReasoningMode,
+    tags: ThinkTagParser,
+    /// Passthrough: a `<think>` tag has been sent and not closed yet
+    in_reasoning: bool,
+}
+
+impl ReasoningFilter {
+    pub(crate) fn new(mode: ReasoningMode) -> Self {
+        Self { mode, tags: ThinkTagParser::default(), in_reasoning: false }
+    }
+
+    pub(crate) fn process(&mut self, content: &str, reasoning: Option<&str>) -> (String, String) {
+        let reasoning = reasoning.unwrap_or_default();
+        match self.mode {
+            ReasoningMode::Strip => {
+                if !reasoning.is_empty() {
+                    tracing::debug!("🧠 Filtered reasoning_content ({} chars): {:?}",
+                                  reasoning.len(),
+                                  reasoning.chars().take(50).collect::<String>());
+                }
+                (String::new(), self.tags.push(content).1)
+            }
+            ReasoningMode::Passthrough => {
+                let mut answer = String::new();
+                if !reasoning.is_empty() {
+                    if !self.in_reasoning {
+                        answer.push_str(THINK_OPEN);
+                        self.in_reasoning = true;
+                    }
+                    answer.push_str(reasoning);
+                }
+                if !content.is_empty() {
+                    answer.push_str(&self.close());
+                    answer.push_str(content);
+                }
+                (String::new(), answer)
+            }
+            ReasoningMode::Native => {
+                let (inline, answer) = self.tags.push(content);
+                (format!("{}{}", reasoning, inline), answer)
+            }
+        }
+    }
+
+    /// (reasoning, answer) text still owed at the end of the stream
+    pub(crate) fn finish(&mut self) -> (String, String) {
+        match self.mode {
+            ReasoningMode::Strip => (String::new(), self.tags.finish().1),
+            ReasoningMode::Passthrough => (String::new(), self.close()),
+            ReasoningMode::Native => self.tags.finish(),
+        }
+    }
+
+    fn close(&mut self) -> String {
+        if std::mem::take(&mut self.in_reasoning) {
+            THINK_CLOSE.to_string()
+        } else {
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_think_tags() {
+        // Test simple think tag
+        assert_eq!(
+            filter_think_tags("<think>reasoning</think>actual content"),
+            "actual content"
+        );
+
+        // Test multiple think tags
+        assert_eq!(
+            filter_think_tags("<think>first</think>content<think>second</think>"),
+            "content"
+        );
+
+        // Test nested think tags
+        assert_eq!(
+            filter_think_tags("<think>outer<think>inner</think></think>text"),
+            "text"
+        );
+
+        // Test standalone closing tags
+        assert_eq!(
+            filter_think_tags("content</think></think>"),
+            "content"
+        );
+
+        // Test no think tags
+        assert_eq!(
+            filter_think_tags("normal content"),
+            "normal content"
+        );
+
+        // Test empty content
+        assert_eq!(
+            filter_think_tags("<think></think>"),
+            ""
+        );
+
+        // Test whitespace preservation (important for streaming!)
+        assert_eq!(
+            filter_think_tags("\n"),
+            "\n"
+        );
+
+        assert_eq!(
+            filter_think_tags("  "),
+            "  "
+        );
+
+        assert_eq!(
+            filter_think_tags("<think>test</think>\n"),
+            "\n"
+        );
+
+        // Test newlines in content
+        assert_eq!(
+            filter_think_tags("line1\nline2"),
+            "line1\nline2"
+        );
+    }
+
+    /// Random content built from tag fragments, text and multi-byte characters
+    fn random_content(rng: &mut fastrand::Rng) -> String {
+        const PIECES: &[&str] = &["<think>", "</think>", "a", "b", " ", "\n", "<", "/", ">", "思考"];
+        (0..rng.usize(0..24)).map(|_| PIECES[rng.usize(..PIECES.len())]).collect()
+    }
+
+    /// Split `content` at random char boundaries
+    fn random_chunks(rng: &mut fastrand::Rng, content: &str) -> Vec<String> {
+        let chars: Vec<char> = content.chars().collect();
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let end = (start + rng.usize(1..=4)).min(chars.len());
+            chunks.push(chars[start..end].iter().collect());
+            start = end;
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_think_tag_parser_any_chunking() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        for _ in 0..2000 {
+            let content = random_content(&mut rng);
+            let expected = split_think_tags(&content);
+
+            let mut parser = ThinkTagParser::default();
+            let (mut reasoning, mut answer) = (String::new(), String::new());
+            for chunk in random_chunks(&mut rng, &content) {
+                let (r, a) = parser.push(&chunk);
+                reasoning.push_str(&r);
+                answer.push_str(&a);
+            }
+            let (r, a) = parser.finish();
+            reasoning.push_str(&r);
+            answer.push_str(&a);
+
+            assert_eq!((reasoning, answer), expected, "content: {:?}", content);
+            assert!(!expected.1.contains("<think>") && !expected.1.contains("</think>"), "content: {:?}", content);
+        }
+    }
+
+    #[test]
+    fn test_think_tag_parser_split_tags() {
+        let content = "<think>plan the answer</think>The answer is 42";
+        // Every single split point, including inside both tags
+        for split in 1..content.len() {
+            let mut parser = ThinkTagParser::default();
+            let (r1, a1) = parser.push(&content[..split]);
+            let (r2, a2) = parser.push(&content[split..]);
+            let (r3, a3) = parser.finish();
+            assert_eq!(r1 + &r2 + &r3, "plan the answer", "split at {}", split);
+            assert_eq!(a1 + &a2 + &a3, "The answer is 42", "split at {}", split);
+        }
+
+        // A lone "<" that turns out not to be a tag is released with the next chunk
+        let mut parser = ThinkTagParser::default();
+        assert_eq!(parser.push("a <"), (String::new(), "a ".to_string()));
+        assert_eq!(parser.push(" b"), (String::new(), "< b".to_string()));
+    }
+
+    #[test]
+    fn test_reasoning_filter() {
+        let mut strip = ReasoningFilter::new(ReasoningMode::Strip);
+        assert_eq!(strip.process("<think>a</think>b", Some("r")), (String::new(), "b".to_string()));
+
+        let mut native = ReasoningFilter::new(ReasoningMode::Native);
+        assert_eq!(native.process("<think>a</think>b", Some("r")), ("ra".to_string(), "b".to_string()));
+
+        let mut passthrough = ReasoningFilter::new(ReasoningMode::Passthrough);
+        let answer: String = [
+            passthrough.process("", Some("step 1")).1,
+            passthrough.process("", Some(", step 2")).1,
+            passthrough.process("answer", None).1,
+            passthrough.finish().1,
+        ]
+        .concat();
+        assert_eq!(answer, "<think>step 1, step 2</think>answer");
+
+        // Reasoning only: the tag is closed when the stream ends
+        let mut passthrough = ReasoningFilter::new(ReasoningMode::Passthrough);
+        passthrough.process("", Some("thinking"));
+        assert_eq!(passthrough.finish().1, "</think>");
+    }
+}
@@ -1,106 +1,96 @@
+use super::errors::connector_error;
+use crate::tokenizer::{counter_for_model, TokenCounter};
+use super::types::{normalize_finish_reason, ChatOptions};
+use super::reasoning::{chunk_parts, ReasoningFilter};
+use super::structured::replay_stream;
+use super::tool_calls::ToolCallAccumulator;
 use super::Client;
-use anyhow::{anyhow, Result};
+use crate::telemetry;
+use anyhow::Result;
 use llm_connector::{
-    types::{ChatRequest, Usage as ConnectorUsage},
+    types::{ChatRequest, ChatStream, Usage as ConnectorUsage},
     StreamFormat,
 };
 use serde_json::{Map, Value};
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::Instrument;
+
+/// Serialize one stream message in the requested format
+fn format_stream_line(format: &StreamFormat, value: &Value) -> String {
+    match format {
+        StreamFormat::SSE => format!("data: {}\n\n", value),
+        StreamFormat::NDJSON => format!("{}\n", value),
+        StreamFormat::Json => value.to_string(),
+    }
+}
 
-/// Filter out <think> tags from content
-/// GLM-4.6 and similar models may include reasoning process in <think></think> tags
-fn filter_think_tags(content: &str) -> String {
-    // Use simple string replacement to remove <think>...</think> tags
-    let mut result = content.to_string();
-
-    // Remove <think>...</think> blocks (non-greedy)
-    while let Some(start) = result.find("<think>") {
-        if let Some(end) = result[start..].find("</think>") {
-            let end_pos = start + end + "</think>".len();
-            result.replace_range(start..end_pos, "");
-        } else {
-            // If no closing tag, remove from <think> to end
-            result.replace_range(start.., "");
-            break;
+/// Prompt / completion tokens for a finished stream
+///
+/// Uses the provider-reported usage when there is one, otherwise estimates
+/// from the request messages and the streamed output.
+fn stream_usage(
+    usage: Option<&ConnectorUsage>,
+    counter: &dyn TokenCounter,
+    prompt_estimate: u32,
+    output_text: &str,
+) -> (u32, u32) {
+    match usage {
+        Some(usage) if usage.prompt_tokens > 0 || usage.completion_tokens > 0 => {
+            (usage.prompt_tokens, usage.completion_tokens)
         }
+        _ => (prompt_estimate, counter.count_text(output_text)),
     }
+}
+
+impl Client {
+    /// Open an upstream stream and wait for its first chunk
+    ///
+    /// A stream that fails before producing anything is reported as an error
+    /// here, so the caller can still fail over to another provider before any
+    /// bytes have been sent to the client.
+    pub(super) async fn open_stream(&self, request: &ChatRequest) -> Result<ChatStream> {
+        use futures_util::StreamExt;
 
-    // Also remove standalone </think> tags (in case of malformed HTML)
-    result = result.replace("</think>", "");
-    result = result.replace("<think>", "");
+        self.check_context_length(request)?;
+        self.dialect().warn_dropped(self.backend.provider_name(), request);
+        let cache_slot = self.cache_slot(request);
+        if let Some(cached) = self.cached_response(cache_slot.as_ref()).await {
+            return Ok(replay_stream(cached));
+        }
 
-    // DON'T trim! Whitespace and newlines are important in streaming chunks
-    // Each chunk might be just a newline or space, which is part of the formatting
-    result
-}
+        let span = self.upstream_span(request);
+        let opened = async {
+            let mut stream = self.upstream_chat_stream(request).await
+                .map_err(|e| connector_error("LLM connector streaming error", e))?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_filter_think_tags() {
-        // Test simple think tag
-        assert_eq!(
-            filter_think_tags("<think>reasoning</think>actual content"),
-            "actual content"
-        );
-
-        // Test multiple think tags
-        assert_eq!(
-            filter_think_tags("<think>first</think>content<think>second</think>"),
-            "content"
-        );
-
-        // Test nested think tags
-        assert_eq!(
-            filter_think_tags("<think>outer<think>inner</think></think>text"),
-            "text"
-        );
-
-        // Test standalone closing tags
-        assert_eq!(
-            filter_think_tags("content</think></think>"),
-            "content"
-        );
-
-        // Test no think tags
-        assert_eq!(
-            filter_think_tags("normal content"),
-            "normal content"
-        );
-
-        // Test empty content
-        assert_eq!(
-            filter_think_tags("<think></think>"),
-            ""
-        );
-
-        // Test whitespace preservation (important for streaming!)
-        assert_eq!(
-            filter_think_tags("\n"),
-            "\n"
-        );
-
-        assert_eq!(
-            filter_think_tags("  "),
-            "  "
-        );
-
-        assert_eq!(
-            filter_think_tags("<think>test</think>\n"),
-            "\n"
-        );
-
-        // Test newlines in content
-        assert_eq!(
-            filter_think_tags("line1\nline2"),
-            "line1\nline2"
-        );
+            match stream.next().await {
+                Some(Err(e)) => Err(connector_error("LLM connector streaming error", e)),
+                Some(Ok(first)) => Ok(Box::pin(futures_util::stream::iter([Ok(first)]).chain(stream)) as ChatStream),
+                None => Ok(stream),
+            }
+        }
+        .instrument(span.clone())
+        .await;
+
+        match (opened, cache_slot) {
+            (Ok(stream), Some(slot)) => Ok(slot.capture(request, stream)),
+            (opened, _) => {
+                if let Err(e) = &opened {
+                    telemetry::record_error(&span, e);
+                }
+                opened
+            }
+        }
+    }
+
+    /// Open the stream for a chat request
+    async fn open_chat_stream(&self, request: &ChatRequest, options: &ChatOptions) -> Result<ChatStream> {
+        match &options.response_format {
+            Some(format) => self.open_structured_stream(request, format).await,
+            None => self.open_stream(request).await,
+        }
     }
-}
 
-impl Client {
     /// Send a streaming chat request with specified format (Ollama-style response)
     ///
     /// This method returns streaming responses in Ollama API format, which is used by
@@ -112,7 +102,7 @@ impl Client {
         messages: Vec<llm_connector::types::Message>,
         format: StreamFormat,
     ) -> Result<UnboundedReceiverStream<String>> {
-        self.chat_stream_with_format_and_tools(model, messages, None, format).await
+        self.chat_stream_with_format_and_tools(model, messages, None, &ChatOptions::default(), format).await
     }
 
     /// Send a streaming chat request with specified format and tools (Ollama-style response)
@@ -125,18 +115,21 @@ impl Client {
         model: &str,
         messages: Vec<llm_connector::types::Message>,
         tools: Option<Vec<llm_connector::types::Tool>>,
+        options: &ChatOptions,
         format: StreamFormat,
     ) -> Result<UnboundedReceiverStream<String>> {
         use futures_util::StreamExt;
 
         // Messages are already in llm-connector format
-        let request = ChatRequest {
+        let mut request = ChatRequest {
             model: model.to_string(),
             messages,
             stream: Some(true),
             tools,
             ..Default::default()
         };
+        options.apply_to(&mut request);
+        self.prepare_images(&mut request)?;
 
         tracing::info!("🔄 Requesting streaming from LLM connector (Ollama format) with {} tools...",
                       request.tools.as_ref().map_or(0, |t| t.len()));
@@ -148,14 +141,19 @@ impl Client {
         }
 
         // Use real streaming API
-        let mut stream = self.llm_client.chat_stream(&request).await
-            .map_err(|e| anyhow!("LLM connector streaming error: {}", e))?;
+        let mut stream = self.open_chat_stream(&request, options).await?;
 
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
         let model_name = model.to_string();
+        let counter = counter_for_model(model);
+        let prompt_estimate = counter.count_request(&request.messages, request.tools.as_deref());
+        let mut output_text = String::new();
         let mut last_finish_reason: Option<String> = None;
         let mut last_usage: Option<ConnectorUsage> = None;
-        let mut thinking_buffer = String::new();
+        let mut reasoning_filter = ReasoningFilter::new(options.reasoning);
+        let mut pending_tool_calls = ToolCallAccumulator::default();
+        let mut stream_error: Option<String> = None;
+        let span = telemetry::stream_span(self.backend.provider_name(), model);
 
         tokio::spawn(async move {
             tracing::debug!("🔄 Starting to process stream chunks (Ollama format)...");
@@ -184,36 +182,24 @@ impl Client {
                         message.insert("images".to_string(), Value::Null);
 
                         let mut has_payload = false;
-                        let mut content_text = String::new();
 
-                        // Only use delta.content, NOT reasoning_content
-                        // This prevents <think> tags from being sent to Zed
-                        if let Some(first_choice) = stream_chunk.choices.first() {
-                            if let Some(content) = &first_choice.delta.content {
-                                if !content.is_empty() {
-                                    // Filter out <think> tags from content
-                                    // GLM-4.6 sometimes includes reasoning in <think></think> tags
-                                    content_text = filter_think_tags(content);
-
-                                    // Only count as payload if there's actual content after filtering
-                                    if content_text.is_empty() && !content.is_empty() {
-                                        tracing::debug!("🧠 Filtered entire chunk (was only <think> tags): {:?}",
-                                                      content.chars().take(50).collect::<String>());
-                                    }
-                                }
-                            }
+                        // GLM-4.6 等模型会在 content 中夹带 <think></think>，
+                        // 按 ReasoningMode 去掉、保留或放入 message.thinking
+                        let (content, reasoning) = chunk_parts(&stream_chunk);
+                        let (thinking_text, content_text) = reasoning_filter.process(content, reasoning);
+                        if content_text.is_empty() && thinking_text.is_empty() && !content.is_empty() {
+                            tracing::debug!("🧠 Filtered entire chunk (was only <think> tags): {:?}",
+                                          content.chars().take(50).collect::<String>());
+                        }
 
-                            // Log if we're filtering out reasoning content
-                            if let Some(reasoning) = &first_choice.delta.reasoning_content {
-                                if !reasoning.is_empty() {
-                                    tracing::debug!("🧠 Filtered reasoning_content ({} chars): {:?}",
-                                                  reasoning.len(),
-                                                  reasoning.chars().take(50).collect::<String>());
-                                }
-                            }
+                        if !thinking_text.is_empty() {
+                            output_text.push_str(&thinking_text);
+                            has_payload = true;
+                            message.insert("thinking".to_string(), Value::String(thinking_text));
                         }
 
                         if !content_text.is_empty() {
+                            output_text.push_str(&content_text);
                             chunk_count += 1;
                             has_payload = true;
                             if chunk_count == 1 {
@@ -225,77 +211,28 @@ impl Client {
 
                         message.insert("content".to_string(), Value::String(content_text));
 
+                        // Tool-call arguments arrive in fragments; Zed needs each call
+                        // whole, so calls are collected and sent once the turn finishes
                         if let Some(first_choice) = stream_chunk.choices.first() {
-                            if let Some(tool_calls) = &first_choice.delta.tool_calls {
-                                if !tool_calls.is_empty() {
-                                    // Convert tool_calls to Ollama format
-                                    // Zed expects arguments to be a JSON object, not a string
-                                    let mut ollama_tool_calls = Vec::new();
-                                    for (i, tc) in tool_calls.iter().enumerate() {
-                                        tracing::debug!("🔧 Processing tool call {}: name={}, args_len={}",
-                                                      i, tc.function.name, tc.function.arguments.len());
-
-                                        let mut ollama_tc = serde_json::Map::new();
-
-                                        // Add tool call ID - this is crucial for Zed
-                                        if !tc.id.is_empty() {
-                                            ollama_tc.insert("id".to_string(), Value::String(tc.id.clone()));
-                                            tracing::debug!("✅ Tool call ID: {}", tc.id);
-                                        } else {
-                                            // Generate a unique ID if missing
-                                            let generated_id = format!("call_{}", uuid::Uuid::new_v4().to_string().replace("-", "")[..8].to_string());
-                                            ollama_tc.insert("id".to_string(), Value::String(generated_id.clone()));
-                                            tracing::warn!("⚠️ Generated tool call ID: {}", generated_id);
-                                        }
-
-                                        // Add type field
-                                        ollama_tc.insert("type".to_string(), Value::String("function".to_string()));
-
-                                        // Add function object
-                                        let mut function = serde_json::Map::new();
-                                        function.insert("name".to_string(), Value::String(tc.function.name.clone()));
-
-                                        // Parse arguments from string to JSON object
-                                        let arguments_value = if tc.function.arguments.is_empty() {
-                                            Value::Object(serde_json::Map::new())
-                                        } else {
-                                            match serde_json::from_str::<Value>(&tc.function.arguments) {
-                                                Ok(v) => {
-                                                    tracing::debug!("✅ Parsed tool arguments: {}", serde_json::to_string(&v).unwrap_or_default());
-                                                    v
-                                                }
-                                                Err(e) => {
-                                                    // If parsing fails, wrap in an object
-                                                    tracing::warn!("⚠️ Failed to parse tool arguments as JSON: {} - Error: {}", tc.function.arguments, e);
-                                                    Value::String(tc.function.arguments.clone())
-                                                }
-                                            }
-                                        };
-                                        function.insert("arguments".to_string(), arguments_value);
-
-                                        ollama_tc.insert("function".to_string(), Value::Object(function));
-
-                                        tracing::debug!("🔧 Converted tool call: {}", serde_json::to_string(&ollama_tc).unwrap_or_default());
-                                        ollama_tool_calls.push(Value::Object(ollama_tc));
-                                    }
+                            if let Some(tool_calls) = first_choice.delta.tool_calls.as_deref().filter(|calls| !calls.is_empty()) {
+                                tracing::debug!("🔧 Chunk includes {} tool call delta(s)", tool_calls.len());
+                                pending_tool_calls.push(tool_calls);
+                            }
 
-                                    message.insert("tool_calls".to_string(), Value::Array(ollama_tool_calls));
-                                    has_payload = true;
-                                    tracing::debug!("🔧 Chunk includes {} tool call(s)", tool_calls.len());
+                            if first_choice.finish_reason.is_some() && !pending_tool_calls.is_empty() {
+                                output_text.push_str(&pending_tool_calls.text());
+                                match pending_tool_calls.finish() {
+                                    Ok(tool_calls) => {
+                                        tracing::debug!("🔧 Sending {} complete tool call(s)", tool_calls.len());
+                                        message.insert("tool_calls".to_string(), Value::Array(tool_calls));
+                                        has_payload = true;
+                                    }
+                                    Err(e) => {
+                                        stream_error = Some(e);
+                                        break;
+                                    }
                                 }
                             }
-
-                            // Don't collect reasoning content for Zed
-                            // Zed doesn't need to see the thinking process (<think> tags)
-                            // If needed in the future, this can be controlled by a config flag
-                            // if let Some(reason_text) = first_choice.delta.reasoning_any() {
-                            //     if !reason_text.is_empty() {
-                            //         if !thinking_buffer.is_empty() {
-                            //             thinking_buffer.push(' ');
-                            //         }
-                            //         thinking_buffer.push_str(reason_text);
-                            //     }
-                            // }
                         }
 
                         if !has_payload {
@@ -326,6 +263,10 @@ impl Client {
                     }
                     Err(e) => {
                         tracing::error!("❌ Stream error: {:?}", e);
+                        telemetry::record_stream_error(&tracing::Span::current());
+                        if !pending_tool_calls.is_empty() {
+                            stream_error = Some(format!("Upstream stream failed in the middle of a tool call: {}", e));
+                        }
                         break;
                     }
                 }
@@ -333,16 +274,44 @@ impl Client {
 
             tracing::info!("✅ Stream processing completed. Total chunks: {}", chunk_count);
 
+            // Calls still open when the stream ends without a finish_reason
+            if stream_error.is_none() && !pending_tool_calls.is_empty() {
+                output_text.push_str(&pending_tool_calls.text());
+                match pending_tool_calls.finish() {
+                    Ok(tool_calls) => {
+                        let response_chunk = serde_json::json!({
+                            "model": &model_name,
+                            "created_at": chrono::Utc::now().to_rfc3339(),
+                            "message": {
+                                "role": "assistant",
+                                "content": "",
+                                "images": null,
+                                "tool_calls": tool_calls
+                            },
+                            "done": false
+                        });
+                        let _ = tx.send(format_stream_line(&format, &response_chunk));
+                    }
+                    Err(e) => stream_error = Some(e),
+                }
+            }
+
+            let span = tracing::Span::current();
+            span.record("llm_link.stream.chunks", chunk_count as i64);
+            if let Some(error) = stream_error {
+                telemetry::record_stream_error(&span);
+                tracing::error!("❌ {}", error);
+                let _ = tx.send(format_stream_line(&format, &serde_json::json!({"error": error})));
+                return;
+            }
+
             // Send final message
             let mut final_message = Map::new();
             final_message.insert("role".to_string(), Value::String("assistant".to_string()));
-            let final_content = String::new();
-            if !thinking_buffer.is_empty() {
-                final_message.insert(
-                    "thinking".to_string(),
-                    Value::String(thinking_buffer.clone()),
-                );
-                thinking_buffer.clear();
+            // 发出解析器中暂存的文本（以及 Passthrough 模式下未闭合的 </think>）
+            let (final_thinking, final_content) = reasoning_filter.finish();
+            if !final_thinking.is_empty() {
+                final_message.insert("thinking".to_string(), Value::String(final_thinking));
             }
             final_message.insert("content".to_string(), Value::String(final_content));
             final_message.insert("images".to_string(), Value::Null);
@@ -356,19 +325,20 @@ impl Client {
             final_chunk.insert("message".to_string(), Value::Object(final_message));
             final_chunk.insert("done".to_string(), Value::Bool(true));
 
-            let done_reason = last_finish_reason.unwrap_or_else(|| "stop".to_string());
+            let done_reason = last_finish_reason.as_deref().map(normalize_finish_reason).unwrap_or("stop").to_string();
+            telemetry::record_finish_reasons(&span, [done_reason.as_str()]);
             final_chunk.insert("done_reason".to_string(), Value::String(done_reason));
 
-            if let Some(usage) = last_usage {
-                final_chunk.insert(
-                    "prompt_eval_count".to_string(),
-                    Value::Number(usage.prompt_tokens.into()),
-                );
-                final_chunk.insert(
-                    "eval_count".to_string(),
-                    Value::Number(usage.completion_tokens.into()),
-                );
-            }
+            let (prompt_tokens, completion_tokens) = stream_usage(last_usage.as_ref(), counter, prompt_estimate, &output_text);
+            telemetry::record_usage(&span, prompt_tokens, completion_tokens);
+            final_chunk.insert(
+                "prompt_eval_count".to_string(),
+                Value::Number(prompt_tokens.into()),
+            );
+            final_chunk.insert(
+                "eval_count".to_string(),
+                Value::Number(completion_tokens.into()),
+            );
 
             let final_chunk_value = Value::Object(final_chunk);
 
@@ -379,7 +349,7 @@ impl Client {
             };
             let _ = tx.send(formatted_final);
             tracing::debug!("🏁 Sent final chunk");
-        });
+        }.instrument(span));
 
         Ok(UnboundedReceiverStream::new(rx))
     }
@@ -397,32 +367,43 @@ impl Client {
         model: &str,
         messages: Vec<llm_connector::types::Message>,
         tools: Option<Vec<llm_connector::types::Tool>>,
+        options: &ChatOptions,
         format: StreamFormat,
     ) -> Result<UnboundedReceiverStream<String>> {
         use futures_util::StreamExt;
 
         // Messages are already in llm-connector format
-        let request = ChatRequest {
+        let mut request = ChatRequest {
             model: model.to_string(),
             messages,
             stream: Some(true),
             tools,
             ..Default::default()
         };
+        options.apply_to(&mut request);
+        self.prepare_images(&mut request)?;
 
         tracing::info!("🔄 Requesting streaming from LLM connector...");
 
         // Use real streaming API
-        let mut stream = self.llm_client.chat_stream(&request).await
-            .map_err(|e| anyhow!("LLM connector streaming error: {}", e))?;
+        let mut stream = self.open_chat_stream(&request, options).await?;
 
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
         let model_name = model.to_string();
+        let counter = counter_for_model(model);
+        let prompt_estimate = counter.count_request(&request.messages, request.tools.as_deref());
+        let include_usage = options.include_usage;
+        let mut reasoning_filter = ReasoningFilter::new(options.reasoning);
+        let span = telemetry::stream_span(self.backend.provider_name(), model);
 
         tokio::spawn(async move {
             tracing::info!("🔄 Starting to process stream chunks (OpenAI format)...");
+            let mut output_text = String::new();
+            let mut last_usage: Option<ConnectorUsage> = None;
             let mut chunk_count = 0;
             let mut has_tool_calls = false;  // Track if tool_calls detected
+            let mut upstream_finish_reason: Option<String> = None;
+            let mut stream_error: Option<String> = None;
             
             // Track tool call IDs by index for Codex CLI compatibility
             // Codex uses `id` field to accumulate arguments across chunks,
@@ -437,28 +418,45 @@ impl Client {
                     Ok(stream_chunk) => {
                         tracing::debug!("✅ Chunk OK, checking for content or tool_calls...");
 
+                        if let Some(usage) = stream_chunk.usage.clone() {
+                            last_usage = Some(usage);
+                        }
+
                         // Build delta object
                         let mut delta = serde_json::json!({});
                         let mut has_data = false;
 
+                        // Split reasoning from the answer according to the ReasoningMode
+                        let (content, reasoning) = chunk_parts(&stream_chunk);
+                        let (reasoning_text, content_text) = reasoning_filter.process(content, reasoning);
+                        if !reasoning_text.is_empty() {
+                            output_text.push_str(&reasoning_text);
+                            delta["reasoning_content"] = serde_json::json!(reasoning_text);
+                            has_data = true;
+                        }
+
                         // Check for content
-                        if let Some(content) = stream_chunk.get_content() {
-                            if !content.is_empty() {
-                                delta["content"] = serde_json::json!(content);
-                                has_data = true;
-                                chunk_count += 1;
-                                tracing::info!("📦 Received chunk #{}: '{}' ({} chars)", chunk_count, content, content.len());
-                            }
+                        if !content_text.is_empty() {
+                            output_text.push_str(&content_text);
+                            tracing::debug!("📦 Received chunk #{} ({} chars)", chunk_count + 1, content_text.len());
+                            delta["content"] = serde_json::json!(content_text);
+                            has_data = true;
+                            chunk_count += 1;
                         }
 
                         // Check for tool_calls (extract from choices[0].delta.tool_calls)
                         if let Some(first_choice) = stream_chunk.choices.first() {
+                            if let Some(reason) = &first_choice.finish_reason {
+                                upstream_finish_reason = Some(reason.clone());
+                            }
                             if let Some(tool_calls) = &first_choice.delta.tool_calls {
                                 // Build tool_calls array with id injection for Codex compatibility
                                 let mut tool_calls_array = Vec::new();
                                 
                                 for tc in tool_calls {
                                     let index = tc.index.unwrap_or(0);
+                                    output_text.push_str(&tc.function.name);
+                                    output_text.push_str(&tc.function.arguments);
                                     
                                     // Remember id from first chunk, inject into subsequent chunks
                                     if !tc.id.is_empty() {
@@ -539,6 +537,7 @@ impl Client {
                     }
                     Err(e) => {
                         tracing::error!("❌ Stream error: {:?}", e);
+                        stream_error = Some(format!("Upstream stream failed: {}", e));
                         break;
                     }
                 }
@@ -546,16 +545,26 @@ impl Client {
 
             tracing::info!("✅ Stream processing completed. Total chunks: {}", chunk_count);
 
+            // 中途失败时发送错误事件而不是正常结束，客户端和用量统计才能看出回答被截断
+            if let Some(error) = stream_error {
+                let span = tracing::Span::current();
+                span.record("llm_link.stream.chunks", chunk_count as i64);
+                telemetry::record_stream_error(&span);
+                let error = serde_json::json!({"error": {"message": error, "type": "api_error"}});
+                let _ = tx.send(format_stream_line(&format, &error));
+                return;
+            }
+
             // Send final message at stream end
             // 🎯 Key fix: If tool_calls detected, finish_reason should be "tool_calls" not "stop"
             let finish_reason = if has_tool_calls {
                 tracing::info!("🎯 Setting finish_reason to 'tool_calls' (detected tool_calls in stream)");
                 "tool_calls"
             } else {
-                "stop"
+                upstream_finish_reason.as_deref().map(normalize_finish_reason).unwrap_or("stop")
             };
 
-            let final_chunk = serde_json::json!({
+            let mut final_chunk = serde_json::json!({
                 "id": "chatcmpl-123",
                 "object": "chat.completion.chunk",
                 "created": chrono::Utc::now().timestamp(),
@@ -566,17 +575,74 @@ impl Client {
                     "finish_reason": finish_reason
                 }]
             });
+            // 原始结束原因（如 "stop_sequence"）规范化后会丢失，Anthropic 前端需要它
+            if let Some(upstream) = upstream_finish_reason.as_deref().filter(|upstream| *upstream != finish_reason) {
+                final_chunk["choices"][0]["upstream_finish_reason"] = Value::String(upstream.to_string());
+            }
+
+            let mut final_chunks = Vec::new();
+            // 发出解析器中暂存的文本（以及 Passthrough 模式下未闭合的 </think>）
+            let (trailing_reasoning, trailing_content) = reasoning_filter.finish();
+            if !trailing_reasoning.is_empty() || !trailing_content.is_empty() {
+                let mut delta = serde_json::json!({});
+                if !trailing_reasoning.is_empty() {
+                    delta["reasoning_content"] = serde_json::json!(trailing_reasoning);
+                }
+                if !trailing_content.is_empty() {
+                    delta["content"] = serde_json::json!(trailing_content);
+                }
+                final_chunks.push(serde_json::json!({
+                    "id": "chatcmpl-123",
+                    "object": "chat.completion.chunk",
+                    "created": chrono::Utc::now().timestamp(),
+                    "model": model_name,
+                    "choices": [{
+                        "index": 0,
+                        "delta": delta,
+                        "finish_reason": null
+                    }]
+                }));
+            }
+            final_chunks.push(final_chunk);
+
+            let span = tracing::Span::current();
+            span.record("llm_link.stream.chunks", chunk_count as i64);
+            telemetry::record_finish_reasons(&span, [finish_reason]);
+
+            let usage = (include_usage || telemetry::is_enabled())
+                .then(|| stream_usage(last_usage.as_ref(), counter, prompt_estimate, &output_text));
+            if let Some((prompt_tokens, completion_tokens)) = usage {
+                telemetry::record_usage(&span, prompt_tokens, completion_tokens);
+            }
+
+            // OpenAI 在 include_usage 时会在结束前额外发送一个 choices 为空的 usage chunk
+            if let (true, Some((prompt_tokens, completion_tokens))) = (include_usage, usage) {
+                final_chunks.push(serde_json::json!({
+                    "id": "chatcmpl-123",
+                    "object": "chat.completion.chunk",
+                    "created": chrono::Utc::now().timestamp(),
+                    "model": model_name,
+                    "choices": [],
+                    "usage": {
+                        "prompt_tokens": prompt_tokens,
+                        "completion_tokens": completion_tokens,
+                        "total_tokens": prompt_tokens + completion_tokens
+                    }
+                }));
+            }
 
             let formatted_final = match format {
-                StreamFormat::SSE => format!("data: {}\n\ndata: [DONE]\n\n", final_chunk),
-                StreamFormat::NDJSON => format!("{}\n", final_chunk),
-                StreamFormat::Json => final_chunk.to_string(),
+                StreamFormat::SSE => final_chunks.iter()
+                    .map(|chunk| format!("data: {}\n\n", chunk))
+                    .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+                    .collect::<String>(),
+                StreamFormat::NDJSON => final_chunks.iter().map(|chunk| format!("{}\n", chunk)).collect(),
+                StreamFormat::Json => final_chunks.iter().map(|chunk| chunk.to_string()).collect(),
             };
             let _ = tx.send(formatted_final);
             tracing::info!("🏁 Sent final chunk and [DONE] marker");
-        });
+        }.instrument(span));
 
         Ok(UnboundedReceiverStream::new(rx))
     }
 }
-
@@ -0,0 +1,586 @@
+//! Structured JSON output
+//!
+//! Providers with an OpenAI-style `response_format` get it as is and stream
+//! normally. For the others the proxy enforces the format itself: the
+//! request gets an instruction describing the expected JSON, and the reply
+//! is repaired and validated before it reaches the client. Replies that
+//! still do not match are sent back to the model with the validation
+//! errors, up to [`MAX_JSON_RETRIES`] times.
+
+use super::reasoning::take_inline_reasoning;
+use super::types::Response;
+use super::wire;
+use super::Client;
+use anyhow::{anyhow, Result};
+use llm_connector::types::{ChatRequest, ChatStream, Message, MessageBlock, Role, StreamingResponse};
+use serde_json::{json, Value};
+
+/// How many times a reply that does not match the requested format is retried
+pub const MAX_JSON_RETRIES: usize = 2;
+
+/// JSON output requested by the client
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// Any JSON object
+    JsonObject,
+    /// JSON matching a schema
+    JsonSchema { name: String, schema: Value },
+}
+
+impl OutputFormat {
+    /// Parse OpenAI `response_format` (`text` means no constraint)
+    pub fn from_openai(value: &Value) -> Option<Self> {
+        match value["type"].as_str()? {
+            "json_object" => Some(OutputFormat::JsonObject),
+            "json_schema" => {
+                let spec = &value["json_schema"];
+                Some(OutputFormat::JsonSchema {
+                    name: spec["name"].as_str().unwrap_or("response").to_string(),
+                    schema: spec.get("schema").cloned().unwrap_or_else(|| json!({"type": "object"})),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse Ollama `format`: `"json"` or a JSON schema
+    pub fn from_ollama(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(format) if format == "json" => Some(OutputFormat::JsonObject),
+            Value::Object(_) => Some(OutputFormat::JsonSchema {
+                name: "response".to_string(),
+                schema: value.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Parse Anthropic `output_format`: `{"type": "json_schema", "schema": ...}`
+    pub fn from_anthropic(value: &Value) -> Option<Self> {
+        match value["type"].as_str()? {
+            "json_schema" => Some(OutputFormat::JsonSchema {
+                name: "response".to_string(),
+                schema: value.get("schema").cloned().unwrap_or_else(|| json!({"type": "object"})),
+            }),
+            _ => None,
+        }
+    }
+
+    /// OpenAI `response_format` object
+    pub fn to_openai(&self) -> Value {
+        match self {
+            OutputFormat::JsonObject => json!({"type": "json_object"}),
+            OutputFormat::JsonSchema { name, schema } => {
+                json!({"type": "json_schema", "json_schema": {"name": name, "schema": schema}})
+            }
+        }
+    }
+
+    /// Value of `response_format.type` on the upstream request
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            OutputFormat::JsonObject => "json_object",
+            OutputFormat::JsonSchema { .. } => "json_schema",
+        }
+    }
+
+    fn instruction(&self) -> String {
+        let base = "Respond with a single valid JSON value only, without code fences or any other text.";
+        match self {
+            OutputFormat::JsonObject => format!("{} The top-level value must be a JSON object.", base),
+            OutputFormat::JsonSchema { name, schema } => format!(
+                "{} The JSON must match the schema \"{}\":\n{}",
+                base,
+                name,
+                serde_json::to_string_pretty(schema).unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Repair `text` into JSON and check it against the format
+    pub fn check(&self, text: &str) -> Result<Value, Vec<String>> {
+        let value = repair_json(text).ok_or_else(|| vec!["reply is not valid JSON".to_string()])?;
+        let errors = match self {
+            OutputFormat::JsonObject if !value.is_object() => vec!["top-level value must be an object".to_string()],
+            OutputFormat::JsonObject => Vec::new(),
+            OutputFormat::JsonSchema { schema, .. } => validate(&value, schema),
+        };
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// How much of a format the provider enforces itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Native {
+    /// The format as requested
+    Full,
+    /// JSON mode only, the schema is up to the proxy
+    JsonMode,
+    None,
+}
+
+fn native_support(provider: &str, format: &OutputFormat) -> Native {
+    match (provider, format) {
+        ("openai" | "volcengine", _) => Native::Full,
+        ("zhipu" | "aliyun" | "moonshot", OutputFormat::JsonObject) => Native::Full,
+        ("zhipu" | "aliyun" | "moonshot", OutputFormat::JsonSchema { .. }) => Native::JsonMode,
+        _ => Native::None,
+    }
+}
+
+/// Ask `provider` for `format`; `true` if the provider enforces it completely
+///
+/// Otherwise the format is described in the system prompt, along with JSON
+/// mode where the provider has one.
+fn prepare_request(provider: &str, request: &mut ChatRequest, format: &OutputFormat) -> bool {
+    match native_support(provider, format) {
+        Native::Full => {
+            request.response_format = Some(wire::response_format(&format.to_openai()));
+            return true;
+        }
+        Native::JsonMode => {
+            request.response_format = Some(wire::response_format(&OutputFormat::JsonObject.to_openai()));
+        }
+        Native::None => {}
+    }
+    add_instruction(request, format);
+    false
+}
+
+impl Client {
+    /// Open the stream of a request whose reply must match `format`
+    ///
+    /// Only providers that enforce the format themselves stream; otherwise
+    /// the reply has to be checked as a whole, so it is completed first and
+    /// replayed by [`replay_stream`].
+    pub(crate) async fn open_structured_stream(&self, request: &ChatRequest, format: &OutputFormat) -> Result<ChatStream> {
+        let provider = self.backend.provider_name();
+        if native_support(provider, format) == Native::Full {
+            let mut request = request.clone();
+            prepare_request(provider, &mut request, format);
+            return self.open_stream(&request).await;
+        }
+        let response = self.complete_structured(request.clone(), format).await?;
+        Ok(replay_stream(response))
+    }
+
+    /// Run a chat request whose reply must match `format`
+    ///
+    /// The reply is checked even when the provider enforces the format, as
+    /// that can still be cut off by `max_tokens`.
+    pub(crate) async fn complete_structured(&self, mut request: ChatRequest, format: &OutputFormat) -> Result<Response> {
+        prepare_request(self.backend.provider_name(), &mut request, format);
+        request.stream = None;
+
+        let mut response = self.send_chat(&request).await?;
+        let mut usage = response.usage.clone();
+        let mut attempt = 0;
+        loop {
+            // 模型选择调用工具时没有 JSON 正文可校验
+            if response.tool_calls.is_some() {
+                response.usage = usage;
+                return Ok(response);
+            }
+
+            // 推理模型可能把思考过程以 <think> 块写在正文前面
+            take_inline_reasoning(&mut response);
+            match format.check(&response.content) {
+                Ok(value) => {
+                    response.content = value.to_string();
+                    response.usage = usage;
+                    return Ok(response);
+                }
+                Err(errors) if attempt < MAX_JSON_RETRIES => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "⚠️ Reply does not match {} ({}), retrying ({}/{})",
+                        format.type_name(), errors.join("; "), attempt, MAX_JSON_RETRIES
+                    );
+                    request.messages.push(Message::assistant(response.content.clone()));
+                    request.messages.push(Message::user(format!(
+                        "Your reply was rejected: {}. Reply again with only the corrected JSON.",
+                        errors.join("; ")
+                    )));
+
+                    response = self.send_chat(&request).await?;
+                    usage.prompt_tokens += response.usage.prompt_tokens;
+                    usage.completion_tokens += response.usage.completion_tokens;
+                    usage.total_tokens += response.usage.total_tokens;
+                }
+                Err(errors) => {
+                    return Err(anyhow!(
+                        "Model reply did not match the requested {} after {} attempts: {}",
+                        format.type_name(),
+                        attempt + 1,
+                        errors.join("; ")
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Append the format instruction to the system prompt, adding one if needed
+fn add_instruction(request: &mut ChatRequest, format: &OutputFormat) {
+    let instruction = format.instruction();
+    match request.messages.iter_mut().find(|m| m.role == Role::System) {
+        Some(system) => {
+            let text = format!("{}\n\n{}", system.content_as_text(), instruction);
+            system.content = vec![MessageBlock::text(text)];
+        }
+        None => request.messages.insert(0, Message::system(instruction)),
+    }
+}
+
+/// A finished response as a one-chunk upstream stream
+pub(crate) fn replay_stream(response: Response) -> ChatStream {
+    let mut delta = json!({"role": "assistant", "content": response.content});
+    if let Some(reasoning) = &response.reasoning {
+        delta["reasoning_content"] = json!(reasoning);
+    }
+    if let Some(Value::Array(tool_calls)) = &response.tool_calls {
+        let indexed: Vec<Value> = tool_calls
+            .iter()
+            .enumerate()
+            .map(|(index, call)| {
+                let mut call = call.clone();
+                call["index"] = json!(index);
+                call
+            })
+            .collect();
+        delta["tool_calls"] = Value::Array(indexed);
+    }
+
+    let chunk = json!({
+        "id": format!("chatcmpl-{}", uuid::Uuid::new_v4().simple()),
+        "object": "chat.completion.chunk",
+        "created": chrono::Utc::now().timestamp(),
+        "model": response.model,
+        "choices": [{
+            "index": 0,
+            "delta": delta,
+            "finish_reason": response.finish_reason.as_deref().unwrap_or("stop"),
+        }],
+        "content": response.content,
+        "usage": {
+            "prompt_tokens": response.usage.prompt_tokens,
+            "completion_tokens": response.usage.completion_tokens,
+            "total_tokens": response.usage.total_tokens,
+        },
+    });
+
+    let chunk = serde_json::from_value::<StreamingResponse>(chunk)
+        .map_err(|e| llm_connector::error::LlmConnectorError::ParseError(e.to_string()));
+    Box::pin(futures_util::stream::iter([chunk]))
+}
+
+/// Best-effort parse of a model reply as JSON
+///
+/// Handles code fences, prose around the JSON, trailing commas and replies
+/// cut off before their closing brackets.
+pub fn repair_json(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+
+    let start = text.find(['{', '['])?;
+    let candidate = &text[start..];
+    let end = candidate.rfind(['}', ']']).map_or(candidate.len(), |end| end + 1);
+    let candidate = &candidate[..end];
+    if let Ok(value) = serde_json::from_str(candidate) {
+        return Some(value);
+    }
+
+    let cleaned = strip_trailing_commas(candidate);
+    if let Ok(value) = serde_json::from_str(&cleaned) {
+        return Some(value);
+    }
+
+    // 输出被截断：补齐未闭合的字符串和括号
+    serde_json::from_str(&close_brackets(&strip_trailing_commas(&text[start..]))).ok()
+}
+
+fn strip_trailing_commas(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '}' || c == ']' {
+            let trimmed = out.trim_end().len();
+            if out[..trimmed].ends_with(',') {
+                out.truncate(trimmed - 1);
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn close_brackets(text: &str) -> String {
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = text.trim_end().to_string();
+    if in_string {
+        out.push('"');
+    }
+    let trimmed = out.trim_end().len();
+    if out[..trimmed].ends_with(',') || out[..trimmed].ends_with(':') {
+        out.truncate(trimmed - 1);
+    }
+    out.extend(stack.iter().rev());
+    out
+}
+
+/// Validate `value` against a JSON schema
+///
+/// Covers the keywords structured-output schemas use in practice: `type`,
+/// `enum`, `const`, `properties`, `required`, `additionalProperties`,
+/// `items`, length / size / range bounds, `anyOf` / `oneOf` / `allOf` and
+/// local `$ref`s. Unknown keywords are ignored.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, schema, "$", &mut errors);
+    errors
+}
+
+fn validate_at(value: &Value, schema: &Value, root: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        match reference.strip_prefix("#").map(|pointer| root.pointer(pointer)) {
+            Some(Some(target)) => validate_at(value, target, root, path, errors),
+            _ => errors.push(format!("{}: unresolved $ref '{}'", path, reference)),
+        }
+        return;
+    }
+
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(value, t)) {
+            errors.push(format!("{}: expected {}, got {}", path, allowed.join(" or "), type_of(value)));
+            return;
+        }
+    }
+
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(value) {
+            errors.push(format!("{}: must be one of {}", path, schema["enum"]));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            errors.push(format!("{}: must be {}", path, expected));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            if let Some(required) = schema["required"].as_array() {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !map.contains_key(key) {
+                        errors.push(format!("{}: missing required property '{}'", path, key));
+                    }
+                }
+            }
+            let properties = schema["properties"].as_object();
+            for (key, item) in map {
+                let item_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(item_schema) => validate_at(item, item_schema, root, &item_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => errors.push(format!("{}: unexpected property", item_path)),
+                        Some(extra @ Value::Object(_)) => validate_at(item, extra, root, &item_path, errors),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema["minItems"].as_u64() {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{}: expected at least {} items", path, min));
+                }
+            }
+            if let Some(max) = schema["maxItems"].as_u64() {
+                if items.len() as u64 > max {
+                    errors.push(format!("{}: expected at most {} items", path, max));
+                }
+            }
+            if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item, item_schema, root, &format!("{}[{}]", path, index), errors);
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if schema["minLength"].as_u64().is_some_and(|min| len < min) {
+                errors.push(format!("{}: shorter than {} characters", path, schema["minLength"]));
+            }
+            if schema["maxLength"].as_u64().is_some_and(|max| len > max) {
+                errors.push(format!("{}: longer than {} characters", path, schema["maxLength"]));
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if schema["minimum"].as_f64().is_some_and(|min| number < min) {
+                errors.push(format!("{}: must be >= {}", path, schema["minimum"]));
+            }
+            if schema["maximum"].as_f64().is_some_and(|max| number > max) {
+                errors.push(format!("{}: must be <= {}", path, schema["maximum"]));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(all) = schema["allOf"].as_array() {
+        for sub in all {
+            validate_at(value, sub, root, path, errors);
+        }
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(options) = schema[keyword].as_array() {
+            let matching = options
+                .iter()
+                .filter(|sub| {
+                    let mut sub_errors = Vec::new();
+                    validate_at(value, sub, root, path, &mut sub_errors);
+                    sub_errors.is_empty()
+                })
+                .count();
+            let ok = if keyword == "oneOf" { matching == 1 } else { matching > 0 };
+            if !ok {
+                errors.push(format!("{}: does not match {}", path, keyword));
+            }
+        }
+    }
+}
+
+fn type_matches(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_json() {
+        assert_eq!(repair_json("{\"a\": 1}"), Some(json!({"a": 1})));
+        assert_eq!(repair_json("```json\n{\"a\": [1, 2,],}\n```"), Some(json!({"a": [1, 2]})));
+        assert_eq!(repair_json("Sure! Here it is: {\"a\": \"x\"} Hope this helps."), Some(json!({"a": "x"})));
+        assert_eq!(repair_json("{\"a\": {\"b\": \"trunc"), Some(json!({"a": {"b": "trunc"}})));
+        assert_eq!(repair_json("no json here"), None);
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"$ref": "#/$defs/tag"}}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false,
+            "$defs": {"tag": {"type": "string", "enum": ["a", "b"]}}
+        });
+
+        assert!(validate(&json!({"name": "x", "age": 3, "tags": ["a"]}), &schema).is_empty());
+
+        let errors = validate(&json!({"name": 1, "tags": ["c"], "extra": true}), &schema);
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("missing required property 'age'")));
+        assert!(errors.iter().any(|e| e.starts_with("$.name: expected string")));
+        assert!(errors.iter().any(|e| e.starts_with("$.tags[0]: must be one of")));
+        assert!(errors.iter().any(|e| e.starts_with("$.extra: unexpected property")));
+    }
+
+    #[test]
+    fn test_output_format_check() {
+        let format = OutputFormat::from_openai(&json!({"type": "json_object"})).unwrap();
+        assert_eq!(format.check("{\"ok\": true}"), Ok(json!({"ok": true})));
+        assert!(format.check("[1, 2]").is_err());
+        assert_eq!(OutputFormat::from_openai(&json!({"type": "text"})), None);
+        assert_eq!(OutputFormat::from_ollama(&json!("json")), Some(OutputFormat::JsonObject));
+    }
+
+    #[test]
+    fn test_native_response_format() {
+        let schema = OutputFormat::JsonSchema { name: "r".to_string(), schema: json!({"type": "object"}) };
+        let prepared = |provider: &str, format: &OutputFormat| {
+            let mut request = ChatRequest { messages: vec![Message::user("hi")], ..Default::default() };
+            let native = prepare_request(provider, &mut request, format);
+            let response_format = request.response_format.as_ref().map(wire::response_format_value);
+            (native, response_format, request.messages.len())
+        };
+
+        // 原生支持：原样转发，不注入提示
+        assert_eq!(prepared("openai", &schema), (true, Some(schema.to_openai()), 1));
+        assert_eq!(prepared("moonshot", &OutputFormat::JsonObject), (true, Some(json!({"type": "json_object"})), 1));
+        // 只有 JSON 模式：schema 仍写进系统提示
+        assert_eq!(prepared("zhipu", &schema), (false, Some(json!({"type": "json_object"})), 2));
+        assert_eq!(prepared("tencent", &schema), (false, None, 2));
+    }
+}
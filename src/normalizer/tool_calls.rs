@@ -0,0 +1,153 @@
+use llm_connector::types::ToolCall;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Tool-call deltas of one stream, accumulated by `index`
+///
+/// Providers stream `arguments` in fragments, while llm-connector's OpenAI
+/// SSE parser re-sends the whole call so far with every chunk. A delta whose
+/// arguments extend what has been collected is therefore taken as a snapshot,
+/// anything else is appended.
+#[derive(Debug, Default)]
+pub(crate) struct ToolCallAccumulator {
+    calls: BTreeMap<usize, PendingToolCall>,
+}
+
+#[derive(Debug, Default)]
+struct PendingToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+impl ToolCallAccumulator {
+    pub(crate) fn push(&mut self, deltas: &[ToolCall]) {
+        for (position, delta) in deltas.iter().enumerate() {
+            let call = self.calls.entry(delta.index.unwrap_or(position)).or_default();
+            if !delta.id.is_empty() {
+                call.id = delta.id.clone();
+            }
+            if !delta.function.name.is_empty() {
+                call.name = delta.function.name.clone();
+            }
+            let arguments = &delta.function.arguments;
+            if arguments.starts_with(&call.arguments) {
+                call.arguments = arguments.clone();
+            } else {
+                call.arguments.push_str(arguments);
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Name and arguments text of the pending calls, for token counting
+    pub(crate) fn text(&self) -> String {
+        self.calls.values().map(|call| format!("{}{}", call.name, call.arguments)).collect()
+    }
+
+    /// The pending calls in OpenAI format, arguments left as text
+    pub(crate) fn to_tool_calls(&self) -> Vec<ToolCall> {
+        self.calls
+            .values()
+            .map(|call| ToolCall {
+                id: call.id.clone(),
+                call_type: "function".to_string(),
+                function: llm_connector::types::FunctionCall {
+                    name: call.name.clone(),
+                    arguments: call.arguments.clone(),
+                },
+                index: None,
+            })
+            .collect()
+    }
+
+    /// Take the finished calls in Ollama format
+    ///
+    /// Zed expects `arguments` as a JSON object, so a call whose arguments do
+    /// not parse (the stream stopped mid-call) is reported as an error.
+    pub(crate) fn finish(&mut self) -> Result<Vec<Value>, String> {
+        let calls = std::mem::take(&mut self.calls);
+        calls
+            .into_values()
+            .map(|call| {
+                if call.name.is_empty() {
+                    return Err("Stream ended before the tool call name was received".to_string());
+                }
+                let arguments = if call.arguments.trim().is_empty() {
+                    json!({})
+                } else {
+                    serde_json::from_str::<Value>(&call.arguments).map_err(|e| {
+                        format!(
+                            "Stream ended in the middle of tool call '{}': arguments are incomplete JSON ({})",
+                            call.name, e
+                        )
+                    })?
+                };
+                let id = if call.id.is_empty() {
+                    // Generate a unique ID if missing
+                    let generated_id = format!("call_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+                    tracing::warn!("⚠️ Generated tool call ID: {}", generated_id);
+                    generated_id
+                } else {
+                    call.id
+                };
+                Ok(json!({
+                    "id": id,
+                    "type": "function",
+                    "function": {
+                        "name": call.name,
+                        "arguments": arguments
+                    }
+                }))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm_connector::types::FunctionCall;
+
+    fn delta(index: usize, id: &str, name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            call_type: String::new(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+            index: Some(index),
+        }
+    }
+
+    #[test]
+    fn test_tool_call_accumulation() {
+        // Fragments
+        let mut calls = ToolCallAccumulator::default();
+        calls.push(&[delta(0, "call_1", "get_weather", "{\"ci")]);
+        calls.push(&[delta(0, "", "", "ty\": \"Par")]);
+        calls.push(&[delta(0, "", "", "is\"}"), delta(1, "call_2", "get_time", "")]);
+        let finished = calls.finish().unwrap();
+        assert_eq!(finished[0]["id"], "call_1");
+        assert_eq!(finished[0]["function"]["arguments"], json!({"city": "Paris"}));
+        assert_eq!(finished[1]["function"]["name"], "get_time");
+        assert_eq!(finished[1]["function"]["arguments"], json!({}));
+        assert!(calls.is_empty());
+
+        // Cumulative snapshots
+        let mut calls = ToolCallAccumulator::default();
+        calls.push(&[delta(0, "call_1", "get_weather", "{\"city\":")]);
+        calls.push(&[delta(0, "call_1", "get_weather", "{\"city\": \"Paris\"}")]);
+        assert_eq!(calls.finish().unwrap()[0]["function"]["arguments"], json!({"city": "Paris"}));
+
+        // Stream cut off mid-call
+        let mut calls = ToolCallAccumulator::default();
+        calls.push(&[delta(0, "call_1", "get_weather", "{\"city\": \"Pa")]);
+        let err = calls.finish().unwrap_err();
+        assert!(err.contains("get_weather"));
+    }
+}
@@ -1,6 +1,8 @@
+use crate::settings::ReasoningMode;
+
 
 /// Token usage information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[allow(dead_code)]
 pub struct Usage {
     pub prompt_tokens: u32,
@@ -9,13 +11,90 @@ pub struct Usage {
 }
 
 /// LLM response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[allow(dead_code)]
 pub struct Response {
     pub content: String,
     pub model: String,
     pub usage: Usage,
     pub tool_calls: Option<serde_json::Value>,  // Store tool_calls from LLM response
+    pub finish_reason: Option<String>,  // Raw finish reason reported by the provider
+    pub reasoning: Option<String>,  // Reasoning / thinking text, separate from the answer
+}
+
+/// Per-request options forwarded onto the upstream `ChatRequest`
+#[derive(Debug, Clone, Default)]
+pub struct ChatOptions {
+    pub tool_choice: Option<llm_connector::types::ToolChoice>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub stop: Option<Vec<String>>,
+    pub user: Option<String>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub seed: Option<u64>,
+    /// Structured JSON output, enforced by the proxy (see `structured`)
+    pub response_format: Option<super::structured::OutputFormat>,
+    /// Number of choices; `ChatRequest` has no such field, so only 1 is served
+    pub n: Option<u32>,
+    /// Emit a trailing usage chunk in OpenAI streams (`stream_options.include_usage`)
+    pub include_usage: bool,
+    /// How reasoning is returned to the client
+    pub reasoning: ReasoningMode,
+    /// Ask the provider to think (Anthropic `thinking`, Ollama `think`)
+    pub enable_thinking: Option<bool>,
+}
+
+impl ChatOptions {
+    /// Copy the options that are set onto an outgoing request
+    ///
+    /// `response_format` is not copied: `structured` decides how it is enforced.
+    pub fn apply_to(&self, request: &mut llm_connector::types::ChatRequest) {
+        if let Some(tool_choice) = &self.tool_choice {
+            request.tool_choice = Some(tool_choice.clone());
+        }
+        if self.temperature.is_some() {
+            request.temperature = self.temperature;
+        }
+        if self.top_p.is_some() {
+            request.top_p = self.top_p;
+        }
+        if self.max_tokens.is_some() {
+            request.max_tokens = self.max_tokens;
+        }
+        if let Some(stop) = self.stop.as_ref().filter(|stop| !stop.is_empty()) {
+            request.stop = Some(stop.clone());
+        }
+        if let Some(user) = &self.user {
+            request.user = Some(user.clone());
+        }
+        if self.presence_penalty.is_some() {
+            request.presence_penalty = self.presence_penalty;
+        }
+        if self.frequency_penalty.is_some() {
+            request.frequency_penalty = self.frequency_penalty;
+        }
+        if self.seed.is_some() {
+            request.seed = self.seed;
+        }
+        if self.enable_thinking.is_some() {
+            request.enable_thinking = self.enable_thinking;
+        }
+        if let Some(n) = self.n.filter(|n| *n > 1) {
+            tracing::warn!("⚠️ n={} is not supported upstream, returning a single choice", n);
+        }
+    }
+}
+
+/// Map provider-specific finish reasons onto the OpenAI vocabulary
+pub fn normalize_finish_reason(reason: &str) -> &str {
+    match reason {
+        "end_turn" | "stop_sequence" => "stop",
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        other => other,
+    }
 }
 
 /// Model information
@@ -24,3 +103,19 @@ pub struct Model {
     pub id: String,
 }
 
+
+/// Embedding request forwarded to the provider
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddingRequest {
+    pub input: Vec<String>,
+    /// Output dimensions, for models that can shorten their vectors
+    pub dimensions: Option<u32>,
+}
+
+/// Embedding vectors, in input order
+#[derive(Debug, Clone)]
+pub struct Embeddings {
+    pub model: String,
+    pub vectors: Vec<Vec<f32>>,
+    pub prompt_tokens: u32,
+}
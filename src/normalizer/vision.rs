@@ -0,0 +1,141 @@
+use super::errors::connector_error;
+use super::Client;
+use crate::settings::LlmBackendSettings;
+use anyhow::Result;
+use llm_connector::error::LlmConnectorError;
+use llm_connector::types::{ChatRequest, ImageSource, ImageUrl, MessageBlock};
+
+impl Client {
+    /// Check image input against the model and put image blocks in the shape
+    /// the provider's protocol expects
+    ///
+    /// Frontends convert every image to `MessageBlock::Image`. llm-connector
+    /// serializes blocks as-is, so OpenAI-compatible providers need them as
+    /// `image_url` parts (base64 as a data URL) while Anthropic keeps
+    /// `image` blocks. Models listed in models.yaml without `supports_vision`
+    /// are rejected; unlisted models are passed through.
+    pub(crate) fn prepare_images(&self, request: &mut ChatRequest) -> Result<()> {
+        let has_images = request
+            .messages
+            .iter()
+            .any(|message| message.content.iter().any(MessageBlock::is_image));
+        if !has_images {
+            return Ok(());
+        }
+
+        if let Some(info) = self.model_infos().into_iter().find(|info| info.id == request.model) {
+            if !info.supports_vision {
+                return Err(invalid_request(format!(
+                    "Model '{}' does not support image input",
+                    request.model
+                )));
+            }
+        }
+
+        match &self.backend {
+            LlmBackendSettings::Anthropic { .. } => {
+                for message in &mut request.messages {
+                    for block in &mut message.content {
+                        if let MessageBlock::ImageUrl { image_url } = block {
+                            *block = image_block_from_url(&image_url.url);
+                        }
+                    }
+                }
+            }
+            // llm-connector 的 Ollama / 阿里云原生协议只发送文本，图片会被静默丢弃
+            LlmBackendSettings::Ollama { .. } | LlmBackendSettings::Aliyun { .. } => {
+                return Err(invalid_request(format!(
+                    "Provider '{}' does not forward image input",
+                    self.backend.provider_name()
+                )));
+            }
+            _ => {
+                for message in &mut request.messages {
+                    for block in &mut message.content {
+                        if let MessageBlock::Image { source } = block {
+                            let url = match source {
+                                ImageSource::Base64 { media_type, data } => format!("data:{};base64,{}", media_type, data),
+                                ImageSource::Url { url } => url.clone(),
+                            };
+                            *block = MessageBlock::ImageUrl {
+                                image_url: ImageUrl { url, detail: None },
+                            };
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Image block for a data URL or a remote URL
+pub fn image_block_from_url(url: &str) -> MessageBlock {
+    if let Some((media_type, data)) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+    {
+        return MessageBlock::Image {
+            source: ImageSource::Base64 {
+                media_type: media_type.to_string(),
+                data: data.to_string(),
+            },
+        };
+    }
+
+    MessageBlock::Image {
+        source: ImageSource::Url { url: url.to_string() },
+    }
+}
+
+fn invalid_request(message: String) -> anyhow::Error {
+    connector_error("Image input rejected", LlmConnectorError::InvalidRequest(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm_connector::types::{Message, Role};
+
+    fn image_request(model: &str) -> ChatRequest {
+        ChatRequest {
+            model: model.to_string(),
+            messages: vec![Message::new(
+                Role::User,
+                vec![
+                    MessageBlock::text("What is this?"),
+                    image_block_from_url("data:image/png;base64,iVBORw0KGgo="),
+                ],
+            )],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prepare_images() {
+        let openai = Client::new(&LlmBackendSettings::OpenAI {
+            api_key: "key".to_string(),
+            base_url: None,
+            model: "gpt-4o".to_string(),
+        })
+        .unwrap();
+
+        let mut request = image_request("gpt-4o");
+        openai.prepare_images(&mut request).unwrap();
+        assert_eq!(
+            request.messages[0].content[1],
+            MessageBlock::ImageUrl {
+                image_url: ImageUrl {
+                    url: "data:image/png;base64,iVBORw0KGgo=".to_string(),
+                    detail: None
+                }
+            }
+        );
+
+        // Text-only model from models.yaml
+        let err = openai.prepare_images(&mut image_request("gpt-3.5-turbo")).unwrap_err();
+        assert!(err.to_string().contains("does not support image input"));
+        assert!(!crate::normalizer::is_failover_error(&err));
+    }
+}
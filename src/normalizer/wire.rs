@@ -0,0 +1,297 @@
+//! Request fields llm-connector does not serialize
+//!
+//! `ChatRequest` has `stop`, `user`, `seed` and `response_format`, but the
+//! connector's protocol request types leave them out, so they never reach
+//! the provider. [`WireProtocol`] wraps a protocol and adds them to the
+//! serialized body in the provider's own dialect. Fields a provider has no
+//! place for are dropped with a warning.
+
+use crate::settings::LlmBackendSettings;
+use async_trait::async_trait;
+use llm_connector::core::{GenericProvider, HttpClient, Protocol, Provider};
+use llm_connector::error::LlmConnectorError;
+use llm_connector::types::{ChatRequest, ChatResponse, ChatStream, ResponseFormat};
+use llm_connector::{AliyunProtocol, LlmClient};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Where a provider's request body takes the extra fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Dialect {
+    /// Top-level `stop`, `user`, `seed` and `response_format`
+    OpenAi,
+    /// DashScope `parameters.stop`, `parameters.seed` and `parameters.response_format`
+    DashScope,
+    /// `stop_sequences` and `metadata.user_id`
+    Anthropic,
+    /// Goes through llm-connector's own Ollama client, which cannot be extended
+    Ollama,
+    /// The mock provider reads `ChatRequest` itself
+    Local,
+}
+
+impl Dialect {
+    pub(super) fn of(backend: &LlmBackendSettings) -> Self {
+        match backend {
+            LlmBackendSettings::Aliyun { .. } => Dialect::DashScope,
+            LlmBackendSettings::Anthropic { .. } => Dialect::Anthropic,
+            LlmBackendSettings::Ollama { .. } => Dialect::Ollama,
+            LlmBackendSettings::Mock { .. } => Dialect::Local,
+            _ => Dialect::OpenAi,
+        }
+    }
+
+    fn supports(self, field: &str) -> bool {
+        match self {
+            Dialect::OpenAi | Dialect::Local => true,
+            Dialect::DashScope => field != "user",
+            Dialect::Anthropic => matches!(field, "stop" | "user"),
+            Dialect::Ollama => false,
+        }
+    }
+
+    /// Log the set fields of `request` this dialect cannot send
+    pub(super) fn warn_dropped(self, provider: &str, request: &ChatRequest) {
+        let set = [
+            ("stop", request.stop.is_some()),
+            ("user", request.user.is_some()),
+            ("seed", request.seed.is_some()),
+            ("response_format", request.response_format.is_some()),
+        ];
+        for (field, set) in set {
+            if set && !self.supports(field) {
+                tracing::warn!("⚠️ {} does not accept '{}', dropping it", provider, field);
+            }
+        }
+    }
+
+    /// Add the fields of `request` the connector left out of `body`
+    fn add_fields(self, body: &mut Value, request: &ChatRequest) {
+        let Some(body) = body.as_object_mut() else {
+            return;
+        };
+        let stop = request.stop.as_ref().map(|stop| json!(stop));
+        let user = request.user.as_ref().map(|user| json!(user));
+        let seed = request.seed.map(|seed| json!(seed));
+        let response_format = request.response_format.as_ref().map(response_format_value);
+        match self {
+            Dialect::OpenAi => {
+                for (field, value) in [("stop", stop), ("user", user), ("seed", seed), ("response_format", response_format)] {
+                    if let Some(value) = value {
+                        body.entry(field).or_insert(value);
+                    }
+                }
+            }
+            Dialect::DashScope => {
+                let parameters = body.entry("parameters").or_insert_with(|| json!({}));
+                if let Some(parameters) = parameters.as_object_mut() {
+                    for (field, value) in [("stop", stop), ("seed", seed), ("response_format", response_format)] {
+                        if let Some(value) = value {
+                            parameters.entry(field).or_insert(value);
+                        }
+                    }
+                }
+            }
+            Dialect::Anthropic => {
+                if let Some(stop) = stop {
+                    body.entry("stop_sequences").or_insert(stop);
+                }
+                if let Some(user) = user {
+                    body.entry("metadata").or_insert_with(|| json!({}))["user_id"] = user;
+                }
+            }
+            Dialect::Ollama | Dialect::Local => {}
+        }
+    }
+}
+
+/// `response_format` for a `ChatRequest`
+///
+/// `ResponseFormat` only has a type, so a format with more to it (a
+/// `json_schema` and its schema) is kept as its JSON text in `format_type`.
+pub(super) fn response_format(value: &Value) -> ResponseFormat {
+    let format_type = match value["type"].as_str() {
+        Some(format_type) if value.as_object().is_some_and(|object| object.len() == 1) => format_type.to_string(),
+        _ => value.to_string(),
+    };
+    ResponseFormat { format_type }
+}
+
+/// The OpenAI `response_format` object a `ChatRequest` carries
+pub(super) fn response_format_value(format: &ResponseFormat) -> Value {
+    if format.format_type.starts_with('{') {
+        if let Ok(value) = serde_json::from_str(&format.format_type) {
+            return value;
+        }
+    }
+    json!({"type": format.format_type})
+}
+
+/// A protocol whose requests also carry the fields the connector drops
+#[derive(Clone)]
+pub(super) struct WireProtocol<P> {
+    inner: P,
+    dialect: Dialect,
+}
+
+#[async_trait]
+impl<P: Protocol> Protocol for WireProtocol<P> {
+    type Request = Value;
+    type Response = P::Response;
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn chat_endpoint(&self, base_url: &str) -> String {
+        self.inner.chat_endpoint(base_url)
+    }
+
+    fn models_endpoint(&self, base_url: &str) -> Option<String> {
+        self.inner.models_endpoint(base_url)
+    }
+
+    fn build_request(&self, request: &ChatRequest) -> Result<Value, LlmConnectorError> {
+        let mut body = serde_json::to_value(self.inner.build_request(request)?)
+            .map_err(|e| LlmConnectorError::InvalidRequest(format!("Failed to serialize request: {}", e)))?;
+        self.dialect.add_fields(&mut body, request);
+        Ok(body)
+    }
+
+    fn parse_response(&self, response: &str) -> Result<ChatResponse, LlmConnectorError> {
+        self.inner.parse_response(response)
+    }
+
+    fn parse_models(&self, response: &str) -> Result<Vec<String>, LlmConnectorError> {
+        self.inner.parse_models(response)
+    }
+
+    fn map_error(&self, status: u16, body: &str) -> LlmConnectorError {
+        self.inner.map_error(status, body)
+    }
+
+    fn auth_headers(&self) -> Vec<(String, String)> {
+        self.inner.auth_headers()
+    }
+
+    async fn parse_stream_response(&self, response: reqwest::Response) -> Result<ChatStream, LlmConnectorError> {
+        self.inner.parse_stream_response(response).await
+    }
+}
+
+/// Client for a provider built on llm-connector's `GenericProvider`
+pub(super) fn client<P: Protocol>(provider: GenericProvider<P>, dialect: Dialect) -> LlmClient {
+    let protocol = WireProtocol { inner: provider.protocol().clone(), dialect };
+    LlmClient::from_provider(Arc::new(GenericProvider::new(protocol, provider.client().clone())))
+}
+
+/// Client for Aliyun DashScope
+pub(super) fn aliyun_client(api_key: &str) -> Result<LlmClient, LlmConnectorError> {
+    let provider = llm_connector::providers::aliyun(api_key)?;
+    Ok(LlmClient::from_provider(Arc::new(DashScope {
+        protocol: WireProtocol { inner: provider.protocol().clone(), dialect: Dialect::DashScope },
+        client: provider.client().clone(),
+    })))
+}
+
+/// Same as llm-connector's Aliyun provider, which is not generic: streaming
+/// requests need the `X-DashScope-SSE` header
+struct DashScope {
+    protocol: WireProtocol<AliyunProtocol>,
+    client: HttpClient,
+}
+
+#[async_trait]
+impl Provider for DashScope {
+    fn name(&self) -> &str {
+        self.protocol.name()
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmConnectorError> {
+        let body = self.protocol.build_request(request)?;
+        let url = self.protocol.chat_endpoint(self.client.base_url());
+        let response = self.client.post(&url, &body).await?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| LlmConnectorError::NetworkError(e.to_string()))?;
+        if !status.is_success() {
+            return Err(self.protocol.map_error(status.as_u16(), &text));
+        }
+        self.protocol.parse_response(&text)
+    }
+
+    async fn chat_stream(&self, request: &ChatRequest) -> Result<ChatStream, LlmConnectorError> {
+        let mut request = request.clone();
+        request.stream = Some(true);
+        let body = self.protocol.build_request(&request)?;
+        let url = self.protocol.chat_endpoint(self.client.base_url());
+        let client = self.client.clone().with_headers(self.protocol.inner.streaming_headers().into_iter().collect());
+        let response = client.stream(&url, &body).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.map_err(|e| LlmConnectorError::NetworkError(e.to_string()))?;
+            return Err(self.protocol.map_error(status.as_u16(), &text));
+        }
+        self.protocol.parse_stream_response(response).await
+    }
+
+    async fn models(&self) -> Result<Vec<String>, LlmConnectorError> {
+        Err(LlmConnectorError::UnsupportedOperation(
+            "Aliyun DashScope does not support model listing".to_string(),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm_connector::types::Message;
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "m".to_string(),
+            messages: vec![Message::user("hi")],
+            stop: Some(vec!["END".to_string()]),
+            user: Some("alice".to_string()),
+            seed: Some(7),
+            response_format: Some(ResponseFormat { format_type: "json_object".to_string() }),
+            ..Default::default()
+        }
+    }
+
+    fn body<P: Protocol>(inner: P, dialect: Dialect) -> Value {
+        WireProtocol { inner, dialect }.build_request(&request()).unwrap()
+    }
+
+    #[test]
+    fn test_dropped_fields_reach_the_wire() {
+        let openai = body(llm_connector::OpenAIProtocol::new("k"), Dialect::OpenAi);
+        assert_eq!(openai["stop"], json!(["END"]));
+        assert_eq!(openai["user"], "alice");
+        assert_eq!(openai["seed"], 7);
+        assert_eq!(openai["response_format"], json!({"type": "json_object"}));
+        assert_eq!(openai["messages"][0]["content"], "hi");
+
+        let zhipu = body(llm_connector::ZhipuProtocol::new_openai_compatible("k"), Dialect::OpenAi);
+        assert_eq!(zhipu["seed"], 7);
+
+        let aliyun = body(AliyunProtocol::new("k"), Dialect::DashScope);
+        assert_eq!(aliyun["parameters"]["stop"], json!(["END"]));
+        assert_eq!(aliyun["parameters"]["seed"], 7);
+        assert_eq!(aliyun["parameters"]["response_format"]["type"], "json_object");
+        assert!(aliyun.get("user").is_none());
+
+        let anthropic = body(llm_connector::AnthropicProtocol::new("k"), Dialect::Anthropic);
+        assert_eq!(anthropic["stop_sequences"], json!(["END"]));
+        assert_eq!(anthropic["metadata"]["user_id"], "alice");
+        assert!(anthropic.get("seed").is_none() && anthropic.get("response_format").is_none());
+
+        // json_schema 连同 schema 一起上传
+        let schema = json!({"type": "json_schema", "json_schema": {"name": "r", "schema": {"type": "object"}}});
+        assert_eq!(response_format_value(&response_format(&schema)), schema);
+        assert_eq!(response_format(&json!({"type": "json_object"})).format_type, "json_object");
+    }
+}
@@ -75,6 +75,11 @@ impl ProviderConfig {
                 model: model.clone(),
                 base_url: None,
             },
+            LlmBackendSettings::Mock { model, .. } => Self {
+                api_key: String::new(),
+                model: model.clone(),
+                base_url: None,
+            },
         }
     }
 }
@@ -211,6 +216,14 @@ impl ProviderRegistry {
                 requires_api_key: true,
                 requires_base_url: false,
             }),
+            "mock" => Some(ProviderInfo {
+                name: "mock",
+                default_model: "mock",
+                env_var: "",
+                api_type: ApiType::Native,
+                requires_api_key: false,
+                requires_base_url: false,
+            }),
             _ => None,
         }
     }
@@ -229,6 +242,7 @@ impl ProviderRegistry {
             "longcat",
             "moonshot",
             "minimax",
+            "mock",
         ]
     }
 }
@@ -256,4 +270,5 @@ pub mod volcengine;
 pub mod tencent;
 pub mod longcat;
 pub mod moonshot;
+// mock 没有单独的实现模块：它由 normalizer 按 LlmBackendSettings::Mock 的配置构建
 
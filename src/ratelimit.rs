@@ -0,0 +1,442 @@
+//! Rate limiting for the inference endpoints
+//!
+//! Requests per minute and prompt tokens per minute are token buckets that
+//! refill continuously; concurrent streams are a plain counter released when
+//! the response body is dropped. Limits apply globally, per API key and per
+//! model pattern, and a request must fit into every bucket that applies.
+
+use crate::api::auth::{Frontend, KeyScope};
+use crate::api::inspect::RequestInfo;
+use crate::api::AppState;
+use crate::service::glob_match;
+use crate::settings::{RateLimit, RateLimitSettings, Settings};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Time source, replaceable in tests
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// What a bucket is shared by
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LimitScope {
+    Global,
+    Key(String),
+    Model(String),
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, now: Instant) -> Self {
+        Self {
+            capacity: capacity as f64,
+            available: capacity as f64,
+            updated: now,
+        }
+    }
+
+    /// Refill for the time since the last update (a full bucket per minute)
+    fn refill(&mut self, capacity: u32, now: Instant) {
+        let capacity = capacity as f64;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * capacity / 60.0).min(capacity);
+        self.capacity = capacity;
+        self.updated = now;
+    }
+
+    /// Time until `amount` is available; a cost above the capacity waits for a full bucket
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * 60.0 / self.capacity)
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available = (self.available - amount.min(self.capacity)).max(0.0);
+    }
+
+    fn status(&self) -> BucketStatus {
+        BucketStatus {
+            limit: self.capacity as u32,
+            remaining: self.available.floor() as u32,
+            reset: self.wait_for(self.capacity),
+        }
+    }
+}
+
+/// State of the most restrictive bucket of one kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again
+    pub reset: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub requests: Option<BucketStatus>,
+    pub tokens: Option<BucketStatus>,
+}
+
+impl RateLimitStatus {
+    fn merge(slot: &mut Option<BucketStatus>, status: BucketStatus) {
+        if slot.is_none_or(|current| status.remaining < current.remaining) {
+            *slot = Some(status);
+        }
+    }
+}
+
+/// Why a request was turned away
+#[derive(Debug)]
+pub struct Rejection {
+    pub message: String,
+    pub retry_after: Duration,
+    pub status: RateLimitStatus,
+}
+
+/// Holds a concurrent stream slot until dropped
+pub struct StreamPermit {
+    limiter: Arc<RateLimiter>,
+    scopes: Vec<LimitScope>,
+}
+
+impl std::fmt::Debug for StreamPermit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamPermit").field("scopes", &self.scopes).finish_non_exhaustive()
+    }
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        for scope in &self.scopes {
+            if let Some(count) = state.streams.get_mut(scope) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct LimiterState {
+    requests: HashMap<LimitScope, TokenBucket>,
+    tokens: HashMap<LimitScope, TokenBucket>,
+    streams: HashMap<LimitScope, u32>,
+}
+
+pub struct RateLimiter {
+    clock: Arc<dyn Clock>,
+    state: Mutex<LimiterState>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+}
+
+impl RateLimiter {
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    /// Admit a request costing one request and `tokens` prompt tokens
+    ///
+    /// Nothing is taken unless every applicable bucket has room, so a
+    /// rejected request does not count against any limit.
+    pub fn acquire(
+        self: &Arc<Self>,
+        limits: &[(LimitScope, RateLimit)],
+        tokens: u32,
+        stream: bool,
+    ) -> Result<(RateLimitStatus, Option<StreamPermit>), Rejection> {
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let mut wait = Duration::ZERO;
+        let mut reason = None;
+
+        for (scope, limit) in limits {
+            if let Some(rpm) = limit.requests_per_minute {
+                let bucket = state.requests.entry(scope.clone()).or_insert_with(|| TokenBucket::new(rpm, now));
+                bucket.refill(rpm, now);
+                let bucket_wait = bucket.wait_for(1.0);
+                if bucket_wait > wait {
+                    wait = bucket_wait;
+                    reason = Some(format!("Rate limit of {} requests per minute reached ({})", rpm, scope));
+                }
+            }
+            if let Some(tpm) = limit.tokens_per_minute {
+                let bucket = state.tokens.entry(scope.clone()).or_insert_with(|| TokenBucket::new(tpm, now));
+                bucket.refill(tpm, now);
+                let bucket_wait = bucket.wait_for(tokens as f64);
+                if bucket_wait > wait {
+                    wait = bucket_wait;
+                    reason = Some(format!("Rate limit of {} tokens per minute reached ({})", tpm, scope));
+                }
+            }
+            if let (true, Some(max)) = (stream, limit.max_concurrent_streams) {
+                if state.streams.get(scope).copied().unwrap_or(0) >= max && reason.is_none() {
+                    wait = wait.max(Duration::from_secs(1));
+                    reason = Some(format!("Limit of {} concurrent streams reached ({})", max, scope));
+                }
+            }
+        }
+
+        if let Some(message) = reason {
+            return Err(Rejection {
+                message,
+                retry_after: wait,
+                status: Self::status(state, limits),
+            });
+        }
+
+        let mut stream_scopes = Vec::new();
+        for (scope, limit) in limits {
+            if let Some(bucket) = limit.requests_per_minute.and(state.requests.get_mut(scope)) {
+                bucket.take(1.0);
+            }
+            if let Some(bucket) = limit.tokens_per_minute.and(state.tokens.get_mut(scope)) {
+                bucket.take(tokens as f64);
+            }
+            if stream && limit.max_concurrent_streams.is_some() {
+                *state.streams.entry(scope.clone()).or_default() += 1;
+                stream_scopes.push(scope.clone());
+            }
+        }
+
+        let permit = (!stream_scopes.is_empty()).then(|| StreamPermit {
+            limiter: Arc::clone(self),
+            scopes: stream_scopes,
+        });
+        Ok((Self::status(state, limits), permit))
+    }
+
+    fn status(state: &LimiterState, limits: &[(LimitScope, RateLimit)]) -> RateLimitStatus {
+        let mut status = RateLimitStatus::default();
+        for (scope, limit) in limits {
+            if let Some(bucket) = limit.requests_per_minute.and(state.requests.get(scope)) {
+                RateLimitStatus::merge(&mut status.requests, bucket.status());
+            }
+            if let Some(bucket) = limit.tokens_per_minute.and(state.tokens.get(scope)) {
+                RateLimitStatus::merge(&mut status.tokens, bucket.status());
+            }
+        }
+        status
+    }
+}
+
+impl std::fmt::Display for LimitScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitScope::Global => write!(f, "global"),
+            LimitScope::Key(name) => write!(f, "key '{}'", name),
+            LimitScope::Model(pattern) => write!(f, "model '{}'", pattern),
+        }
+    }
+}
+
+/// Limits that apply to a request for `model` made with `key`
+fn applicable_limits(settings: &Settings, key: Option<(String, RateLimit)>, model: Option<&str>) -> Vec<(LimitScope, RateLimit)> {
+    let limits = &settings.rate_limits;
+    let mut applicable = Vec::new();
+    if !limits.global.is_unlimited() {
+        applicable.push((LimitScope::Global, limits.global.clone()));
+    }
+    if let Some((name, own)) = key {
+        let limit = own.or(&limits.per_key);
+        if !limit.is_unlimited() {
+            applicable.push((LimitScope::Key(name), limit));
+        }
+    }
+    if let Some(model) = model {
+        for entry in &limits.models {
+            if glob_match(&entry.model, model) && !entry.limit.is_unlimited() {
+                applicable.push((LimitScope::Model(entry.model.clone()), entry.limit.clone()));
+            }
+        }
+    }
+    applicable
+}
+
+fn set_header(headers: &mut HeaderMap, name: String, value: impl ToString) {
+    if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::from_str(&value.to_string())) {
+        headers.insert(name, value);
+    }
+}
+
+/// `x-ratelimit-*` (OpenAI) or `anthropic-ratelimit-*` headers for `status`
+fn insert_headers(headers: &mut HeaderMap, frontend: Frontend, status: &RateLimitStatus) {
+    for (kind, bucket) in [("requests", status.requests), ("tokens", status.tokens)] {
+        let Some(bucket) = bucket else { continue };
+        if frontend == Frontend::Anthropic {
+            let reset = chrono::Utc::now() + chrono::Duration::from_std(bucket.reset).unwrap_or_default();
+            set_header(headers, format!("anthropic-ratelimit-{}-limit", kind), bucket.limit);
+            set_header(headers, format!("anthropic-ratelimit-{}-remaining", kind), bucket.remaining);
+            set_header(
+                headers,
+                format!("anthropic-ratelimit-{}-reset", kind),
+                reset.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            );
+        } else {
+            set_header(headers, format!("x-ratelimit-limit-{}", kind), bucket.limit);
+            set_header(headers, format!("x-ratelimit-remaining-{}", kind), bucket.remaining);
+            set_header(headers, format!("x-ratelimit-reset-{}", kind), format!("{:.3}s", bucket.reset.as_secs_f64()));
+        }
+    }
+}
+
+/// Rate limit middleware for the inference endpoints of one frontend
+///
+/// Runs inside [`require_api_key`](crate::api::auth::require_api_key), so
+/// only authenticated requests take from the buckets and the key's own limits
+/// come from its [`KeyScope`].
+pub async fn enforce(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(info) = RequestInfo::inference(&request) else {
+        return next.run(request).await;
+    };
+    let limits = {
+        let config = state.config.read().await;
+        if config.rate_limits == RateLimitSettings::default() {
+            drop(config);
+            return next.run(request).await;
+        }
+        let key = request.extensions().get::<KeyScope>().map(|scope| (scope.name.clone(), scope.rate_limit.clone()));
+        applicable_limits(&config, key, info.model.as_deref())
+    };
+    let (frontend, tokens, stream) = (info.frontend, info.prompt_tokens, info.stream);
+
+    let (status, permit) = match state.rate_limiter.acquire(&limits, tokens, stream) {
+        Ok(admitted) => admitted,
+        Err(rejection) => {
+            warn!("🚦 {}", rejection.message);
+            let mut response = frontend.error(StatusCode::TOO_MANY_REQUESTS, rejection.message);
+            let headers = response.headers_mut();
+            set_header(headers, RETRY_AFTER.to_string(), rejection.retry_after.as_secs_f64().ceil().max(1.0));
+            insert_headers(headers, frontend, &rejection.status);
+            return response;
+        }
+    };
+
+    let mut response = next.run(request).await;
+    insert_headers(response.headers_mut(), frontend, &status);
+    match permit {
+        // 流式响应结束（或客户端断开）时才释放并发名额
+        Some(permit) => response.map(|body| {
+            Body::from_stream(body.into_data_stream().map(move |chunk| {
+                let _ = &permit;
+                chunk
+            }))
+        }),
+        None => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockClock(Mutex<Instant>);
+
+    impl MockClock {
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn limiter() -> (Arc<MockClock>, Arc<RateLimiter>) {
+        let clock = Arc::new(MockClock(Mutex::new(Instant::now())));
+        (clock.clone(), Arc::new(RateLimiter::with_clock(clock)))
+    }
+
+    fn limit(rpm: Option<u32>, tpm: Option<u32>, streams: Option<u32>) -> RateLimit {
+        RateLimit {
+            requests_per_minute: rpm,
+            tokens_per_minute: tpm,
+            max_concurrent_streams: streams,
+        }
+    }
+
+    #[test]
+    fn test_token_buckets() {
+        let (clock, limiter) = limiter();
+        let limits = vec![
+            (LimitScope::Global, limit(Some(60), None, None)),
+            (LimitScope::Key("ci".to_string()), limit(Some(2), Some(1000), None)),
+        ];
+
+        let (status, _) = limiter.acquire(&limits, 400, false).unwrap();
+        assert_eq!(status.requests.unwrap().remaining, 1);
+        assert_eq!(status.tokens.unwrap().remaining, 600);
+        limiter.acquire(&limits, 400, false).unwrap();
+
+        // The key's request bucket is empty; one request refills in 30s
+        let rejection = limiter.acquire(&limits, 100, false).unwrap_err();
+        assert!(rejection.message.contains("key 'ci'"));
+        assert_eq!(rejection.retry_after, Duration::from_secs(30));
+        assert_eq!(rejection.status.requests.unwrap().remaining, 0);
+
+        // Rejected requests are not counted anywhere
+        clock.advance(Duration::from_secs(30));
+        let (status, _) = limiter.acquire(&limits, 100, false).unwrap();
+        assert_eq!(status.tokens.unwrap().remaining, 200 + 500 - 100);
+
+        // Not enough tokens left for a large prompt
+        clock.advance(Duration::from_secs(60));
+        limiter.acquire(&limits, 1000, false).unwrap();
+        let rejection = limiter.acquire(&limits, 500, false).unwrap_err();
+        assert!(rejection.message.contains("tokens per minute"));
+        assert_eq!(rejection.retry_after, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_concurrent_streams() {
+        let (_, limiter) = limiter();
+        let limits = vec![(LimitScope::Model("gpt-*".to_string()), limit(None, None, Some(1)))];
+
+        let (_, permit) = limiter.acquire(&limits, 0, true).unwrap();
+        assert!(permit.is_some());
+        assert!(limiter.acquire(&limits, 0, false).is_ok());
+        assert!(limiter.acquire(&limits, 0, true).is_err());
+        drop(permit);
+        assert!(limiter.acquire(&limits, 0, true).is_ok());
+    }
+}
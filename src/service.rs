@@ -1,58 +1,285 @@
-use crate::normalizer::{Client, Model, Response};
-use crate::settings::LlmBackendSettings;
-use anyhow::Result;
+use crate::normalizer::{is_failover_error, ChatOptions, Client, EmbeddingRequest, Embeddings, Model, Response};
+use crate::models::ModelInfo;
+use crate::settings::{BackendRouteSettings, LlmBackendSettings};
+use anyhow::{anyhow, Result};
 use llm_connector::types::Tool;
 use llm_connector::StreamFormat;
+use std::future::Future;
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::warn;
+
+tokio::task_local! {
+    /// Backends the request being handled may use
+    static ROUTE_FILTER: RouteFilter;
+}
+
+/// Backends a request may be routed to
+///
+/// Set per request by the auth middleware from the caller's key scope and
+/// narrowed by the budget middleware. Routing and failover both skip
+/// backends it does not allow.
+#[derive(Debug, Clone, Default)]
+pub struct RouteFilter {
+    /// Backend names the caller may use; empty means any
+    pub providers: Vec<String>,
+    /// Backends whose budget is used up
+    pub excluded: Vec<String>,
+}
+
+impl RouteFilter {
+    pub fn allows(&self, provider: &str) -> bool {
+        (self.providers.is_empty() || self.providers.iter().any(|allowed| allowed == provider))
+            && !self.excluded.iter().any(|excluded| excluded == provider)
+    }
+
+    /// Filter of the request being handled (allows everything outside a request)
+    pub fn current() -> Self {
+        ROUTE_FILTER.try_with(Clone::clone).unwrap_or_default()
+    }
+
+    /// Run `f` with this filter applied to every routing decision it makes
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        ROUTE_FILTER.scope(self, f).await
+    }
+}
+
+/// Output of a request together with the provider that produced it
+pub struct Served<T> {
+    pub provider: String,
+    pub output: T,
+}
+
+/// A single upstream provider the service can dispatch to
+struct Backend {
+    name: String,
+    settings: LlmBackendSettings,
+    client: Client,
+    model: String,
+    /// Model names or glob patterns explicitly routed to this backend
+    models: Vec<String>,
+}
+
+impl Backend {
+    /// Whether `model` should be routed to this backend
+    fn serves(&self, model: &str) -> bool {
+        self.models.iter().any(|pattern| glob_match(pattern, model)) || self.client.knows_model(model)
+    }
+
+    /// Whether `prefix` (from a `prefix/model` name) refers to this backend
+    fn is_named(&self, prefix: &str) -> bool {
+        self.name == prefix || self.settings.provider_name() == prefix
+    }
+}
+
+/// Match `name` against a glob pattern supporting `*` and `?`
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
 
 /// Service layer - Business logic for LLM operations
 ///
 /// This layer sits between handlers (HTTP) and client (LLM communication).
 /// It handles:
 /// - Model selection and validation
+/// - Routing requests to the backend that serves the requested model
 /// - Delegating to the appropriate client methods
 /// - Business-level error handling
+///
+/// Backends are kept in priority order. In single mode the first backend is
+/// `llm_backend` followed by any configured routes; in multi mode there is one
+/// backend per enabled provider. A request goes to the backends that serve
+/// its model and fails over between them in that order.
 pub struct Service {
-    client: Client,
-    #[allow(dead_code)]
-    model: String,
+    backends: Vec<Backend>,
 }
 
 impl Service {
     /// Create a new service with the specified backend configuration
     pub fn new(config: &LlmBackendSettings) -> Result<Self> {
+        Self::with_routes(config, &[])
+    }
+
+    /// Create a service for `config` plus extra model-routed backends
+    pub fn with_routes(config: &LlmBackendSettings, routes: &[BackendRouteSettings]) -> Result<Self> {
+        let mut service = Self::empty();
+        service.add_backend(config.provider_name(), config, &[])?;
+        for route in routes {
+            service.add_backend(&route.name, &route.backend, &route.models)?;
+        }
+        Ok(service)
+    }
+
+    /// Create a service without any backends
+    ///
+    /// Requests fail until a backend is added. Used by multi mode before any
+    /// provider has been configured.
+    pub fn empty() -> Self {
+        Self { backends: Vec::new() }
+    }
+
+    /// Append a backend; backends added first take precedence
+    ///
+    /// `models` lists model names or glob patterns routed to this backend in
+    /// addition to the models known for its provider.
+    pub fn add_backend(&mut self, name: &str, config: &LlmBackendSettings, models: &[String]) -> Result<()> {
         let client = Client::new(config)?;
-        let model = match config {
-            LlmBackendSettings::OpenAI { model, .. } => model.clone(),
-            LlmBackendSettings::Anthropic { model, .. } => model.clone(),
-            LlmBackendSettings::Ollama { model, .. } => model.clone(),
-            LlmBackendSettings::Aliyun { model, .. } => model.clone(),
-            LlmBackendSettings::Zhipu { model, .. } => model.clone(),
-            LlmBackendSettings::Volcengine { model, .. } => model.clone(),
-            LlmBackendSettings::Tencent { model, .. } => model.clone(),
-            LlmBackendSettings::Longcat { model, .. } => model.clone(),
-            LlmBackendSettings::Moonshot { model, .. } => model.clone(),
-            LlmBackendSettings::Minimax { model, .. } => model.clone(),
+        self.backends.push(Backend {
+            name: name.to_string(),
+            settings: config.clone(),
+            client,
+            model: config.get_model(),
+            models: models.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Settings of the backend that currently serves requests
+    pub fn primary_settings(&self) -> Option<&LlmBackendSettings> {
+        self.backends.first().map(|b| &b.settings)
+    }
+
+    /// Names of all configured backends in priority order
+    pub fn backend_names(&self) -> Vec<&str> {
+        self.backends.iter().map(|b| b.name.as_str()).collect()
+    }
+
+    /// Order backends for a request and pick the model each should use
+    ///
+    /// - `name/model` sends `model` to the backends with that name or provider type
+    /// - otherwise the backends whose patterns or model lists include the model
+    ///   are tried in priority order; failover never swaps in another model
+    /// - a model no backend knows goes unchanged to the primary backend only
+    /// - without a model every backend is a candidate with its own default model
+    ///
+    /// Backends `filter` does not allow are left out before any of this, so
+    /// "primary" means the first allowed backend.
+    fn route<'a>(&'a self, model: Option<&'a str>, filter: &RouteFilter) -> Vec<(&'a Backend, String)> {
+        let allowed = || self.backends.iter().filter(|b| filter.allows(&b.name));
+        let candidates: Vec<(&Backend, &str)> = match model {
+            None => allowed().map(|b| (b, b.model.as_str())).collect(),
+            Some(model) => {
+                let named: Vec<_> = model
+                    .split_once('/')
+                    .map(|(prefix, rest)| allowed().filter(|b| b.is_named(prefix)).map(|b| (b, rest)).collect())
+                    .unwrap_or_default();
+                let serving: Vec<_> = if named.is_empty() {
+                    allowed().filter(|b| b.serves(model)).map(|b| (b, model)).collect()
+                } else {
+                    named
+                };
+                if serving.is_empty() {
+                    allowed().next().map(|b| (b, model)).into_iter().collect()
+                } else {
+                    serving
+                }
+            }
         };
 
-        Ok(Self { client, model })
+        candidates
+            .into_iter()
+            .map(|(backend, model)| (backend, backend.client.resolve_model(model, &backend.model)))
+            .collect()
+    }
+
+    /// Upstream model the first routed backend would use for `model`
+    pub fn resolve_model(&self, model: Option<&str>) -> Option<String> {
+        self.route(model, &RouteFilter::current()).into_iter().next().map(|(_, model)| model)
+    }
+
+    /// Name of the backend a request for `model` is routed to first
+    pub fn provider_for(&self, model: Option<&str>) -> Option<String> {
+        self.providers_for(model).into_iter().next()
+    }
+
+    /// Names of the backends a request for `model` may be served by, in failover order
+    pub fn providers_for(&self, model: Option<&str>) -> Vec<String> {
+        self.route(model, &RouteFilter::current()).into_iter().map(|(backend, _)| backend.name.clone()).collect()
+    }
+
+    /// Run `call` against the routed backends in order until one succeeds
+    ///
+    /// Only errors classified by [`is_failover_error`] move on to the next
+    /// backend; anything else is returned immediately. Backends the request's
+    /// [`RouteFilter`] does not allow are never tried.
+    async fn with_failover<'a, T, F, Fut>(&'a self, model: Option<&'a str>, call: F) -> Result<Served<T>>
+    where
+        F: Fn(&'a Client, String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let candidates = self.route(model, &RouteFilter::current());
+        if candidates.is_empty() && !self.backends.is_empty() {
+            return Err(anyhow!("No provider this request may use is available"));
+        }
+        Self::run_failover(candidates, call).await
+    }
+
+    /// Run `call` against `candidates` in order until one succeeds
+    async fn run_failover<'a, T, F, Fut>(candidates: Vec<(&'a Backend, String)>, call: F) -> Result<Served<T>>
+    where
+        F: Fn(&'a Client, String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        for (index, (backend, backend_model)) in candidates.iter().enumerate() {
+            match call(&backend.client, backend_model.clone()).await {
+                Ok(output) => {
+                    return Ok(Served {
+                        provider: backend.name.clone(),
+                        output,
+                    })
+                }
+                Err(e) if is_failover_error(&e) && index + 1 < candidates.len() => {
+                    warn!(
+                        "⚠️ Provider '{}' failed ({}), failing over to '{}'",
+                        backend.name,
+                        e,
+                        candidates[index + 1].0.name
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(anyhow!("No enabled providers configured"))
     }
 
     /// Chat with a specific model (non-streaming)
     ///
     /// If model is None, uses the default model from configuration.
-    #[allow(dead_code)]
     pub async fn chat(
         &self,
         model: Option<&str>,
         messages: Vec<llm_connector::types::Message>,
         tools: Option<Vec<Tool>>,
-    ) -> Result<Response> {
-        let requested = model.unwrap_or(&self.model);
-        let backend_model = self
-            .client
-            .resolve_model(requested, &self.model);
-        self.client.chat(&backend_model, messages, tools).await
+        options: &ChatOptions,
+    ) -> Result<Served<Response>> {
+        self.with_failover(model, |client, model| {
+            let messages = messages.clone();
+            let tools = tools.clone();
+            let options = options.clone();
+            async move { client.chat(&model, messages, tools, &options).await }
+        })
+        .await
     }
 
     /// Chat with streaming (Ollama format)
@@ -64,65 +291,294 @@ impl Service {
         model: Option<&str>,
         messages: Vec<llm_connector::types::Message>,
         format: StreamFormat,
-    ) -> Result<UnboundedReceiverStream<String>> {
-        let requested = model.unwrap_or(&self.model);
-        let backend_model = self
-            .client
-            .resolve_model(requested, &self.model);
-        self.client
-            .chat_stream_with_format(&backend_model, messages, format)
-            .await
+    ) -> Result<Served<UnboundedReceiverStream<String>>> {
+        self.with_failover(model, |client, model| {
+            let messages = messages.clone();
+            async move { client.chat_stream_with_format(&model, messages, format).await }
+        })
+        .await
     }
 
     /// Chat with streaming (Ollama format) with tools support
     ///
     /// If model is None, uses the default model from configuration.
-    #[allow(dead_code)]
     pub async fn chat_stream_ollama_with_tools(
         &self,
         model: Option<&str>,
         messages: Vec<llm_connector::types::Message>,
         tools: Option<Vec<llm_connector::types::Tool>>,
+        options: &ChatOptions,
         format: StreamFormat,
-    ) -> Result<UnboundedReceiverStream<String>> {
-        let requested = model.unwrap_or(&self.model);
-        let backend_model = self
-            .client
-            .resolve_model(requested, &self.model);
-        self.client
-            .chat_stream_with_format_and_tools(&backend_model, messages, tools, format)
-            .await
+    ) -> Result<Served<UnboundedReceiverStream<String>>> {
+        self.with_failover(model, |client, model| {
+            let messages = messages.clone();
+            let tools = tools.clone();
+            let options = options.clone();
+            async move {
+                client
+                    .chat_stream_with_format_and_tools(&model, messages, tools, &options, format)
+                    .await
+            }
+        })
+        .await
     }
 
     /// Chat with streaming (OpenAI format)
     ///
     /// If model is None, uses the default model from configuration.
-    #[allow(dead_code)]
     pub async fn chat_stream_openai(
         &self,
         model: Option<&str>,
         messages: Vec<llm_connector::types::Message>,
         tools: Option<Vec<Tool>>,
+        options: &ChatOptions,
         format: StreamFormat,
-    ) -> Result<UnboundedReceiverStream<String>> {
-        let requested = model.unwrap_or(&self.model);
-        let backend_model = self
-            .client
-            .resolve_model(requested, &self.model);
-        self.client
-            .chat_stream_openai(&backend_model, messages, tools, format)
-            .await
+    ) -> Result<Served<UnboundedReceiverStream<String>>> {
+        self.with_failover(model, |client, model| {
+            let messages = messages.clone();
+            let tools = tools.clone();
+            let options = options.clone();
+            async move { client.chat_stream_openai(&model, messages, tools, &options, format).await }
+        })
+        .await
+    }
+
+    /// Order the backends that can embed with a model
+    ///
+    /// Vectors from different models are not comparable, so unlike chat there
+    /// is no failover to another model: only backends that list the model
+    /// (or are named by a `name/model` prefix) are tried. An unknown model
+    /// goes unchanged to the first backend with an embeddings endpoint, and
+    /// no model means that backend's first listed embedding model.
+    fn route_embeddings<'a>(&'a self, model: Option<&str>) -> Vec<(&'a Backend, String)> {
+        let filter = RouteFilter::current();
+        let allowed: Vec<&Backend> = self.backends.iter().filter(|b| filter.allows(&b.name)).collect();
+        let mut embedders = allowed.iter().copied().filter(|b| b.client.supports_embeddings());
+
+        let Some(model) = model else {
+            return embedders
+                .find_map(|b| b.client.default_embedding_model().map(|model| (b, model)))
+                .into_iter()
+                .collect();
+        };
+
+        if let Some((prefix, rest)) = model.split_once('/') {
+            if let Some(backend) = allowed.iter().find(|b| b.is_named(prefix)) {
+                return vec![(*backend, rest.to_string())];
+            }
+        }
+
+        let known: Vec<_> = allowed
+            .iter()
+            .copied()
+            .filter(|b| b.client.supports_embeddings() && b.client.knows_embedding_model(model))
+            .map(|b| (b, model.to_string()))
+            .collect();
+        if !known.is_empty() {
+            return known;
+        }
+        embedders.next().map(|b| (b, model.to_string())).into_iter().collect()
     }
 
-    /// List available models
+    /// Create embeddings for a batch of inputs
+    ///
+    /// If model is None, uses the first embedding model of the first backend
+    /// that supports embeddings.
+    pub async fn embed(&self, model: Option<&str>, request: &EmbeddingRequest) -> Result<Served<Embeddings>> {
+        let candidates = self.route_embeddings(model);
+        if candidates.is_empty() {
+            return Err(anyhow!("No configured provider supports embeddings"));
+        }
+        Self::run_failover(candidates, |client, model| async move { client.embed(&model, request).await }).await
+    }
+
+    /// List available models of every backend, without duplicates
+    ///
+    /// A backend whose list cannot be fetched is skipped so one unreachable
+    /// provider does not hide the models of the others.
     pub async fn list_models(&self) -> Result<Vec<Model>> {
-        self.client.list_models().await
+        if self.backends.is_empty() {
+            return Err(anyhow!("No enabled providers configured"));
+        }
+
+        let mut models: Vec<Model> = Vec::new();
+        for backend in &self.backends {
+            match backend.client.list_models().await {
+                Ok(list) => {
+                    for model in list {
+                        if !models.iter().any(|m| m.id == model.id) {
+                            models.push(model);
+                        }
+                    }
+                }
+                Err(e) => warn!("⚠️ Failed to list models for provider '{}': {}", backend.name, e),
+            }
+        }
+        Ok(models)
+    }
+
+    /// Model metadata from models.yaml for every backend, without duplicates
+    pub fn model_infos(&self) -> Vec<ModelInfo> {
+        let mut infos: Vec<ModelInfo> = Vec::new();
+        for backend in &self.backends {
+            for info in backend.client.model_infos() {
+                if !infos.iter().any(|i| i.id == info.id) {
+                    infos.push(info);
+                }
+            }
+        }
+        infos
     }
 
     /// Validate if a model is available
     #[allow(dead_code)]
     pub async fn validate_model(&self, model: &str) -> Result<bool> {
-        let available_models = self.client.list_models().await?;
+        let available_models = self.list_models().await?;
         Ok(available_models.iter().any(|m| m.id == model))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::MockSettings;
+    use llm_connector::types::Message;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("glm-*", "glm-4-flash"));
+        assert!(glob_match("glm-*", "glm-"));
+        assert!(glob_match("qwen-?ax", "qwen-max"));
+        assert!(glob_match("*-turbo*", "kimi-k2-turbo-preview"));
+        assert!(glob_match("kimi-k2", "kimi-k2"));
+        assert!(!glob_match("glm-*", "qwen-max"));
+        assert!(!glob_match("kimi-k2", "kimi-k2-turbo-preview"));
+    }
+
+    fn routed_service() -> Service {
+        let mut service = Service::empty();
+        let zhipu = LlmBackendSettings::Zhipu {
+            api_key: "key".to_string(),
+            base_url: None,
+            model: "glm-4-flash".to_string(),
+        };
+        let aliyun = LlmBackendSettings::Aliyun {
+            api_key: "key".to_string(),
+            model: "qwen-max".to_string(),
+        };
+        let moonshot = LlmBackendSettings::Moonshot {
+            api_key: "key".to_string(),
+            model: "kimi-k2-turbo-preview".to_string(),
+        };
+        service.add_backend("zhipu", &zhipu, &["glm-*".to_string()]).unwrap();
+        service.add_backend("aliyun", &aliyun, &[]).unwrap();
+        service.add_backend("kimi", &moonshot, &["kimi-*".to_string()]).unwrap();
+        service
+    }
+
+    fn route_names(service: &Service, model: Option<&str>) -> Vec<(String, String)> {
+        service
+            .route(model, &RouteFilter::default())
+            .into_iter()
+            .map(|(backend, model)| (backend.name.clone(), model))
+            .collect()
+    }
+
+    #[test]
+    fn test_route_by_model_name() {
+        let service = routed_service();
+
+        // Glob pattern; backends that do not serve the model are not failover targets
+        let routed = route_names(&service, Some("kimi-k2"));
+        assert_eq!(routed, vec![("kimi".to_string(), "kimi-k2".to_string())]);
+
+        // Exact name from the configured model
+        assert_eq!(route_names(&service, Some("qwen-max"))[0].0, "aliyun");
+
+        // provider/model prefix, by backend name or provider type
+        assert_eq!(
+            route_names(&service, Some("kimi/moonshot-v1-8k"))[0],
+            ("kimi".to_string(), "moonshot-v1-8k".to_string())
+        );
+        assert_eq!(route_names(&service, Some("aliyun/qwen-plus"))[0].1, "qwen-plus");
+        assert_eq!(route_names(&service, Some("moonshot/kimi-latest"))[0].0, "kimi");
+
+        // Unknown models go to the primary backend unchanged
+        let routed = route_names(&service, Some("my-custom-model"));
+        assert_eq!(routed, vec![("zhipu".to_string(), "my-custom-model".to_string())]);
+
+        // No model: every backend with its own default
+        let routed = route_names(&service, None);
+        assert_eq!(routed[0].1, "glm-4-flash");
+        assert_eq!(routed[1], ("aliyun".to_string(), "qwen-max".to_string()));
+    }
+
+    fn mock(model: &str, error_status: Option<u16>) -> LlmBackendSettings {
+        LlmBackendSettings::Mock {
+            model: model.to_string(),
+            mock: MockSettings { error_status, ..Default::default() },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_backend_serving_model() {
+        let mut service = Service::empty();
+        service.add_backend("down", &mock("shared", Some(503)), &[]).unwrap();
+        service.add_backend("other", &mock("other", None), &[]).unwrap();
+        service.add_backend("up", &mock("shared", None), &[]).unwrap();
+        let messages = vec![Message::user("hi")];
+        let options = ChatOptions::default();
+
+        let served = service.chat(Some("shared"), messages.clone(), None, &options).await.unwrap();
+        assert_eq!(served.provider, "up");
+        assert_eq!(served.output.model, "shared");
+
+        // 流式请求在第一个块之前失败同样切换
+        let served = service
+            .chat_stream_openai(Some("shared"), messages.clone(), None, &options, StreamFormat::SSE)
+            .await
+            .unwrap();
+        assert_eq!(served.provider, "up");
+
+        // 只有失败的后端提供该模型时不换成别的模型
+        let mut service = Service::empty();
+        service.add_backend("down", &mock("shared", Some(503)), &[]).unwrap();
+        service.add_backend("other", &mock("other", None), &[]).unwrap();
+        match service.chat(Some("shared"), messages, None, &options).await {
+            Err(e) => assert!(is_failover_error(&e)),
+            Ok(served) => panic!("served by '{}' with model '{}'", served.provider, served.output.model),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_respects_route_filter() {
+        let mut service = Service::empty();
+        service.add_backend("down", &mock("shared", Some(503)), &[]).unwrap();
+        service.add_backend("up", &mock("shared", None), &[]).unwrap();
+        let messages = vec![Message::user("hi")];
+        let options = ChatOptions::default();
+        let only = |provider: &str| RouteFilter { providers: vec![provider.to_string()], ..Default::default() };
+
+        // 限定在失败后端的 key 不会切换到它无权使用的后端
+        let result = only("down").scope(service.chat(Some("shared"), messages.clone(), None, &options)).await;
+        match result {
+            Err(e) => assert!(is_failover_error(&e)),
+            Ok(served) => panic!("served by '{}'", served.provider),
+        }
+        let result = only("down")
+            .scope(service.chat_stream_openai(Some("shared"), messages.clone(), None, &options, StreamFormat::SSE))
+            .await;
+        assert!(result.is_err());
+
+        // 主后端不允许时，直接路由到允许的后端
+        let served = only("up").scope(service.chat(Some("shared"), messages.clone(), None, &options)).await.unwrap();
+        assert_eq!(served.provider, "up");
+        assert_eq!(only("up").scope(async { service.provider_for(Some("unknown-model")) }).await.as_deref(), Some("up"));
+        assert!(only("none").scope(service.chat(None, messages.clone(), None, &options)).await.is_err());
+
+        // 预算用完的后端同样不参与故障切换
+        let excluded = RouteFilter { excluded: vec!["up".to_string()], ..Default::default() };
+        assert_eq!(excluded.clone().scope(async { service.providers_for(Some("shared")) }).await, vec!["down"]);
+        assert!(excluded.scope(service.chat(Some("shared"), messages, None, &options)).await.is_err());
+    }
+}
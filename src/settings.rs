@@ -4,7 +4,13 @@ use serde::{Deserialize, Serialize};
 pub struct Settings {
     pub server: ServerSettings,
     pub llm_backend: LlmBackendSettings,
+    /// Extra backends served alongside `llm_backend`, selected by model name
+    #[serde(default)]
+    pub routes: Vec<BackendRouteSettings>,
     pub apis: ApiSettings,
+    /// Request, token and stream limits for the inference endpoints
+    #[serde(default)]
+    pub rate_limits: RateLimitSettings,
     pub client_adapters: Option<ClientAdapterSettings>,
 }
 
@@ -60,6 +66,35 @@ pub enum LlmBackendSettings {
         api_key: String,
         model: String,
     },
+    /// Offline backend that answers locally, for tests without network access
+    Mock {
+        model: String,
+        #[serde(flatten)]
+        mock: MockSettings,
+    },
+}
+
+/// Replies of the `mock` backend
+///
+/// Per-request directives in the last user message (`[mock:tool]`,
+/// `[mock:error=429]`, `[mock:disconnect=2]`) override these.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MockSettings {
+    /// Fixed reply; the last user message is echoed back when unset
+    #[serde(default)]
+    pub response: Option<String>,
+    /// Delay before the response and before each streamed chunk
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Characters per streamed chunk (8 when unset)
+    #[serde(default)]
+    pub chunk_size: Option<usize>,
+    /// Fail every request with this upstream status, e.g. 429 or 500
+    #[serde(default)]
+    pub error_status: Option<u16>,
+    /// Break every stream after this many chunks
+    #[serde(default)]
+    pub disconnect_after: Option<usize>,
 }
 
 impl LlmBackendSettings {
@@ -76,8 +111,113 @@ impl LlmBackendSettings {
             LlmBackendSettings::Longcat { model, .. } => model.clone(),
             LlmBackendSettings::Moonshot { model, .. } => model.clone(),
             LlmBackendSettings::Minimax { model, .. } => model.clone(),
+            LlmBackendSettings::Mock { model, .. } => model.clone(),
         }
     }
+
+    /// Get the provider name (lowercase, as used by CLI and models.yaml)
+    pub fn provider_name(&self) -> &'static str {
+        match self {
+            LlmBackendSettings::OpenAI { .. } => "openai",
+            LlmBackendSettings::Anthropic { .. } => "anthropic",
+            LlmBackendSettings::Ollama { .. } => "ollama",
+            LlmBackendSettings::Zhipu { .. } => "zhipu",
+            LlmBackendSettings::Aliyun { .. } => "aliyun",
+            LlmBackendSettings::Volcengine { .. } => "volcengine",
+            LlmBackendSettings::Tencent { .. } => "tencent",
+            LlmBackendSettings::Longcat { .. } => "longcat",
+            LlmBackendSettings::Moonshot { .. } => "moonshot",
+            LlmBackendSettings::Minimax { .. } => "minimax",
+            LlmBackendSettings::Mock { .. } => "mock",
+        }
+    }
+
+    /// Build backend settings from a provider type and its JSON config
+    ///
+    /// Used by multi mode, where providers are stored as `(type, config)` rows
+    /// with a lowercase type such as `openai` and a config like
+    /// `{"api_key": "...", "model": "...", "base_url": "..."}`.
+    pub fn from_provider_config(provider_type: &str, config: &serde_json::Value) -> anyhow::Result<Self> {
+        let tag = match provider_type.to_lowercase().as_str() {
+            "openai" => "OpenAI",
+            "anthropic" => "Anthropic",
+            "ollama" => "Ollama",
+            "zhipu" => "Zhipu",
+            "aliyun" => "Aliyun",
+            "volcengine" => "Volcengine",
+            "tencent" => "Tencent",
+            "longcat" => "Longcat",
+            "moonshot" => "Moonshot",
+            "minimax" => "Minimax",
+            "mock" => "Mock",
+            other => anyhow::bail!("Unsupported provider type: {}", other),
+        };
+
+        let mut fields = config
+            .as_object()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Provider config must be a JSON object"))?;
+        fields.insert("type".to_string(), serde_json::Value::String(tag.to_string()));
+
+        serde_json::from_value(serde_json::Value::Object(fields))
+            .map_err(|e| anyhow::anyhow!("Invalid {} provider config: {}", provider_type, e))
+    }
+}
+
+/// A named backend that serves a set of models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendRouteSettings {
+    /// Name used for `name/model` prefixes and in the provider response header
+    pub name: String,
+    /// Model names or glob patterns (e.g. `glm-*`) routed to this backend.
+    /// Models listed for the provider in models.yaml are routed here as well.
+    #[serde(default)]
+    pub models: Vec<String>,
+    pub backend: LlmBackendSettings,
+}
+
+/// Token-bucket limits; unset fields are unlimited
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_minute: Option<u32>,
+    /// Prompt tokens, estimated from the request before it is sent
+    pub tokens_per_minute: Option<u32>,
+    pub max_concurrent_streams: Option<u32>,
+}
+
+impl RateLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.requests_per_minute.is_none() && self.tokens_per_minute.is_none() && self.max_concurrent_streams.is_none()
+    }
+
+    /// Fields of `self`, falling back to `default` where unset
+    pub fn or(&self, default: &RateLimit) -> RateLimit {
+        RateLimit {
+            requests_per_minute: self.requests_per_minute.or(default.requests_per_minute),
+            tokens_per_minute: self.tokens_per_minute.or(default.tokens_per_minute),
+            max_concurrent_streams: self.max_concurrent_streams.or(default.max_concurrent_streams),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitSettings {
+    /// Shared by all requests
+    #[serde(default)]
+    pub global: RateLimit,
+    /// Applied to each API key separately; a key's own limits take precedence
+    #[serde(default)]
+    pub per_key: RateLimit,
+    /// Limits for model names or glob patterns, shared by all matching models
+    #[serde(default)]
+    pub models: Vec<ModelRateLimit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelRateLimit {
+    pub model: String,
+    #[serde(flatten)]
+    pub limit: RateLimit,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -95,6 +235,23 @@ pub struct ClientAdapterSettings {
     pub force_adapter: Option<String>,
     /// Zed.dev specific configuration
     pub zed: Option<ZedAdapterSettings>,
+    /// How reasoning / thinking content is returned to the client
+    #[serde(default)]
+    pub reasoning_mode: Option<ReasoningMode>,
+}
+
+/// How reasoning (`reasoning_content`, `<think>` blocks) reaches the client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningMode {
+    /// Drop reasoning, return only the answer
+    #[default]
+    Strip,
+    /// Keep reasoning inline in the content as `<think>...</think>`
+    Passthrough,
+    /// Return reasoning in the protocol's own field: OpenAI `reasoning_content`,
+    /// Anthropic `thinking` blocks, Ollama `message.thinking`
+    Native,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -130,6 +287,7 @@ pub struct AnthropicApiSettings {
     pub enabled: bool,
     pub path: String,
     pub api_key_header: Option<String>,
+    pub api_key: Option<String>,
 }
 
 impl Default for Settings {
@@ -144,6 +302,7 @@ impl Default for Settings {
                 base_url: Some("http://localhost:11434".to_string()),
                 model: "llama2".to_string(),
             },
+            routes: Vec::new(),
             apis: ApiSettings {
                 ollama: Some(OllamaApiSettings {
                     enabled: true,
@@ -161,8 +320,10 @@ impl Default for Settings {
                     enabled: true,
                     path: "/anthropic".to_string(),
                     api_key_header: None,
+                    api_key: None,
                 }),
             },
+            rate_limits: RateLimitSettings::default(),
             client_adapters: None,
         }
     }
@@ -171,5 +332,13 @@ impl Default for Settings {
 impl Settings {
     // Settings are now generated by AppConfigGenerator only
     // No file-based configuration loading needed
+
+    /// Reasoning mode configured for the app, used when a request does not choose one
+    pub fn reasoning_mode(&self) -> ReasoningMode {
+        self.client_adapters
+            .as_ref()
+            .and_then(|adapters| adapters.reasoning_mode)
+            .unwrap_or_default()
+    }
 }
 
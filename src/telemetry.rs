@@ -0,0 +1,278 @@
+use crate::api::inspect::RequestInfo;
+use anyhow::Result;
+use axum::{extract::Request, middleware::Next, response::Response};
+use once_cell::sync::OnceCell;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{Array, StringValue, Value};
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::{field, Instrument, Level, Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+static PROVIDER: OnceCell<SdkTracerProvider> = OnceCell::new();
+
+/// Whether spans are exported (`--otlp-endpoint` was given)
+pub fn is_enabled() -> bool {
+    PROVIDER.get().is_some()
+}
+
+/// OTLP/HTTP (JSON) trace exporter for a collector at `endpoint`
+///
+/// `endpoint` is the collector's base URL; `/v1/traces` is appended unless
+/// the path is already there. Spans are exported in batches from a
+/// background thread.
+pub fn tracer_provider(endpoint: &str) -> Result<SdkTracerProvider> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    };
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpJson)
+        .with_endpoint(endpoint)
+        .build()?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name("llm-link")
+                .with_attribute(opentelemetry::KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+                .build(),
+        )
+        .build())
+}
+
+/// `tracing` layer that exports spans through `provider`
+///
+/// Only spans (info and above) and warnings / errors are exported, so the
+/// per-chunk logs of streams do not end up as span events.
+pub fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("llm-link"))
+        .with_filter(filter_fn(|metadata| {
+            // 避免导出器自身的日志再被导出
+            if metadata.target().starts_with("opentelemetry") {
+                return false;
+            }
+            let max = if metadata.is_span() { Level::INFO } else { Level::WARN };
+            *metadata.level() <= max
+        }))
+}
+
+/// Set up OTLP export to `endpoint` and return the layer to install
+pub fn init<S>(endpoint: &str) -> Result<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let provider = tracer_provider(endpoint)?;
+    let layer = layer(&provider);
+    let _ = PROVIDER.set(provider);
+    Ok(layer)
+}
+
+/// GenAI operation of an inference endpoint
+fn operation_name(path: &str) -> &'static str {
+    if path.ends_with("/embeddings") || path.ends_with("/embed") {
+        "embeddings"
+    } else if path.ends_with("/generate") {
+        "text_completion"
+    } else {
+        "chat"
+    }
+}
+
+/// Span of one upstream call, `{operation} {model}`
+///
+/// Usage and finish reasons are filled in with [`record_usage`] and
+/// [`record_finish_reasons`] once the response is known.
+pub fn upstream_span(system: &str, operation: &str, model: &str) -> Span {
+    tracing::info_span!(
+        "gen_ai.upstream",
+        otel.name = %format!("{} {}", operation, model),
+        otel.kind = "client",
+        otel.status_code = field::Empty,
+        gen_ai.operation.name = operation,
+        gen_ai.system = system,
+        gen_ai.request.model = model,
+        gen_ai.request.max_tokens = field::Empty,
+        gen_ai.request.temperature = field::Empty,
+        gen_ai.response.model = field::Empty,
+        gen_ai.usage.input_tokens = field::Empty,
+        gen_ai.usage.output_tokens = field::Empty,
+        error.type = field::Empty,
+    )
+}
+
+/// Span of a streamed response, from the first chunk until the stream ends
+pub fn stream_span(system: &str, model: &str) -> Span {
+    tracing::info_span!(
+        "gen_ai.stream",
+        otel.status_code = field::Empty,
+        gen_ai.system = system,
+        gen_ai.request.model = model,
+        gen_ai.usage.input_tokens = field::Empty,
+        gen_ai.usage.output_tokens = field::Empty,
+        llm_link.stream.chunks = field::Empty,
+        error.type = field::Empty,
+    )
+}
+
+/// Token counts are recorded as `i64`: unsigned `tracing` values would be
+/// exported as strings
+pub fn record_usage(span: &Span, input_tokens: u32, output_tokens: u32) {
+    span.record("gen_ai.usage.input_tokens", i64::from(input_tokens));
+    span.record("gen_ai.usage.output_tokens", i64::from(output_tokens));
+}
+
+/// `gen_ai.response.finish_reasons` is a string array, which `tracing`
+/// fields cannot hold, so it is set on the OpenTelemetry span directly
+pub fn record_finish_reasons<'a>(span: &Span, reasons: impl IntoIterator<Item = &'a str>) {
+    let reasons: Vec<StringValue> = reasons.into_iter().map(|reason| reason.to_string().into()).collect();
+    span.set_attribute("gen_ai.response.finish_reasons", Value::Array(Array::String(reasons)));
+}
+
+pub fn record_error(span: &Span, error: &anyhow::Error) {
+    span.record("otel.status_code", "ERROR");
+    span.record("error.type", crate::api::error_class(error));
+}
+
+/// The upstream stream failed after it was opened
+pub fn record_stream_error(span: &Span) {
+    span.record("otel.status_code", "ERROR");
+    span.record("error.type", "stream");
+}
+
+/// Tracing middleware for the inference endpoints of all frontends
+///
+/// Opens the server span of a request; conversion, model resolution, the
+/// upstream call and the stream become its children. A no-op unless OTLP
+/// export is enabled.
+pub async fn trace(request: Request, next: Next) -> Response {
+    if !is_enabled() {
+        return next.run(request).await;
+    }
+    let Some(info) = RequestInfo::inference(&request) else {
+        return next.run(request).await;
+    };
+
+    let (method, path) = (request.method(), request.uri().path());
+    let span = tracing::info_span!(
+        "gen_ai.request",
+        otel.name = %format!("{} {}", method, path),
+        otel.kind = "server",
+        otel.status_code = field::Empty,
+        http.request.method = %method,
+        url.path = path,
+        http.response.status_code = field::Empty,
+        llm_link.frontend = info.frontend.as_str(),
+        gen_ai.operation.name = operation_name(path),
+        gen_ai.request.model = info.model.as_deref().unwrap_or("unknown"),
+    );
+
+    let response = next.run(request).instrument(span.clone()).await;
+    span.record("http.response.status_code", i64::from(response.status().as_u16()));
+    if response.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalizer::Client;
+    use crate::settings::LlmBackendSettings;
+    use axum::{routing::post, Json, Router};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Attributes of an exported OTLP/JSON span as `key -> value`
+    fn attribute<'a>(span: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+        span["attributes"]
+            .as_array()?
+            .iter()
+            .find(|attribute| attribute["key"] == key)
+            .map(|attribute| &attribute["value"])
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_spans_exported_to_otlp_receiver() {
+        // 进程内的 OTLP/HTTP 接收端
+        let received: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+        let sink = received.clone();
+        let receiver = Router::new().route(
+            "/v1/traces",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let sink = sink.clone();
+                async move {
+                    sink.lock().unwrap().push(body);
+                    Json(json!({}))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let provider = tracer_provider(&format!("http://{}", addr)).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let client = Client::new(&LlmBackendSettings::Ollama { base_url: None, model: "llama3".to_string() }).unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("gen_ai.request", otel.kind = "server");
+            let _entered = request.enter();
+
+            crate::api::convert::openai_messages_to_llm(vec![json!({"role": "user", "content": "hi"})]).unwrap();
+            assert_eq!(client.resolve_model("llama3", "llama3"), "llama3");
+
+            let upstream = upstream_span("ollama", "chat", "llama3");
+            record_usage(&upstream, 12, 5);
+            record_finish_reasons(&upstream, ["stop"]);
+        });
+
+        let flushed = provider.clone();
+        tokio::task::spawn_blocking(move || flushed.force_flush()).await.unwrap().unwrap();
+
+        let spans: Vec<serde_json::Value> = received
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|body| body["resourceSpans"].as_array().cloned().unwrap_or_default())
+            .flat_map(|resource| resource["scopeSpans"].as_array().cloned().unwrap_or_default())
+            .flat_map(|scope| scope["spans"].as_array().cloned().unwrap_or_default())
+            .collect();
+        let span = |name: &str| {
+            spans
+                .iter()
+                .find(|span| span["name"] == name)
+                .unwrap_or_else(|| panic!("span {} not exported: {:?}", name, spans))
+        };
+
+        let request = span("gen_ai.request");
+        let upstream = span("chat llama3");
+        assert_eq!(upstream["kind"], 3); // SPAN_KIND_CLIENT
+        assert_eq!(upstream["parentSpanId"], request["spanId"]);
+        assert_eq!(span("openai_messages_to_llm")["parentSpanId"], request["spanId"]);
+        assert_eq!(attribute(upstream, "gen_ai.system").unwrap()["stringValue"], "ollama");
+        assert_eq!(attribute(upstream, "gen_ai.usage.input_tokens").unwrap()["intValue"], "12");
+        assert_eq!(attribute(upstream, "gen_ai.usage.output_tokens").unwrap()["intValue"], "5");
+        assert_eq!(
+            attribute(upstream, "gen_ai.response.finish_reasons").unwrap()["arrayValue"]["values"][0]["stringValue"],
+            "stop"
+        );
+        let resolve = span("resolve_model");
+        assert_eq!(attribute(resolve, "gen_ai.system").unwrap()["stringValue"], "ollama");
+        assert_eq!(attribute(resolve, "llm_link.resolved_model").unwrap()["stringValue"], "llama3");
+    }
+}
@@ -0,0 +1,67 @@
+use super::{is_wide_char, TokenCounter};
+
+/// Calibrated character-ratio counter for tokenizers we do not embed
+///
+/// Latin text is counted by characters per token and CJK text by tokens per
+/// character, which is where tokenizers of different families differ most.
+#[derive(Debug, Clone, Copy)]
+pub struct HeuristicCounter {
+    chars_per_token: f32,
+    tokens_per_wide_char: f32,
+    message_overhead: u32,
+    tools_overhead: u32,
+}
+
+impl HeuristicCounter {
+    pub const fn new(chars_per_token: f32, tokens_per_wide_char: f32) -> Self {
+        Self {
+            chars_per_token,
+            tokens_per_wide_char,
+            message_overhead: 4,
+            tools_overhead: 12,
+        }
+    }
+
+    pub const fn with_overheads(mut self, message_overhead: u32, tools_overhead: u32) -> Self {
+        self.message_overhead = message_overhead;
+        self.tools_overhead = tools_overhead;
+        self
+    }
+}
+
+impl TokenCounter for HeuristicCounter {
+    fn count_text(&self, text: &str) -> u32 {
+        let (wide, other) = text.chars().fold((0u32, 0u32), |(wide, other), c| {
+            if is_wide_char(c) {
+                (wide + 1, other)
+            } else {
+                (wide, other + 1)
+            }
+        });
+
+        let tokens = wide as f32 * self.tokens_per_wide_char + other as f32 / self.chars_per_token;
+        tokens.ceil() as u32
+    }
+
+    fn message_overhead(&self) -> u32 {
+        self.message_overhead
+    }
+
+    fn tools_overhead(&self) -> u32 {
+        self.tools_overhead
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heuristic_count() {
+        let counter = HeuristicCounter::new(4.0, 0.5);
+        assert_eq!(counter.count_text(""), 0);
+        assert_eq!(counter.count_text("abcdefgh"), 2);
+        assert_eq!(counter.count_text("你好世界"), 2);
+        assert_eq!(counter.count_text("abcd你好"), 2);
+    }
+}
@@ -0,0 +1,208 @@
+//! Token counting
+//!
+//! Providers bill with different tokenizers, so counting is done by a
+//! [`TokenCounter`] chosen from the model name. The same counters back the
+//! Anthropic `count_tokens` endpoint, usage estimates for providers that do
+//! not report usage, and context-length checks before a request is sent.
+
+mod heuristic;
+mod openai;
+
+pub use heuristic::HeuristicCounter;
+pub use openai::{Encoding, OpenAiCounter};
+
+use llm_connector::types::{Message, MessageBlock, Tool};
+
+/// Counts tokens the way a model family bills them
+pub trait TokenCounter: Send + Sync {
+    /// Tokens in a piece of plain text
+    fn count_text(&self, text: &str) -> u32;
+
+    /// Tokens added per message for role markers and separators
+    fn message_overhead(&self) -> u32 {
+        4
+    }
+
+    /// Tokens that prime the assistant reply
+    fn reply_overhead(&self) -> u32 {
+        3
+    }
+
+    /// Fixed cost of sending tools (the injected tool-use prompt)
+    fn tools_overhead(&self) -> u32 {
+        12
+    }
+
+    /// Flat cost of an image block
+    fn image_tokens(&self) -> u32 {
+        85
+    }
+
+    /// Tokens of a conversation, including the system prompt if it is one of the messages
+    fn count_messages(&self, messages: &[Message]) -> u32 {
+        let content: u32 = messages
+            .iter()
+            .map(|message| {
+                let blocks: u32 = message
+                    .content
+                    .iter()
+                    .map(|block| match block {
+                        MessageBlock::Text { text } => self.count_text(text),
+                        _ => self.image_tokens(),
+                    })
+                    .sum();
+                let tool_calls: u32 = message
+                    .tool_calls
+                    .iter()
+                    .flatten()
+                    .map(|call| self.count_text(&call.function.name) + self.count_text(&call.function.arguments))
+                    .sum();
+                self.message_overhead() + blocks + tool_calls
+            })
+            .sum();
+
+        content + self.reply_overhead()
+    }
+
+    /// Tokens of the tool definitions sent with a request
+    fn count_tools(&self, tools: &[Tool]) -> u32 {
+        if tools.is_empty() {
+            return 0;
+        }
+        let definitions: u32 = tools
+            .iter()
+            .map(|tool| {
+                self.count_text(&tool.function.name)
+                    + tool.function.description.as_deref().map_or(0, |d| self.count_text(d))
+                    + self.count_text(&tool.function.parameters.to_string())
+                    + 8
+            })
+            .sum();
+        self.tools_overhead() + definitions
+    }
+
+    /// Prompt tokens of a full request
+    fn count_request(&self, messages: &[Message], tools: Option<&[Tool]>) -> u32 {
+        self.count_messages(messages) + tools.map_or(0, |tools| self.count_tools(tools))
+    }
+}
+
+/// Tokenizer families with distinct billing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerFamily {
+    /// OpenAI cl100k_base
+    Cl100k,
+    /// OpenAI o200k_base
+    O200k,
+    Claude,
+    Glm,
+    Qwen,
+    Doubao,
+    Generic,
+}
+
+static CL100K: OpenAiCounter = OpenAiCounter::new(Encoding::Cl100k);
+static O200K: OpenAiCounter = OpenAiCounter::new(Encoding::O200k);
+// Anthropic 的 tokenizer 未公开；工具调用的系统提示固定开销约 346 tokens
+static CLAUDE: HeuristicCounter = HeuristicCounter::new(3.5, 1.2).with_overheads(3, 346);
+// GLM / Qwen / Doubao 的中文压缩率明显高于 cl100k
+static GLM: HeuristicCounter = HeuristicCounter::new(4.0, 0.65);
+static QWEN: HeuristicCounter = HeuristicCounter::new(4.0, 0.7);
+static DOUBAO: HeuristicCounter = HeuristicCounter::new(4.0, 0.6);
+static GENERIC: HeuristicCounter = HeuristicCounter::new(4.0, 1.0);
+
+impl TokenizerFamily {
+    /// Guess the family from a model name (`provider/model` prefixes are ignored)
+    pub fn from_model(model: &str) -> Self {
+        let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+
+        if name.contains("claude") {
+            TokenizerFamily::Claude
+        } else if name.contains("glm") {
+            TokenizerFamily::Glm
+        } else if name.contains("qwen") {
+            TokenizerFamily::Qwen
+        } else if name.contains("doubao") {
+            TokenizerFamily::Doubao
+        } else if ["gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5", "o1", "o3", "o4", "chatgpt"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            TokenizerFamily::O200k
+        } else if name.starts_with("gpt") || name.starts_with("text-embedding") {
+            TokenizerFamily::Cl100k
+        } else {
+            TokenizerFamily::Generic
+        }
+    }
+
+    pub fn counter(self) -> &'static dyn TokenCounter {
+        match self {
+            TokenizerFamily::Cl100k => &CL100K,
+            TokenizerFamily::O200k => &O200K,
+            TokenizerFamily::Claude => &CLAUDE,
+            TokenizerFamily::Glm => &GLM,
+            TokenizerFamily::Qwen => &QWEN,
+            TokenizerFamily::Doubao => &DOUBAO,
+            TokenizerFamily::Generic => &GENERIC,
+        }
+    }
+}
+
+/// Token counter for a model name
+pub fn counter_for_model(model: &str) -> &'static dyn TokenCounter {
+    TokenizerFamily::from_model(model).counter()
+}
+
+/// Whether a character is usually encoded on its own (CJK, kana, hangul, full-width)
+pub(crate) fn is_wide_char(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x303F     // CJK punctuation
+        | 0x3040..=0x30FF   // Hiragana / Katakana
+        | 0x3400..=0x4DBF   // CJK Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF   // Hangul
+        | 0xF900..=0xFAFF   // CJK Compatibility Ideographs
+        | 0xFF00..=0xFFEF   // Full-width forms
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_family_from_model() {
+        assert_eq!(TokenizerFamily::from_model("gpt-4"), TokenizerFamily::Cl100k);
+        assert_eq!(TokenizerFamily::from_model("gpt-4o"), TokenizerFamily::O200k);
+        assert_eq!(TokenizerFamily::from_model("openai/o3-mini"), TokenizerFamily::O200k);
+        assert_eq!(TokenizerFamily::from_model("claude-3-5-sonnet-20241022"), TokenizerFamily::Claude);
+        assert_eq!(TokenizerFamily::from_model("glm-4-flash"), TokenizerFamily::Glm);
+        assert_eq!(TokenizerFamily::from_model("qwen-max"), TokenizerFamily::Qwen);
+        assert_eq!(TokenizerFamily::from_model("doubao-pro-32k"), TokenizerFamily::Doubao);
+        assert_eq!(TokenizerFamily::from_model("llama2"), TokenizerFamily::Generic);
+    }
+
+    #[test]
+    fn test_count_request() {
+        let counter = counter_for_model("gpt-4");
+        let messages = vec![Message::system("Be brief."), Message::user("hello world")];
+        // 2 messages * 4 overhead + "Be brief." (3) + "hello world" (2) + 3 reply priming
+        assert_eq!(counter.count_messages(&messages), 8 + 3 + 2 + 3);
+
+        let tool = Tool {
+            tool_type: "function".to_string(),
+            function: llm_connector::types::Function {
+                name: "get_weather".to_string(),
+                description: Some("Get the weather".to_string()),
+                parameters: serde_json::json!({"type": "object"}),
+            },
+        };
+        let with_tools = counter.count_request(&messages, Some(std::slice::from_ref(&tool)));
+        assert!(with_tools > counter.count_messages(&messages) + counter.tools_overhead());
+
+        // 中文在 GLM 上比 cl100k 更省 token
+        let chinese = "今天天气怎么样，适合出去散步吗";
+        assert!(counter_for_model("glm-4").count_text(chinese) < counter.count_text(chinese));
+    }
+}
@@ -0,0 +1,59 @@
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton, CoreBPE};
+
+use super::TokenCounter;
+
+/// BPE encoding of an OpenAI model family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// GPT-3.5, GPT-4 and the text-embedding-3 models
+    Cl100k,
+    /// GPT-4o, GPT-4.1, GPT-5 and the o-series
+    O200k,
+}
+
+/// Counter for OpenAI models, with tiktoken's rank tables
+#[derive(Debug, Clone, Copy)]
+pub struct OpenAiCounter {
+    encoding: Encoding,
+}
+
+impl OpenAiCounter {
+    pub const fn new(encoding: Encoding) -> Self {
+        Self { encoding }
+    }
+
+    fn bpe(&self) -> &'static CoreBPE {
+        match self.encoding {
+            Encoding::Cl100k => cl100k_base_singleton(),
+            Encoding::O200k => o200k_base_singleton(),
+        }
+    }
+}
+
+impl TokenCounter for OpenAiCounter {
+    fn count_text(&self, text: &str) -> u32 {
+        // 用户文本里的 <|endoftext|> 等按普通文本编码
+        self.bpe().encode_ordinary(text).len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_count() {
+        let cl100k = OpenAiCounter::new(Encoding::Cl100k);
+        assert_eq!(cl100k.count_text(""), 0);
+        assert_eq!(cl100k.count_text("hello world"), 2);
+        assert_eq!(cl100k.count_text("Hello, world!"), 4);
+        assert_eq!(cl100k.count_text("12345"), 2);
+        assert_eq!(cl100k.count_text("I don't know"), 4);
+        assert_eq!(cl100k.count_text("今天天气怎么样"), 9);
+
+        let o200k = OpenAiCounter::new(Encoding::O200k);
+        assert_eq!(o200k.count_text("Hello, world!"), 4);
+        assert_eq!(o200k.count_text("今天天气怎么样"), 4);
+        assert_eq!(o200k.count_text("<|endoftext|>"), 7);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::api::auth::KeyScope;
+use crate::api::inspect::RequestInfo;
+use crate::api::{AppState, PROVIDER_HEADER};
+use crate::cache::CACHE_HEADER;
+use crate::db::{DatabasePool, NewUsage};
+use crate::models::ModelsConfig;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::time::Instant;
+use tracing::error;
+
+/// Model prices, loaded once from `models.yaml`
+static MODELS_CONFIG: Lazy<ModelsConfig> = Lazy::new(ModelsConfig::load_with_fallback);
+
+/// Token counts and errors reported in a response body
+///
+/// Bodies are scanned line by line, which covers both plain JSON responses
+/// and the SSE / NDJSON streams of all three frontends.
+#[derive(Debug, Default)]
+pub(crate) struct UsageScanner {
+    line: Vec<u8>,
+    pub(crate) prompt_tokens: Option<u64>,
+    pub(crate) completion_tokens: Option<u64>,
+    pub(crate) failed: bool,
+}
+
+impl UsageScanner {
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                self.scan_line(&line);
+            } else {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.scan_line(&line);
+    }
+
+    fn scan_line(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        let line = line.strip_prefix("data:").map(str::trim_start).unwrap_or(line);
+        if !["usage", "eval_count", "error"].iter().any(|field| line.contains(field)) {
+            return;
+        }
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+
+        if value.get("error").is_some_and(|e| !e.is_null()) || value["type"] == "error" {
+            self.failed = true;
+        }
+        // OpenAI / Anthropic usage (message_start 把 usage 放在 message 里), Ollama counters
+        for usage in [&value["usage"], &value["message"]["usage"]] {
+            Self::update(&mut self.prompt_tokens, &usage["prompt_tokens"]);
+            Self::update(&mut self.prompt_tokens, &usage["input_tokens"]);
+            Self::update(&mut self.completion_tokens, &usage["completion_tokens"]);
+            Self::update(&mut self.completion_tokens, &usage["output_tokens"]);
+        }
+        Self::update(&mut self.prompt_tokens, &value["prompt_eval_count"]);
+        Self::update(&mut self.completion_tokens, &value["eval_count"]);
+    }
+
+    /// Streams repeat or grow their counts, so keep the largest
+    fn update(slot: &mut Option<u64>, value: &Value) {
+        if let Some(tokens) = value.as_u64() {
+            *slot = Some(slot.map_or(tokens, |current| current.max(tokens)));
+        }
+    }
+}
+
+/// Usage of a request whose response body is still being sent
+///
+/// Written to the database when the body is dropped, i.e. once the stream
+/// has finished or the client went away.
+struct PendingUsage {
+    db: DatabasePool,
+    usage: NewUsage,
+    estimated_prompt_tokens: u32,
+    started: Instant,
+    scanner: UsageScanner,
+    /// Served from the response cache, so nothing was spent upstream
+    cached: bool,
+}
+
+impl Drop for PendingUsage {
+    fn drop(&mut self) {
+        self.scanner.finish();
+        let mut usage = self.usage.clone();
+        usage.latency_ms = self.started.elapsed().as_millis() as u64;
+        // OpenAI streams only report usage with `stream_options.include_usage`;
+        // requests rejected before reaching the provider cost nothing
+        let estimated = if usage.status < 400 { self.estimated_prompt_tokens.into() } else { 0 };
+        usage.prompt_tokens = self.scanner.prompt_tokens.unwrap_or(estimated);
+        usage.completion_tokens = self.scanner.completion_tokens.unwrap_or(0);
+        if self.scanner.failed && usage.status < 400 {
+            usage.status = StatusCode::INTERNAL_SERVER_ERROR.as_u16();
+        }
+        usage.cost = if self.cached {
+            Some(0.0)
+        } else {
+            usage
+                .model
+                .as_deref()
+                .and_then(|model| MODELS_CONFIG.pricing(model))
+                .map(|pricing| pricing.cost(usage.prompt_tokens, usage.completion_tokens))
+        };
+
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let db = self.db.clone();
+        handle.spawn(async move {
+            if let Err(e) = db.record_usage(usage).await {
+                error!("❌ Failed to record usage: {}", e);
+            }
+        });
+    }
+}
+
+/// Usage accounting middleware for the inference endpoints of all frontends
+///
+/// Records key, provider, model, token counts, latency, status and cost of
+/// every request in the `usage` table. Only active with a database (multi mode).
+pub async fn record(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(db) = state.db.clone() else {
+        return next.run(request).await;
+    };
+    let Some(info) = RequestInfo::inference(&request) else {
+        return next.run(request).await;
+    };
+    let (frontend, model, estimated_prompt_tokens) = (info.frontend, info.model.clone(), info.prompt_tokens);
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    let provider = response
+        .headers()
+        .get(PROVIDER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let key_name = response.extensions().get::<KeyScope>().map(|scope| scope.name.clone());
+    let cached = response.headers().get(CACHE_HEADER).is_some_and(|value| value == "hit");
+    let mut pending = PendingUsage {
+        db,
+        usage: NewUsage {
+            key_name,
+            frontend: frontend.as_str().to_string(),
+            provider,
+            model,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            latency_ms: 0,
+            status: response.status().as_u16(),
+            cost: None,
+        },
+        estimated_prompt_tokens,
+        started,
+        scanner: UsageScanner::default(),
+        cached,
+    };
+
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                pending.scanner.push(bytes);
+            }
+            chunk
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_scanner() {
+        // OpenAI stream, usage split across chunks
+        let mut scanner = UsageScanner::default();
+        scanner.push(b"data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\ndata: {\"choices\":[],\"usa");
+        scanner.push(b"ge\":{\"prompt_tokens\":20,\"completion_tokens\":7}}\n\ndata: [DONE]\n\n");
+        scanner.finish();
+        assert_eq!((scanner.prompt_tokens, scanner.completion_tokens), (Some(20), Some(7)));
+        assert!(!scanner.failed);
+
+        // Anthropic stream
+        let mut scanner = UsageScanner::default();
+        scanner.push(b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n");
+        scanner.push(b"event: message_delta\ndata: {\"type\":\"message_delta\",\"usage\":{\"input_tokens\":12,\"output_tokens\":30}}\n\n");
+        scanner.finish();
+        assert_eq!((scanner.prompt_tokens, scanner.completion_tokens), (Some(12), Some(30)));
+
+        // Ollama non-stream response without a trailing newline
+        let mut scanner = UsageScanner::default();
+        scanner.push(b"{\"done\":true,\"prompt_eval_count\":5,\"eval_count\":9}");
+        scanner.finish();
+        assert_eq!((scanner.prompt_tokens, scanner.completion_tokens), (Some(5), Some(9)));
+
+        // Error reported inside a 200 stream
+        let mut scanner = UsageScanner::default();
+        scanner.push(b"data: {\"error\":{\"message\":\"upstream failed\"}}\n\n");
+        scanner.finish();
+        assert!(scanner.failed);
+        assert_eq!(scanner.prompt_tokens, None);
+    }
+}